@@ -59,6 +59,8 @@ LICENSE.txt: $(S)COPYRIGHT $(S)LICENSE-APACHE $(S)LICENSE-MIT
 $(PKG_EXE): rust.iss modpath.iss LICENSE.txt rust-logo.ico \
             $(PKG_FILES) $(CSREQ3_T_$(CFG_BUILD)_H_$(CFG_BUILD))
 	$(CFG_PYTHON) $(S)src/etc/copy-runtime-deps.py i686-pc-mingw32/stage3/bin
+	$(CFG_PYTHON) $(S)src/etc/copy-mingw-runtime.py $(CC_i686-pc-mingw32) \
+	    i686-pc-mingw32/stage3/$(CFG_LIBDIR)/rustlib/i686-pc-mingw32/mingw/lib
 	@$(call E, ISCC: $@)
 	$(Q)"$(CFG_ISCC)" $<
 endif
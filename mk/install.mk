@@ -74,7 +74,7 @@ TB$(1)$(2) = $$(TBIN$$(ISTAGE)_T_$(1)_H_$(2))
 TL$(1)$(2) = $$(TLIB$$(ISTAGE)_T_$(1)_H_$(2))
 
 # PT{R,B,L} == Prefix Target {Root, Bin, Lib}
-PTR$(1)$(2) = $$(PREFIX_LIB)/rustc/$(1)
+PTR$(1)$(2) = $$(PREFIX_LIB)/rustlib/$(1)
 PTB$(1)$(2) = $$(PTR$(1)$(2))/bin
 PTL$(1)$(2) = $$(PTR$(1)$(2))/$(CFG_LIBDIR)
 
@@ -153,6 +153,8 @@ install-host: $(CSREQ$(ISTAGE)_T_$(CFG_BUILD_)_H_$(CFG_BUILD_))
 	$(Q)$(call INSTALL_LIB,$(LIBSYNTAX_GLOB_$(CFG_BUILD)))
 	$(Q)$(call INSTALL_LIB,$(LIBRUSTPKG_GLOB_$(CFG_BUILD)))
 	$(Q)$(call INSTALL_LIB,$(LIBRUSTDOC_GLOB_$(CFG_BUILD)))
+	$(Q)$(call MK_INSTALL_DIR,$(PHL)/rustlib/etc)
+	$(Q)$(call INSTALL,$(S)src/etc,$(PHL)/rustlib/etc,gdb_rust_pretty_printing.py)
 	$(Q)$(call INSTALL,$(S)/man,$(CFG_MANDIR)/man1,rustc.1)
 	$(Q)$(call INSTALL,$(S)/man,$(CFG_MANDIR)/man1,rustdoc.1)
 	$(Q)$(call INSTALL,$(S)/man,$(CFG_MANDIR)/man1,rustpkg.1)
@@ -181,7 +183,7 @@ uninstall:
         ; \
         do rm -f $$i ; \
         done
-	$(Q)rm -Rf $(PHL)/rustc
+	$(Q)rm -Rf $(PHL)/rustlib
 	$(Q)rm -f $(CFG_MANDIR)/man1/rustc.1
 	$(Q)rm -f $(CFG_MANDIR)/man1/rustdoc.1
 	$(Q)rm -f $(CFG_MANDIR)/man1/rusti.1
@@ -0,0 +1,48 @@
+#!/usr/bin/env python
+# xfail-license
+
+# Copies the startup objects and runtime libraries of mingw which Rust
+# programs are linked with into the specified directory, where rustc links
+# them from in place of those of the installed gcc.
+#
+# usage: copy-mingw-runtime.py CC DEST_DIR
+
+import os, shutil, subprocess, sys
+
+mingw_runtime = [
+    "crt2.o",
+    "dllcrt2.o",
+    "crtbegin.o",
+    "crtend.o",
+    "libgcc.a",
+    "libgcc_eh.a",
+    "libstdc++.a",
+    "libmingw32.a",
+    "libmingwex.a",
+    "libmoldname.a",
+    "libmsvcrt.a",
+    "libkernel32.a",
+    "libuser32.a",
+    "libadvapi32.a",
+    "libshell32.a",
+    # needed by libuv
+    "libws2_32.a",
+    "libpsapi.a",
+    "libiphlpapi.a",
+]
+
+def find_runtime_file(cc, name):
+    # gcc prints the name back unchanged when it can't find the file
+    out = subprocess.Popen([cc, "-print-file-name=" + name],
+                           stdout=subprocess.PIPE).communicate()[0].strip()
+    if out == name or not os.path.isfile(out):
+        raise Exception("Could not find mingw runtime file: %s" % name)
+    return out
+
+def copy_mingw_runtime(cc, dest_dir):
+    if not os.path.isdir(dest_dir):
+        os.makedirs(dest_dir)
+    for name in mingw_runtime:
+        shutil.copy(find_runtime_file(cc, name), dest_dir)
+
+copy_mingw_runtime(sys.argv[1], sys.argv[2])
@@ -0,0 +1,134 @@
+# Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+# file at the top-level directory of this distribution and at
+# http://rust-lang.org/COPYRIGHT.
+#
+# Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+# http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+# <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+# option. This file may not be copied, modified, or distributed
+# except according to those terms.
+
+# gdb pretty-printers for Rust values. rustc names this script in the
+# .debug_gdb_scripts section of the programs it compiles with debug info, so
+# gdb loads it by itself (as long as the sysroot is in gdb's auto-load
+# safe-path).
+#
+# The printers work off the shape of the debug info that rustc emits:
+#
+# * `~[T]` points to a struct with the fields `fill` (the length in bytes),
+#   `alloc` and `elements`, and `~str` is the same with `i8` elements.
+# * `&[T]` and `&str` are structs with the fields `data_ptr` and `length`.
+# * Enums with data are unions of one struct per variant, all of which start
+#   with an unnamed discriminant field.
+
+import gdb
+
+def strip_nul(s):
+    if s.endswith('\0'):
+        return s[:-1]
+    return s
+
+def field_names(t):
+    return [f.name for f in t.fields()]
+
+def is_vec(t):
+    return (t.code == gdb.TYPE_CODE_STRUCT and
+            field_names(t) == ['fill', 'alloc', 'elements'])
+
+def is_slice(t):
+    return (t.code == gdb.TYPE_CODE_STRUCT and
+            field_names(t) == ['data_ptr', 'length'])
+
+def is_enum(t):
+    if t.code != gdb.TYPE_CODE_UNION or len(t.fields()) == 0:
+        return False
+    for variant in t.fields():
+        fields = variant.type.strip_typedefs().fields()
+        if len(fields) == 0 or fields[0].type.code != gdb.TYPE_CODE_ENUM:
+            return False
+    return True
+
+def elements(vec):
+    elem_type = vec.type.strip_typedefs()['elements'].type.target()
+    data = vec['elements'].address.cast(elem_type.pointer())
+    return (elem_type, data, int(vec['fill']) // max(elem_type.sizeof, 1))
+
+class StringPrinter(object):
+    def __init__(self, data, length):
+        self.data = data
+        self.length = length
+
+    def to_string(self):
+        bytes = self.data.cast(gdb.lookup_type('char').pointer())
+        return strip_nul(bytes.string('utf-8', 'replace', self.length))
+
+    def display_hint(self):
+        return 'string'
+
+class VecPrinter(object):
+    def __init__(self, prefix, elem_type, data, length):
+        self.prefix = prefix
+        self.elem_type = elem_type
+        self.data = data
+        self.length = length
+
+    def to_string(self):
+        return '%s[%s] of length %d' % (self.prefix, self.elem_type,
+                                        self.length)
+
+    def children(self):
+        for i in range(self.length):
+            yield ('[%d]' % i, (self.data + i).dereference())
+
+    def display_hint(self):
+        return 'array'
+
+class EnumPrinter(object):
+    def __init__(self, val):
+        self.val = val
+        variants = val.type.strip_typedefs().fields()
+        discr = val[variants[0]]
+        discr = discr[discr.type.strip_typedefs().fields()[0]]
+        self.name = str(discr)
+        self.variant = None
+        for variant in variants:
+            name = str(variant.type.strip_typedefs()).split('::')[-1]
+            if name == self.name:
+                self.variant = val[variant]
+
+    def to_string(self):
+        return self.name
+
+    def children(self):
+        if self.variant is None:
+            return
+        fields = self.variant.type.strip_typedefs().fields()
+        for (i, f) in enumerate(fields[1:]):
+            yield (f.name or str(i), self.variant[f])
+
+def lookup(val):
+    t = val.type.strip_typedefs()
+    if t.code == gdb.TYPE_CODE_PTR:
+        target = t.target().strip_typedefs()
+        if is_vec(target):
+            vec = val.dereference()
+            (elem_type, data, length) = elements(vec)
+            if str(elem_type) == 'i8':
+                return StringPrinter(data, length)
+            return VecPrinter('~', elem_type, data, length)
+    elif is_slice(t):
+        elem_type = t['data_ptr'].type.target()
+        length = int(val['length'])
+        if str(elem_type) == 'i8':
+            return StringPrinter(val['data_ptr'], length)
+        return VecPrinter('&', elem_type, val['data_ptr'], length)
+    elif is_enum(t):
+        return EnumPrinter(val)
+    return None
+
+# The printers apply to the program naming this script, or to everything when
+# the script is sourced by hand
+if gdb.current_objfile() is not None:
+    gdb.current_objfile().pretty_printers.append(lookup)
+else:
+    gdb.pretty_printers.append(lookup)
@@ -43,7 +43,12 @@ if [ -z $TARG_DIR ]; then
 fi
 
 cp ${PREFIX}/bin/rustc${BIN_SUF} ${TARG_DIR}/stage0/bin/
-cp ${PREFIX}/${LIB_DIR}/rustc/${TARG_DIR}/${LIB_DIR}/* ${TARG_DIR}/stage0/${LIB_DIR}/
+# Target libraries are in lib/rustlib, or lib/rustc for older installations
+RUSTLIB_DIR=rustlib
+if [ ! -d ${PREFIX}/${LIB_DIR}/${RUSTLIB_DIR} ]; then
+    RUSTLIB_DIR=rustc
+fi
+cp ${PREFIX}/${LIB_DIR}/${RUSTLIB_DIR}/${TARG_DIR}/${LIB_DIR}/* ${TARG_DIR}/stage0/${LIB_DIR}/
 cp ${PREFIX}/${LIB_DIR}/${LIB_PREFIX}extra*${LIB_SUF} ${TARG_DIR}/stage0/${LIB_DIR}/
 cp ${PREFIX}/${LIB_DIR}/${LIB_PREFIX}rust*${LIB_SUF} ${TARG_DIR}/stage0/${LIB_DIR}/
 cp ${PREFIX}/${LIB_DIR}/${LIB_PREFIX}std*${LIB_SUF} ${TARG_DIR}/stage0/${LIB_DIR}/
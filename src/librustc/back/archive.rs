@@ -10,42 +10,46 @@
 
 //! A helper class for dealing with static archives
 
+use back::link::{Command, CommandOutput, lossy_utf8};
+use back::lto;
 use driver::session::Session;
 use metadata::filesearch;
 
+use std::cmp;
+use std::hashmap::HashSet;
+use std::io;
 use std::io::fs;
-use std::os;
-use std::run::{ProcessOptions, Process, ProcessOutput};
 use std::str;
-use extra::tempfile::TempDir;
+use std::uint;
 use syntax::abi;
 
 pub static METADATA_FILENAME: &'static str = "metadata";
+static WHOLE_ARCHIVE_SUFFIX: &'static str = ".whole.a";
 
 pub struct Archive {
     priv sess: Session,
     priv dst: Path,
+    // The members of the archive, read the first time it's changed. Each
+    // change is written out along with a new symbol table.
+    priv members: Option<~[Member]>,
 }
 
-fn run_ar(sess: Session, args: &str, cwd: Option<&Path>,
-        paths: &[&Path]) -> ProcessOutput {
+// The paths are passed as they are, in a response file if they aren't UTF-8
+fn run_ar(sess: Session, args: &str, paths: &[&Path]) -> CommandOutput {
     let ar = sess.opts.ar.clone().unwrap_or_else(|| ~"ar");
-    let mut args = ~[args.to_owned()];
-    let mut paths = paths.iter().map(|p| p.as_str().unwrap().to_owned());
-    args.extend(&mut paths);
-    let mut opts = ProcessOptions::new();
-    opts.dir = cwd;
-    debug!("{} {}", ar, args.connect(" "));
-    match cwd {
-        Some(p) => { debug!("inside {}", p.display()); }
-        None => {}
-    }
-    let o = Process::new(ar, args.as_slice(), opts).finish_with_output();
+    let mut cmd = Command::new(ar);
+    cmd.allow_response_file();
+    cmd.arg(args);
+    for path in paths.iter() {
+        cmd.arg_path("", *path);
+    }
+    let line = cmd.command_line();
+    debug!("{}", line);
+    let o = sess.profile("archive", line, || cmd.output());
     if !o.status.success() {
-        sess.err(format!("{} {} failed with: {}", ar, args.connect(" "),
-                         o.status));
-        sess.note(format!("stdout ---\n{}", str::from_utf8(o.output)));
-        sess.note(format!("stderr ---\n{}", str::from_utf8(o.error)));
+        sess.err(format!("{} failed with: {}", line, o.status));
+        sess.note(format!("stdout ---\n{}", o.output));
+        sess.note(format!("stderr ---\n{}", o.error));
         sess.abort_if_errors();
     }
     o
@@ -55,124 +59,655 @@ impl Archive {
     /// Initializes a new static archive with the given object file
     pub fn create<'a>(sess: Session, dst: &'a Path,
                       initial_object: &'a Path) -> Archive {
-        run_ar(sess, "crus", None, [dst, initial_object]);
-        Archive { sess: sess, dst: dst.clone() }
+        let mut archive = Archive {
+            sess: sess, dst: dst.clone(), members: Some(~[]),
+        };
+        archive.add_file(initial_object);
+        archive
+    }
+
+    /// Initializes a new static archive with no members, for an rlib which
+    /// has no object file of its own
+    pub fn create_empty(sess: Session, dst: &Path) -> Archive {
+        let mut archive = Archive {
+            sess: sess, dst: dst.clone(), members: Some(~[]),
+        };
+        archive.update_symbols();
+        archive
     }
 
     /// Opens an existing static archive
     pub fn open(sess: Session, dst: Path) -> Archive {
         assert!(dst.exists());
-        Archive { sess: sess, dst: dst }
+        Archive { sess: sess, dst: dst, members: None }
     }
 
-    /// Read a file in the archive
+    /// Read a file in the archive. Only that member is read from the disk.
     pub fn read(&self, file: &str) -> ~[u8] {
-        // Apparently if "ar p" is used on windows, it generates a corrupt file
-        // which has bad headers and LLVM will immediately choke on it
-        if cfg!(windows) && cfg!(windows) { // FIXME(#10734) double-and
-            let loc = TempDir::new("rsar").unwrap();
-            let archive = os::make_absolute(&self.dst);
-            run_ar(self.sess, "x", Some(loc.path()), [&archive,
-                                                      &Path::new(file)]);
-            fs::File::open(&loc.path().join(file)).read_to_end()
-        } else {
-            run_ar(self.sess, "p", None, [&self.dst, &Path::new(file)]).output
+        let data = match self.members {
+            Some(ref members) => {
+                members.iter().find(|m| m.name.as_slice() == file)
+                       .map(|m| m.data.clone())
+            }
+            None => {
+                let extent = |name: &str| {
+                    if name == file { uint::max_value } else { 0 }
+                };
+                scan_members(self.sess, &self.dst, extent).move_iter()
+                    .find(|m| m.name.as_slice() == file).map(|m| m.data)
+            }
+        };
+        match data {
+            Some(data) => data,
+            None => {
+                self.sess.fatal(format!("archive {} has no member `{}`",
+                                        self.dst.display(), file));
+            }
         }
     }
 
     /// Adds all of the contents of a native library to this archive. This will
     /// search in the relevant locations for a library named `name`.
     pub fn add_native_library(&mut self, name: &str) {
-        let location = self.find_library(name);
-        self.add_archive(&location, name, []);
+        let location = find_library(self.sess, name);
+        let added = read_members(self.sess, &location);
+        self.add_members(added, name, []);
+    }
+
+    /// Adds the native static library `name` as a member of its own, rather
+    /// than adding its contents, for it to be linked whole downstream.
+    pub fn add_whole_archive(&mut self, name: &str) {
+        let location = find_library(self.sess, name);
+        let member = Member {
+            name: whole_archive_member(name),
+            data: fs::File::open(&location).read_to_end(),
+        };
+        self.members_mut().push(member);
+        self.update_symbols();
     }
 
     /// Adds all of the contents of the rlib at the specified path to this
     /// archive.
     ///
     /// This ignores adding the bytecode from the rlib, and if LTO is enabled
-    /// then the object file also isn't added.
+    /// then the object file also isn't added. The libraries bundled to be
+    /// linked whole aren't added either, it's up to the caller to add their
+    /// contents.
     pub fn add_rlib(&mut self, rlib: &Path, name: &str, lto: bool) {
+        let mut added = read_members(self.sess, rlib);
+        added.retain(|m| !m.name.ends_with(WHOLE_ARCHIVE_SUFFIX));
         let object = format!("{}.o", name);
+        let index = lto::bytecode_index_name(name);
+        // The rlibs of earlier compilers have a single `name.bc` instead of
+        // the bytecode of each codegen unit and their index
         let bytecode = format!("{}.bc", name);
-        let mut ignore = ~[METADATA_FILENAME, bytecode.as_slice()];
+        let units = match added.iter().find(|m| m.name == index) {
+            Some(member) => lto::read_bytecode_index(str::from_utf8(member.data)),
+            None => ~[],
+        };
+        let mut ignore = ~[METADATA_FILENAME, index.as_slice(),
+                           bytecode.as_slice()];
+        for unit in units.iter() {
+            ignore.push(unit.member.as_slice());
+        }
         if lto {
             ignore.push(object.as_slice());
         }
-        self.add_archive(rlib, name, ignore);
+        self.add_members(added, name, ignore);
+    }
+
+    /// Adds all of the contents of the static library at the specified path to
+    /// this archive.
+    pub fn add_static_archive(&mut self, archive: &Path) {
+        let name = archive.filestem_str().unwrap();
+        let added = read_members(self.sess, archive);
+        self.add_members(added, name, []);
     }
 
     /// Adds an arbitrary file to this archive
     pub fn add_file(&mut self, file: &Path) {
-        run_ar(self.sess, "r", None, [&self.dst, file]);
+        self.add_files([file.clone()]);
+    }
+
+    /// Adds arbitrary files to this archive, as members named like the files.
+    /// Where a member of the same name exists already, the file is added
+    /// under a unique name instead (`foo-1.o` for `foo.o`), where `ar` would
+    /// replace that member. The names may be of any length.
+    pub fn add_files(&mut self, files: &[Path]) {
+        let members = self.members_mut();
+        let mut names = HashSet::new();
+        for member in members.iter() {
+            names.insert(member.name.clone());
+        }
+        for file in files.iter() {
+            let unique = unique_member_name(&names, file);
+            names.insert(unique.clone());
+            members.push(Member {
+                name: unique,
+                data: fs::File::open(file).read_to_end(),
+            });
+        }
+        self.update_symbols();
     }
 
     /// Removes a file from this archive
     pub fn remove_file(&mut self, file: &str) {
-        run_ar(self.sess, "d", None, [&self.dst, &Path::new(file)]);
+        self.members_mut().retain(|m| m.name.as_slice() != file);
+        self.update_symbols();
     }
 
+    /// All of the members of the archive, for when several of them are read
+    pub fn into_members(self) -> ~[Member] {
+        match self.members {
+            Some(members) => members,
+            None => read_members(self.sess, &self.dst),
+        }
+    }
+
+    /// The names of the members of the archive, for which only the headers
+    /// of the members are read
     pub fn files(&self) -> ~[~str] {
-        let output = run_ar(self.sess, "t", None, [&self.dst]);
-        str::from_utf8(output.output).lines().map(|s| s.to_owned()).collect()
+        self.with_heads(|members| {
+            members.iter().map(|m| m.name.clone()).collect()
+        })
     }
 
-    fn add_archive(&mut self, archive: &Path, name: &str, skip: &[&str]) {
-        let loc = TempDir::new("rsar").unwrap();
+    /// Whether the archive has any object files (or import members) for the
+    /// linker, as opposed to just metadata and bytecode. Only the start of
+    /// each member is read, which tells what kind of file it is.
+    pub fn has_objects(&self) -> bool {
+        self.with_heads(|members| members.iter().any(|m| m.is_object()))
+    }
 
-        // First, extract the contents of the archive to a temporary directory
-        let archive = os::make_absolute(archive);
-        run_ar(self.sess, "x", Some(loc.path()), [&archive]);
+    // Writes out the archive after a change, and has `ar` put a new symbol
+    // table in it
+    fn update_symbols(&mut self) {
+        let bsd = self.sess.targ_cfg.os == abi::OsMacos;
+        let bytes = write_archive(*self.members.get_ref(), bsd);
+        fs::File::create(&self.dst).write(bytes);
+        run_ar(self.sess, "s", [&self.dst]);
+    }
 
-        // Next, we must rename all of the inputs to "guaranteed unique names".
+    // Calls `f` with the members of the archive, of which only the first
+    // `OBJECT_MAGIC_LEN` bytes are read unless the archive was changed
+    fn with_heads<T>(&self, f: |&[Member]| -> T) -> T {
+        match self.members {
+            Some(ref members) => f(members.as_slice()),
+            None => f(scan_members(self.sess, &self.dst, |_| OBJECT_MAGIC_LEN)),
+        }
+    }
+
+    // The members of the archive, read the first time the archive is changed
+    fn members_mut<'a>(&'a mut self) -> &'a mut ~[Member] {
+        if self.members.is_none() {
+            self.members = Some(read_members(self.sess, &self.dst));
+        }
+        self.members.get_mut_ref()
+    }
+
+    // Adds the members of another archive, which is named `name`
+    fn add_members(&mut self, added: ~[Member], name: &str, skip: &[&str]) {
+        let members = self.members_mut();
+        let mut names = HashSet::new();
+        for member in members.iter() {
+            names.insert(member.name.clone());
+        }
+
+        // The members of the archive are renamed to "guaranteed unique names".
         // The reason for this is that archives are keyed off the name of the
         // files, so if two files have the same name they will override one
-        // another in the archive (bad).
+        // another in the archive (bad). Even a single archive may well have
+        // two members of the same name.
         //
-        // We skip any files explicitly desired for skipping, and we also skip
-        // all SYMDEF files as these are just magical placeholders which get
-        // re-created when we make a new archive anyway.
-        let files = fs::readdir(loc.path());
-        let mut inputs = ~[];
-        for file in files.iter() {
-            let filename = file.filename_str().unwrap();
+        // We skip any files explicitly desired for skipping. Symbol tables
+        // aren't members as far as `read_archive` is concerned.
+        for member in added.move_iter() {
+            let filename = member.filename().to_owned();
             if skip.iter().any(|s| *s == filename) { continue }
-            if filename.contains(".SYMDEF") { continue }
 
-            let filename = format!("r-{}-{}", name, filename);
-            let new_filename = file.with_filename(filename);
-            fs::rename(file, &new_filename);
-            inputs.push(new_filename);
+            let mut unique = format!("r-{}-{}", name, filename);
+            let mut i = 1;
+            while names.contains(&unique) {
+                unique = format!("r-{}-{}-{}", name, i, filename);
+                i += 1;
+            }
+            names.insert(unique.clone());
+            members.push(Member { name: unique, data: member.data });
         }
+        self.update_symbols();
+    }
+}
 
-        // Finally, add all the renamed files to this archive
-        let mut args = ~[&self.dst];
-        args.extend(&mut inputs.iter());
-        run_ar(self.sess, "r", None, args.as_slice());
+// The members of the archive at `path`, which is read here rather than with
+// `ar`: that doesn't understand thin archives, nor the long names written by
+// the archivers of other platforms.
+fn read_members(sess: Session, path: &Path) -> ~[Member] {
+    scan_members(sess, path, |_| uint::max_value)
+}
+
+// The members of the archive at `path`, of which only the first
+// `extent(name)` bytes are read (see `scan_archive`). The rest of the file
+// is skipped.
+fn scan_members(sess: Session, path: &Path,
+                extent: |&str| -> uint) -> ~[Member] {
+    let len = path.stat().size as uint;
+    let mut file = fs::File::open(path);
+    let read_at = |pos: uint, n: uint| {
+        file.seek(pos as i64, io::SeekSet);
+        file.read_bytes(cmp::min(n, len - pos))
+    };
+    match scan_archive(len, read_at, path, extent) {
+        Ok(members) => members,
+        Err(e) => {
+            sess.fatal(format!("failed to read archive {}: {}",
+                               path.display(), e));
+        }
     }
+}
 
-    fn find_library(&self, name: &str) -> Path {
-        let (osprefix, osext) = match self.sess.targ_cfg.os {
-            abi::OsWin32 => ("", "lib"), _ => ("lib", "a"),
+// The name for `file` in an archive which has members of the given names. A
+// file name which isn't UTF-8 keeps its other bytes as Latin-1 characters.
+fn unique_member_name(names: &HashSet<~str>, file: &Path) -> ~str {
+    let name = lossy_utf8(file.filename().unwrap_or(bytes!("file")));
+    let mut unique = name.clone();
+    let mut i = 1;
+    while names.contains(&unique) {
+        unique = match name.rfind('.') {
+            Some(dot) if dot > 0 => {
+                format!("{}-{}{}", name.slice_to(dot), i, name.slice_from(dot))
+            }
+            _ => format!("{}-{}", name, i),
         };
-        // On windows, static libraries sometimes show up as libfoo.a and other
-        // times show up as foo.lib
-        let oslibname = format!("{}{}.{}", osprefix, name, osext);
-        let unixlibname = format!("lib{}.a", name);
-
-        let mut rustpath = filesearch::rust_path();
-        rustpath.push(self.sess.filesearch.get_target_lib_path());
-        let path = self.sess.opts.addl_lib_search_paths.iter();
-        for path in path.chain(rustpath.iter()) {
-            debug!("looking for {} inside {}", name, path.display());
-            let test = path.join(oslibname.as_slice());
-            if test.exists() { return test }
-            if oslibname != unixlibname {
-                let test = path.join(unixlibname.as_slice());
-                if test.exists() { return test }
+        i += 1;
+    }
+    unique
+}
+
+/// A member of an archive file
+pub struct Member {
+    name: ~str,
+    data: ~[u8],
+}
+
+impl Member {
+    /// The file name of the member. The members of thin archives are named
+    /// by their paths, and so are those of the archives written by the MSVC
+    /// librarian, with either kind of slash.
+    pub fn filename<'a>(&'a self) -> &'a str {
+        match self.name.rfind(&['/', '\\']) {
+            Some(i) => self.name.slice_from(i + 1),
+            None => self.name.as_slice(),
+        }
+    }
+
+    /// Whether the member is an import member of an MSVC import library,
+    /// which stands for a function or data of a DLL. These are short import
+    /// objects rather than COFF objects, and start with the signatures 0 and
+    /// 0xFFFF followed by version 0.
+    pub fn is_import(&self) -> bool {
+        self.data.starts_with([0u8, 0, 0xff, 0xff, 0, 0])
+    }
+
+    /// Whether the member is for the linker, i.e. an object file or an import
+    /// member. This goes by the header of the member rather than its name,
+    /// which needn't have the usual extension (e.g. objects given with
+    /// `-C link-objects`, or those of the MSVC librarian).
+    pub fn is_object(&self) -> bool {
+        let data = self.data.as_slice();
+        // ELF
+        data.starts_with(bytes!("\x7fELF")) ||
+        // Mach-O, 32 and 64 bit in either byte order, and universal
+        data.starts_with([0xfeu8, 0xed, 0xfa, 0xce]) ||
+        data.starts_with([0xceu8, 0xfa, 0xed, 0xfe]) ||
+        data.starts_with([0xfeu8, 0xed, 0xfa, 0xcf]) ||
+        data.starts_with([0xcfu8, 0xfa, 0xed, 0xfe]) ||
+        data.starts_with([0xcau8, 0xfe, 0xba, 0xbe]) ||
+        // COFF, by its machine type: i386, AMD64, ARM, ARMv7 and ARM64
+        data.starts_with([0x4cu8, 0x01]) ||
+        data.starts_with([0x64u8, 0x86]) ||
+        data.starts_with([0xc0u8, 0x01]) ||
+        data.starts_with([0xc4u8, 0x01]) ||
+        data.starts_with([0x64u8, 0xaa]) ||
+        // Import members, and the big COFF objects of `/bigobj` which have
+        // the same signatures but a later version
+        data.starts_with([0u8, 0, 0xff, 0xff])
+    }
+}
+
+static GLOBAL_HEADER: &'static [u8] = bytes!("!<arch>\n");
+static THIN_HEADER: &'static [u8] = bytes!("!<thin>\n");
+static HEADER_LEN: uint = 60;
+
+// Symbol tables, as named by GNU and BSD archivers respectively. The archives
+// of the MSVC librarian start with two symbol tables named "/", and may have
+// one for ARM64EC symbols as well.
+fn is_symbol_table(name: &str) -> bool {
+    name == "/" || name == "/SYM64/" || name == "/<ECSYMBOLS>/" ||
+        name.starts_with("__.SYMDEF")
+}
+
+// The members which are object files are told by their first bytes (see
+// `Member::is_object`)
+static OBJECT_MAGIC_LEN: uint = 6;
+
+/// Reads the members of an archive in the GNU, BSD or COFF (MSVC) format, or
+/// of a GNU thin archive, from the contents of the file at `path`. The members of a thin
+/// archive are read from their own files, relative to the archive. Symbol
+/// tables and the table of long names are left out.
+pub fn read_archive(bytes: &[u8], path: &Path) -> Result<~[Member], ~str> {
+    let read_at = |pos: uint, n: uint| {
+        bytes.slice(pos, cmp::min(pos + n, bytes.len())).to_owned()
+    };
+    scan_archive(bytes.len(), read_at, path, |_| uint::max_value)
+}
+
+// Reads the members of an archive of `len` bytes, whose bytes are read with
+// `read_at(pos, n)`, as `read_archive` does. Only the first `extent(name)`
+// bytes of the data of each member are read, and the headers otherwise.
+fn scan_archive(len: uint, read_at: |uint, uint| -> ~[u8], path: &Path,
+                extent: |&str| -> uint) -> Result<~[Member], ~str> {
+    let magic = read_at(0, GLOBAL_HEADER.len());
+    let thin = if magic.as_slice() == GLOBAL_HEADER {
+        false
+    } else if magic.as_slice() == THIN_HEADER {
+        true
+    } else {
+        return Err(~"not an archive");
+    };
+
+    let mut members = ~[];
+    let mut long_names = ~[];
+    let mut pos = GLOBAL_HEADER.len();
+    while pos < len {
+        if pos + HEADER_LEN > len {
+            return Err(format!("truncated member header at offset {}", pos));
+        }
+        let header = read_at(pos, HEADER_LEN);
+        let name = str::from_utf8_opt(header.slice(0, 16));
+        let size = str::from_utf8_opt(header.slice(48, 58)).and_then(|s| {
+            from_str::<uint>(s.trim())
+        });
+        let (name, size) = match (name, size) {
+            (Some(name), Some(size)) if header.slice(58, 60) == bytes!("`\n") => {
+                (name.trim_right(), size)
             }
+            _ => return Err(format!("bad member header at offset {}", pos)),
+        };
+        pos += HEADER_LEN;
+
+        // Thin archives only hold the symbol table and the long names, the
+        // data of the other members is left in their files
+        let special = name == "//" || is_symbol_table(name);
+        let stored = if thin && !special { 0 } else { size };
+        if pos + stored > len {
+            return Err(format!("truncated member at offset {}", pos));
+        }
+        let (mut start, mut stored) = (pos, stored);
+        // The members are aligned to two bytes
+        pos += stored + stored % 2;
+
+        if name == "//" {
+            long_names = read_at(start, stored);
+            continue
         }
-        self.sess.fatal(format!("could not find native static library `{}`, \
-                                 perhaps an -L flag is missing?", name));
+        if special { continue }
+
+        let name = if name.starts_with("#1/") {
+            // BSD names which don't fit in the header (or have spaces) come
+            // first in the data, padded with nuls
+            let len = match from_str::<uint>(name.slice_from(3)) {
+                Some(len) if len <= stored => len,
+                _ => return Err(format!("bad member name `{}`", name)),
+            };
+            let name = read_at(start, len);
+            start += len;
+            stored -= len;
+            let end = name.iter().position(|&b| b == 0).unwrap_or(len);
+            str::from_utf8_opt(name.slice_to(end)).map(|s| s.to_owned())
+        } else if name.starts_with("/") {
+            // GNU names which don't fit in the header are found at the given
+            // offset in the long names, each ending with "/\n". The MSVC
+            // librarian ends them with a nul instead.
+            let offset = match from_str::<uint>(name.slice_from(1)) {
+                Some(offset) if offset < long_names.len() => offset,
+                _ => return Err(format!("bad member name `{}`", name)),
+            };
+            let rest = long_names.slice_from(offset);
+            let end = rest.iter().position(|&b| b == '\n' as u8 || b == 0)
+                          .unwrap_or(rest.len());
+            let name = rest.slice_to(end);
+            let name = if name.ends_with(bytes!("/")) {
+                name.slice_to(end - 1)
+            } else {
+                name
+            };
+            str::from_utf8_opt(name).map(|s| s.to_owned())
+        } else {
+            // GNU names end with a '/', so that they can have spaces
+            Some(name.trim_right_chars(&'/').to_owned())
+        };
+        let name = match name {
+            Some(name) => name,
+            None => return Err(~"member name isn't valid UTF-8"),
+        };
+
+        let wanted = extent(name.as_slice());
+        let data = if thin {
+            let file = path.dir_path().join(name.as_slice());
+            if !file.exists() {
+                return Err(format!("member {} of thin archive not found",
+                                   file.display()));
+            }
+            let size = file.stat().size as uint;
+            fs::File::open(&file).read_bytes(cmp::min(wanted, size))
+        } else {
+            read_at(start, cmp::min(wanted, stored))
+        };
+        members.push(Member { name: name, data: data });
+    }
+    Ok(members)
+}
+
+/// Writes an archive holding the given members, in the BSD format when `bsd`
+/// is set and in the GNU format otherwise. It has no symbol table, which
+/// `ar s` adds.
+pub fn write_archive(members: &[Member], bsd: bool) -> ~[u8] {
+    let mut out = GLOBAL_HEADER.to_owned();
+
+    if !bsd {
+        let mut long_names = ~[];
+        for member in members.iter() {
+            if member.name.len() > 15 {
+                long_names.push_all(member.name.as_bytes());
+                long_names.push_all(bytes!("/\n"));
+            }
+        }
+        if !long_names.is_empty() {
+            write_member(&mut out, "//", [], long_names);
+        }
+        let mut offset = 0;
+        for member in members.iter() {
+            if member.name.len() > 15 {
+                let name = format!("/{}", offset);
+                write_member(&mut out, name, [], member.data);
+                offset += member.name.len() + 2;
+            } else {
+                write_member(&mut out, member.name + "/", [], member.data);
+            }
+        }
+        return out;
+    }
+
+    for member in members.iter() {
+        if member.name.len() <= 16 && !member.name.contains_char(' ') {
+            write_member(&mut out, member.name, [], member.data);
+            continue
+        }
+        // The name is padded so that the data starts at a multiple of eight
+        // bytes, which the linker of OSX expects
+        let mut name = member.name.as_bytes().to_owned();
+        while (out.len() + HEADER_LEN + name.len()) % 8 != 0 {
+            name.push(0);
+        }
+        let header_name = "#1/" + name.len().to_str();
+        write_member(&mut out, header_name, name, member.data);
+    }
+    out
+}
+
+fn write_member(out: &mut ~[u8], name: &str, prefix: &[u8], data: &[u8]) {
+    let size = prefix.len() + data.len();
+    let header = format!("{:<16s}{:<12s}{:<6s}{:<6s}{:<8s}{:<10s}`\n",
+                         name, "0", "0", "0", "644", size.to_str());
+    out.push_all(header.as_bytes());
+    out.push_all(prefix);
+    out.push_all(data);
+    if size % 2 == 1 {
+        out.push('\n' as u8);
+    }
+}
+
+/// The name of the member of an rlib which bundles the native static library
+/// `name`, which is linked whole (see `Archive::add_whole_archive`)
+pub fn whole_archive_member(name: &str) -> ~str {
+    format!("lib{}{}", name, WHOLE_ARCHIVE_SUFFIX)
+}
+
+/// Searches the native library search paths for the static library `name`,
+/// failing if it can't be found.
+pub fn find_library(sess: Session, name: &str) -> Path {
+    let (osprefix, osext) = match sess.targ_cfg.os {
+        abi::OsWin32 => ("", "lib"), _ => ("lib", "a"),
+    };
+    // On windows, static libraries sometimes show up as libfoo.a and other
+    // times show up as foo.lib
+    let oslibname = format!("{}{}.{}", osprefix, name, osext);
+    let unixlibname = format!("lib{}.a", name);
+
+    let mut rustpath = filesearch::rust_path_lib_paths(sess.opts.target_triple);
+    rustpath.push(sess.filesearch.get_target_lib_path());
+    let search = sess.native_search_paths();
+    for path in search.iter().chain(rustpath.iter()) {
+        debug!("looking for {} inside {}", name, path.display());
+        let test = path.join(oslibname.as_slice());
+        if test.exists() { return test }
+        if oslibname != unixlibname {
+            let test = path.join(unixlibname.as_slice());
+            if test.exists() { return test }
+        }
+    }
+    sess.fatal(format!("could not find native static library `{}`, \
+                        perhaps an -L flag is missing?", name));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Member, read_archive, scan_archive, write_archive};
+    use super::unique_member_name;
+    use std::cmp;
+    use std::hashmap::HashSet;
+
+    fn member(name: &str, data: &str) -> Member {
+        Member { name: name.to_owned(), data: data.as_bytes().to_owned() }
+    }
+
+    fn roundtrip(bsd: bool) {
+        let members = ~[member("a.o", "odd"),
+                         member("a_rather_long_name.o", "even"),
+                         member("with space.o", "")];
+        let bytes = write_archive(members, bsd);
+        let read = read_archive(bytes, &Path::new("foo.a")).unwrap();
+        assert_eq!(read.len(), members.len());
+        for (a, b) in read.iter().zip(members.iter()) {
+            assert_eq!(a.name.as_slice(), b.name.as_slice());
+            assert_eq!(a.data.as_slice(), b.data.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_gnu() { roundtrip(false) }
+
+    #[test]
+    fn test_unique_member_name() {
+        let mut names = HashSet::new();
+        let foo = Path::new("dir/foo.o");
+        assert_eq!(unique_member_name(&names, &foo), ~"foo.o");
+        names.insert(~"foo.o");
+        assert_eq!(unique_member_name(&names, &foo), ~"foo-1.o");
+        names.insert(~"foo-1.o");
+        assert_eq!(unique_member_name(&names, &foo), ~"foo-2.o");
+        names.insert(~"metadata");
+        assert_eq!(unique_member_name(&names, &Path::new("metadata")),
+                   ~"metadata-1");
+        let latin1 = Path::new(bytes!("dir/caf", 0xe9, ".o"));
+        assert_eq!(unique_member_name(&names, &latin1), ~"caf\u00e9.o");
+    }
+
+    #[test]
+    fn test_roundtrip_bsd() { roundtrip(true) }
+
+    #[test]
+    fn test_read_gnu() {
+        let bytes = bytes!("!<arch>\n",
+                           "/               0           0     0     0       4         `\n",
+                           "\x00\x00\x00\x00",
+                           "//                                              22        `\n",
+                           "a_rather_long_name.o/\n",
+                           "/0              0           0     0     644     1         `\n",
+                           "x\n",
+                           "b.o/            0           0     0     644     2         `\n",
+                           "yz");
+        let read = read_archive(bytes, &Path::new("foo.a")).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].name.as_slice(), "a_rather_long_name.o");
+        assert_eq!(read[0].data.as_slice(), bytes!("x"));
+        assert_eq!(read[1].name.as_slice(), "b.o");
+        assert_eq!(read[1].data.as_slice(), bytes!("yz"));
+    }
+
+    #[test]
+    fn test_read_heads() {
+        let members = ~[member("a.o", "odd"),
+                         member("a_rather_long_name.o", "even")];
+        for &bsd in [false, true].iter() {
+            let bytes = write_archive(members, bsd);
+            let read_at = |pos: uint, n: uint| {
+                bytes.slice(pos, cmp::min(pos + n, bytes.len())).to_owned()
+            };
+            let extent = |name: &str| if name == "a.o" { 0 } else { 2 };
+            let read = scan_archive(bytes.len(), read_at, &Path::new("foo.a"),
+                                    extent).unwrap();
+            assert_eq!(read[0].name.as_slice(), "a.o");
+            assert!(read[0].data.is_empty());
+            assert_eq!(read[1].name.as_slice(), "a_rather_long_name.o");
+            assert_eq!(read[1].data.as_slice(), bytes!("ev"));
+        }
+    }
+
+    #[test]
+    fn test_read_msvc() {
+        let bytes = bytes!("!<arch>\n",
+                           "/               0           0     0     0       4         `\n",
+                           "\x00\x00\x00\x00",
+                           "/               0           0     0     0       4         `\n",
+                           "\x00\x00\x00\x00",
+                           "//                                              22        `\n",
+                           "C:\\build\\a_long.obj\x00\x00\x00",
+                           "/0              0           0     0     644     2         `\n",
+                           0x64, 0x86,
+                           "foo.dll/        0           0     0     644     6         `\n",
+                           0, 0, 0xff, 0xff, 0, 0);
+        let read = read_archive(bytes, &Path::new("foo.lib")).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].filename(), "a_long.obj");
+        assert!(read[0].is_object() && !read[0].is_import());
+        assert_eq!(read[1].filename(), "foo.dll");
+        assert!(read[1].is_object() && read[1].is_import());
+    }
+
+    #[test]
+    fn test_read_bad() {
+        assert!(read_archive(bytes!("not an archive"), &Path::new("foo.a")).is_err());
+        assert!(read_archive(bytes!("!<arch>\nb.o/"), &Path::new("foo.a")).is_err());
     }
 }
@@ -0,0 +1,174 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Generation of C headers for the items a crate exports to C (`--emit
+//! c-header`). These are the reachable `#[no_mangle]` functions with a
+//! foreign ABI and `#[no_mangle]` statics: the symbols of any other items are
+//! mangled, so C code couldn't link with them by their names.
+//!
+//! Only types with an obvious C counterpart (scalars and pointers to them)
+//! are translated; items using any other types are left out of the header
+//! with a warning.
+
+use middle::trans::context::CrateContext;
+use middle::ty;
+
+use std::ascii::StrAsciiExt;
+use extra::sort;
+use syntax::ast;
+use syntax::ast_map;
+use syntax::attr;
+
+/// Generates the header for the crate being translated
+pub fn generate(ccx: &CrateContext) -> ~str {
+    let mut decls = ~[];
+    for id in ccx.reachable.iter() {
+        let sym = match ccx.item_symbols.find(id) {
+            Some(sym) => sym.clone(),
+            None => continue,
+        };
+        let item = match ccx.tcx.items.find(id) {
+            Some(&ast_map::node_item(item, _)) => item,
+            _ => continue,
+        };
+        if !attr::contains_name(item.attrs, "no_mangle") {
+            continue
+        }
+        let decl = match item.node {
+            ast::item_fn(_, _, abis, ref generics, _)
+                    if !abis.is_rust() && !abis.is_intrinsic() &&
+                       !generics.is_type_parameterized() => {
+                fn_decl(sym.as_slice(), ty::node_id_to_type(ccx.tcx, *id))
+            }
+            ast::item_static(_, mutbl, _) => {
+                static_decl(sym.as_slice(), mutbl, ty::node_id_to_type(ccx.tcx, *id))
+            }
+            _ => continue,
+        };
+        match decl {
+            Some(decl) => decls.push((sym, decl)),
+            None => {
+                ccx.sess.span_warn(item.span,
+                                   format!("the type of `{}` has no C \
+                                            equivalent, leaving it out of \
+                                            the header", sym));
+            }
+        }
+    }
+    sort::quick_sort(decls, |&(ref a, _), &(ref b, _)| *a <= *b);
+
+    let mut guard = ~"";
+    for c in ccx.link_meta.pkgid.name.to_ascii_upper().chars() {
+        match c {
+            'A' .. 'Z' | '0' .. '9' => guard.push_char(c),
+            _ => guard.push_char('_'),
+        }
+    }
+    guard.push_str("_H");
+
+    let mut header = format!("/* Generated by rustc from the `{}` crate */\n\n\
+                              \\#ifndef {}\n\
+                              \\#define {}\n\n\
+                              \\#include <stdbool.h>\n\
+                              \\#include <stdint.h>\n\n\
+                              \\#ifdef __cplusplus\n\
+                              extern \"C\" \\{\n\
+                              \\#endif\n\n",
+                             ccx.link_meta.pkgid.name, guard, guard);
+    for &(_, ref decl) in decls.iter() {
+        header.push_str(*decl);
+        header.push_str(";\n");
+    }
+    header.push_str(format!("\n\\#ifdef __cplusplus\n\\}\n\\#endif\n\n\
+                             \\#endif /* {} */\n", guard));
+    header
+}
+
+fn fn_decl(name: &str, fty: ty::t) -> Option<~str> {
+    let sig = match ty::get(fty).sty {
+        ty::ty_bare_fn(ref f) => &f.sig,
+        _ => return None,
+    };
+    let output = match ty::get(sig.output).sty {
+        ty::ty_nil | ty::ty_bot => ~"void",
+        _ => match c_type(sig.output) {
+            Some(t) => t,
+            None => return None,
+        },
+    };
+    let mut inputs = ~[];
+    for &input in sig.inputs.iter() {
+        match c_type(input) {
+            Some(t) => inputs.push(t),
+            None => return None,
+        }
+    }
+    if inputs.is_empty() {
+        inputs.push(~"void");
+    }
+    Some(format!("{}{}({})", declarator(output), name, inputs.connect(", ")))
+}
+
+fn static_decl(name: &str, mutbl: ast::Mutability, t: ty::t) -> Option<~str> {
+    c_type(t).map(|t| {
+        match mutbl {
+            ast::MutMutable => format!("extern {}{}", declarator(t), name),
+            // The const of a pointer has to follow the `*`
+            ast::MutImmutable if t.ends_with("*") => {
+                format!("extern {}const {}", t, name)
+            }
+            ast::MutImmutable => format!("extern const {} {}", t, name),
+        }
+    })
+}
+
+// Separates a type from the name it's declaring, which pointers don't need
+fn declarator(t: ~str) -> ~str {
+    if t.ends_with("*") { t } else { t + " " }
+}
+
+fn c_type(t: ty::t) -> Option<~str> {
+    let ty = match ty::get(t).sty {
+        ty::ty_bool => ~"bool",
+        ty::ty_char => ~"uint32_t",
+        ty::ty_int(ast::ty_i) => ~"intptr_t",
+        ty::ty_int(ast::ty_i8) => ~"int8_t",
+        ty::ty_int(ast::ty_i16) => ~"int16_t",
+        ty::ty_int(ast::ty_i32) => ~"int32_t",
+        ty::ty_int(ast::ty_i64) => ~"int64_t",
+        ty::ty_uint(ast::ty_u) => ~"uintptr_t",
+        ty::ty_uint(ast::ty_u8) => ~"uint8_t",
+        ty::ty_uint(ast::ty_u16) => ~"uint16_t",
+        ty::ty_uint(ast::ty_u32) => ~"uint32_t",
+        ty::ty_uint(ast::ty_u64) => ~"uint64_t",
+        ty::ty_float(ast::ty_f32) => ~"float",
+        ty::ty_float(ast::ty_f64) => ~"double",
+        ty::ty_ptr(ref mt) | ty::ty_rptr(_, ref mt) => {
+            let pointee = match ty::get(mt.ty).sty {
+                ty::ty_nil => ~"void",
+                _ => match c_type(mt.ty) {
+                    Some(t) => t,
+                    None => return None,
+                },
+            };
+            // A pointer pointee is const-qualified after its own `*`, so that
+            // the constness of every level of indirection is kept
+            match mt.mutbl {
+                ast::MutMutable => format!("{} *", pointee),
+                ast::MutImmutable if pointee.ends_with("*") => {
+                    format!("{}const *", pointee)
+                }
+                ast::MutImmutable => format!("const {} *", pointee),
+            }
+        }
+        _ => return None,
+    };
+    Some(ty)
+}
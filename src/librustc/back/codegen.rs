@@ -0,0 +1,855 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The backends which turn a translated crate into the output files of
+//! `--emit`. Which one is used is selected with `-Z codegen-backend=NAME`,
+//! and LLVM is the default. The session owns the backend it selected, which
+//! in turn owns whatever it needs to generate code for the target, and
+//! decides which passes the crate goes through.
+
+use back::link::{llvm_err, output_type};
+use back::link::{output_type_assembly, output_type_bitcode};
+use back::link::{output_type_exe, output_type_llvm_assembly};
+use back::link::{output_type_none, output_type_object};
+use back::link;
+use back::lto;
+use driver::driver::CrateTranslation;
+use driver::session::Session;
+use driver::session;
+use lib::llvm::llvm;
+use lib::llvm::{ContextRef, ModuleRef, TargetMachineRef, PassManagerRef};
+use lib::llvm::TargetLibraryInfoRef;
+use lib;
+use util::common::time;
+
+use std::c_str::ToCStr;
+use std::comm::{PortOne, oneshot};
+use std::hashmap::HashMap;
+use std::io::fs;
+use std::libc::{c_char, c_uint, c_int};
+use std::path::Path;
+use std::str;
+use std::task;
+use std::vec;
+use extra::time::precise_time_s;
+use syntax::abi;
+
+/// The name of the backend used when none is given on the command line
+pub static DEFAULT_BACKEND: &'static str = "llvm";
+
+pub trait CodegenBackend {
+    /// The name this backend is selected with
+    fn name(&self) -> &'static str;
+
+    /// Optimizes the translated crate and writes it to `output` as
+    /// `output_type`. The modules of the translation are consumed.
+    fn run_passes(&self, sess: Session, trans: &CrateTranslation,
+                  output_type: output_type, output: &Path);
+
+    /// Generates the object file of an rlib built with `-C bitcode-only-rlib`
+    /// from the bitcode of its codegen units, when it's linked
+    fn compile_rlib_bitcode(&self, sess: Session, name: &str, rlib: &Path,
+                            output: &Path);
+
+    /// Assembles the assembly file written by `run_passes` into an object
+    /// file, for `-Z no-integrated-as`
+    fn run_assembler(&self, sess: Session, assembly: &Path, object: &Path) {
+        link::write::run_assembler(sess, assembly, object)
+    }
+
+    /// Moves the debuginfo of an object file written by `run_passes` into a
+    /// separate file, for `-Z split-dwarf`
+    fn split_dwarf(&self, sess: Session, object: &Path) {
+        link::write::split_dwarf(sess, object)
+    }
+}
+
+/// The backend driving LLVM's pass managers and target machine
+pub struct LlvmBackend {
+    // Created the first time code is generated, see `target_machine`
+    priv target_machine: @mut Option<@TargetMachine>,
+}
+
+impl LlvmBackend {
+    pub fn new() -> LlvmBackend {
+        LlvmBackend { target_machine: @mut None }
+    }
+
+    // The target machine of the session. LLVM is configured, and its targets
+    // are initialized, before the machine is first created.
+    fn target_machine(&self, sess: Session) -> @TargetMachine {
+        match *self.target_machine {
+            Some(machine) => return machine,
+            None => {}
+        }
+
+        unsafe {
+            llvm::LLVMInitializePasses();
+
+            // Only initialize the platforms supported by Rust here, because
+            // using --llvm-root will have multiple platforms that rustllvm
+            // doesn't actually link to and it's pointless to put target info
+            // into the registry that Rust can not generate machine code for.
+            llvm::LLVMInitializeX86TargetInfo();
+            llvm::LLVMInitializeX86Target();
+            llvm::LLVMInitializeX86TargetMC();
+            llvm::LLVMInitializeX86AsmPrinter();
+            llvm::LLVMInitializeX86AsmParser();
+
+            llvm::LLVMInitializeARMTargetInfo();
+            llvm::LLVMInitializeARMTarget();
+            llvm::LLVMInitializeARMTargetMC();
+            llvm::LLVMInitializeARMAsmPrinter();
+            llvm::LLVMInitializeARMAsmParser();
+
+            llvm::LLVMInitializeMipsTargetInfo();
+            llvm::LLVMInitializeMipsTarget();
+            llvm::LLVMInitializeMipsTargetMC();
+            llvm::LLVMInitializeMipsAsmPrinter();
+            llvm::LLVMInitializeMipsAsmParser();
+
+            configure_llvm(sess);
+        }
+
+        let machine = @TargetMachine::new(&target_options(sess));
+        *self.target_machine = Some(machine);
+        machine
+    }
+}
+
+impl CodegenBackend for LlvmBackend {
+    fn name(&self) -> &'static str { "llvm" }
+
+    fn run_passes(&self, sess: Session, trans: &CrateTranslation,
+                  output_type: output_type, output: &Path) {
+        let llmod = trans.module;
+        let llcx = trans.context;
+        unsafe {
+            if sess.opts.save_temps {
+                output.with_extension("no-opt.bc").with_c_str(|buf| {
+                    llvm::LLVMWriteBitcodeToFile(llmod, buf);
+                })
+            }
+
+            if sess.quick_codegen() && sess.opts.optimize != session::No {
+                sess.warn("`-Z quick-codegen` ignores the optimization level");
+            }
+
+            let machine = self.target_machine(sess);
+            let tm = machine.tm;
+
+            // Create the two optimizing pass managers. These mirror what clang
+            // does, and are by populated by LLVM's default PassManagerBuilder.
+            // Each manager has a different set of passes, but they also share
+            // some common passes.
+            //
+            // With `-Z verify-llvm-each-pass` they're managers which verify the
+            // IR after each of their passes.
+            let (fpm, mpm) = if sess.verify_llvm_each_pass() {
+                (llvm::LLVMRustCreateVerifyingFunctionPassManager(llmod),
+                 llvm::LLVMRustCreateVerifyingPassManager())
+            } else {
+                (llvm::LLVMCreateFunctionPassManagerForModule(llmod),
+                 llvm::LLVMCreatePassManager())
+            };
+
+            // With `-Z quick-codegen` the function pass manager is left
+            // empty and never run, which also leaves out the verifier, and the
+            // module passes only do what's needed for correct code.
+            let quick = sess.quick_codegen();
+
+            // If we're verifying or linting, add them to the function pass
+            // manager.
+            let addpass = |pass: &str| {
+                pass.with_c_str(|s| llvm::LLVMRustAddPass(fpm, s))
+            };
+            if !quick && !sess.no_verify() { assert!(addpass("verify")); }
+            if !quick && sess.lint_llvm()  { assert!(addpass("lint"));   }
+
+            if !sess.no_prepopulate_passes() {
+                llvm::LLVMRustAddAnalysisPasses(tm, mpm, llmod);
+                if quick {
+                    populate_quick_passes(mpm, machine.tli);
+                } else {
+                    llvm::LLVMRustAddAnalysisPasses(tm, fpm, llmod);
+                    populate_llvm_passes(fpm, mpm, machine.tli,
+                                         target_options(sess).opt_level,
+                                         sess.merge_functions());
+                }
+            }
+
+            for pass in sess.opts.custom_passes.iter() {
+                pass.with_c_str(|s| {
+                    // The names were checked along with the other options
+                    assert!(llvm::LLVMRustAddPass(mpm, s));
+                })
+            }
+
+            // Finally, run the actual optimization passes
+            if !quick {
+                time(sess.time_passes(), "llvm function passes", (), |()| {
+                    if sess.self_profile() {
+                        run_function_passes_profiled(sess, fpm, llmod);
+                    } else {
+                        llvm::LLVMRustRunFunctionPassManager(fpm, llmod);
+                    }
+                });
+            }
+            time(sess.time_passes(), "llvm module passes", (), |()|
+                 llvm::LLVMRunPassManager(mpm, llmod));
+
+            // Deallocate managers that we're now done with
+            llvm::LLVMDisposePassManager(fpm);
+            llvm::LLVMDisposePassManager(mpm);
+
+            // Emit the bytecode if we're either saving our temporaries or
+            // emitting an rlib. Whenever an rlib is create, the bytecode is
+            // inserted into the archive in order to allow LTO against it.
+            if sess.opts.save_temps {
+                output.with_extension("bc").with_c_str(|buf| {
+                    llvm::LLVMWriteBitcodeToFile(llmod, buf);
+                })
+            }
+            if sess.outputs.iter().any(|&o| o == session::OutputRlib) {
+                lto::write_bytecode(llmod, trans.link.pkgid.name,
+                                    trans.tmpdir.path());
+            }
+
+            if sess.lto() {
+                time(sess.time_passes(), "all lto passes", (), |()|
+                     lto::run(sess, llmod, tm, trans.reachable));
+
+                if sess.opts.save_temps {
+                    output.with_extension("lto.bc").with_c_str(|buf| {
+                        llvm::LLVMWriteBitcodeToFile(llmod, buf);
+                    })
+                }
+            }
+
+            if sess.opts.cg.embed_bitcode.unwrap_or(false) {
+                match output_type {
+                    output_type_exe | output_type_object => {
+                        embed_bitcode(sess, llcx, llmod, output);
+                    }
+                    _ => {}
+                }
+            }
+
+            let metadata_out = output.with_extension("metadata.o");
+            let mut metadata = MetadataCodegen::new(trans);
+            time(sess.time_passes(), "codegen passes", (), |()| {
+                match output_type {
+                    output_type_none => {}
+                    output_type_bitcode => {
+                        output.with_c_str(|buf| {
+                            llvm::LLVMWriteBitcodeToFile(llmod, buf);
+                        })
+                    }
+                    output_type_llvm_assembly => {
+                        output.with_c_str(|output| {
+                            with_codegen(machine, llmod, |cpm| {
+                                llvm::LLVMRustPrintModule(cpm, llmod, output);
+                            })
+                        });
+                        filter_output(sess, output, true);
+                    }
+                    output_type_assembly => {
+                        // If we're not using the LLVM assembler, this function
+                        // could be invoked specially with output_type_assembly,
+                        // so in this case we still want the metadata object
+                        // file.
+                        if sess.opts.output_type != output_type_assembly &&
+                           sess.needs_metadata_object() {
+                            metadata.spawn(sess, &metadata_out);
+                        }
+
+                        with_codegen(machine, llmod, |cpm| {
+                            WriteOutputFile(sess, tm, cpm, llmod, output,
+                                            lib::llvm::AssemblyFile);
+                        });
+                        if sess.opts.output_type == output_type_assembly {
+                            if sess.asm_comments() {
+                                annotate_assembly(sess, output);
+                            }
+                            filter_output(sess, output, false);
+                        }
+
+                        metadata.wait(sess, &metadata_out);
+                    }
+                    output_type_exe | output_type_object => {
+                        if sess.needs_metadata_object() {
+                            metadata.spawn(sess, &metadata_out);
+                        }
+                        with_codegen(machine, llmod, |cpm| {
+                            WriteOutputFile(sess, tm, cpm, llmod, output,
+                                            lib::llvm::ObjectFile);
+                        });
+                        metadata.wait(sess, &metadata_out);
+                    }
+                }
+            });
+
+            llvm::LLVMDisposeModule(llmod);
+            llvm::LLVMContextDispose(llcx);
+            match sess.opts.debugging_values.llvm_stats {
+                Some(ref path) => {
+                    let written = path.with_c_str(|p| {
+                        llvm::LLVMRustWritePassTimingsAndStats(p)
+                    });
+                    if !written {
+                        sess.err(format!("could not write the LLVM statistics \
+                                          to {}", path.display()));
+                    }
+                }
+                None if sess.time_llvm_passes() => {
+                    llvm::LLVMRustPrintPassTimings();
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn compile_rlib_bitcode(&self, sess: Session, name: &str, rlib: &Path,
+                            output: &Path) {
+        unsafe {
+            let llcx = llvm::LLVMContextCreate();
+            let llmod = name.with_c_str(|buf| {
+                llvm::LLVMModuleCreateWithNameInContext(buf, llcx)
+            });
+            let target_strs = &sess.targ_cfg.target_strs;
+            target_strs.data_layout.with_c_str(|buf| {
+                llvm::LLVMSetDataLayout(llmod, buf);
+            });
+            target_strs.target_triple.with_c_str(|buf| {
+                llvm::LLVMRustSetNormalizedTarget(llmod, buf);
+            });
+
+            for (unit, bc) in lto::read_bytecode(sess, rlib, name).move_iter() {
+                lto::link_unit(sess, llmod, &unit, bc);
+            }
+
+            let machine = self.target_machine(sess);
+            with_codegen(machine, llmod, |cpm| {
+                WriteOutputFile(sess, machine.tm, cpm, llmod, output,
+                                lib::llvm::ObjectFile);
+            });
+            llvm::LLVMDisposeModule(llmod);
+            llvm::LLVMContextDispose(llcx);
+        }
+    }
+}
+
+/// All the backends compiled into rustc
+pub fn backends() -> ~[@CodegenBackend] {
+    ~[@LlvmBackend::new() as @CodegenBackend]
+}
+
+/// The backend with the given name, if there is one
+pub fn find_backend(name: &str) -> Option<@CodegenBackend> {
+    backends().move_iter().find(|backend| backend.name() == name)
+}
+
+// Runs the function passes over one function at a time, so that
+// `-Z self-profile` can tell how long each of them took
+unsafe fn run_function_passes_profiled(sess: Session, fpm: PassManagerRef,
+                                       llmod: ModuleRef) {
+    llvm::LLVMInitializeFunctionPassManager(fpm);
+    let mut f = llvm::LLVMGetFirstFunction(llmod);
+    while f.is_not_null() {
+        if llvm::LLVMIsDeclaration(f) == lib::llvm::False {
+            let name = str::raw::from_c_str(llvm::LLVMGetValueName(f));
+            sess.profile("llvm function passes", name, || {
+                llvm::LLVMRunFunctionPassManager(fpm, f);
+            });
+        }
+        f = llvm::LLVMGetNextFunction(f);
+    }
+    llvm::LLVMFinalizeFunctionPassManager(fpm);
+}
+
+/// The LLVM target machine of a session, along with the library info of
+/// its target. They're created the first time code is generated and then
+/// shared by the optimization passes, LTO, and the code generation of
+/// every module and output file.
+pub struct TargetMachine {
+    priv tm: TargetMachineRef,
+    priv tli: TargetLibraryInfoRef,
+}
+
+// Everything that's needed to create a target machine. Unlike the
+// session, this can be sent to another task.
+struct TargetOptions {
+    triple: ~str,
+    cpu: ~str,
+    features: ~str,
+    opt_level: lib::llvm::CodeGenOptLevel,
+    soft_float: bool,
+    no_fp_elim: bool,
+    segmented_stacks: bool,
+    function_sections: bool,
+    data_sections: bool,
+}
+
+fn target_options(sess: Session) -> TargetOptions {
+    TargetOptions {
+        triple: sess.targ_cfg.target_strs.target_triple.clone(),
+        cpu: sess.opts.target_cpu.clone(),
+        features: sess.opts.target_feature.clone(),
+        opt_level: match sess.opts.optimize {
+            _ if sess.quick_codegen() => lib::llvm::CodeGenLevelNone,
+            session::No => lib::llvm::CodeGenLevelNone,
+            session::Less => lib::llvm::CodeGenLevelLess,
+            session::Default => lib::llvm::CodeGenLevelDefault,
+            session::Aggressive => lib::llvm::CodeGenLevelAggressive,
+        },
+        soft_float: sess.opts.cg.soft_float,
+        // Frame pointers are kept unless asked otherwise, so that
+        // profilers and debuggers can always walk the stack
+        no_fp_elim: sess.opts.cg.force_frame_pointers.unwrap_or(true),
+        segmented_stacks: !*sess.no_split_stack,
+        // The linker can only fold functions which are in sections of
+        // their own
+        function_sections: sess.opts.cg.function_sections ||
+                           sess.opts.cg.icf,
+        data_sections: sess.opts.cg.data_sections,
+    }
+}
+
+impl TargetMachine {
+    fn new(opts: &TargetOptions) -> TargetMachine {
+        unsafe {
+            let tm = opts.triple.with_c_str(|T| {
+                opts.cpu.with_c_str(|CPU| {
+                    opts.features.with_c_str(|Features| {
+                        llvm::LLVMRustCreateTargetMachine(
+                            T, CPU, Features,
+                            lib::llvm::CodeModelDefault,
+                            lib::llvm::RelocPIC,
+                            opts.opt_level,
+                            opts.segmented_stacks,
+                            opts.soft_float,
+                            opts.no_fp_elim,
+                            opts.function_sections,
+                            opts.data_sections
+                        )
+                    })
+                })
+            });
+            let tli = opts.triple.with_c_str(|T| {
+                llvm::LLVMRustCreateTargetLibraryInfo(T)
+            });
+            TargetMachine { tm: tm, tli: tli }
+        }
+    }
+}
+
+impl Drop for TargetMachine {
+    fn drop(&mut self) {
+        unsafe {
+            llvm::LLVMRustDisposeTargetLibraryInfo(self.tli);
+            llvm::LLVMRustDisposeTargetMachine(self.tm);
+        }
+    }
+}
+
+pub fn WriteOutputFile(
+        sess: Session,
+        Target: lib::llvm::TargetMachineRef,
+        PM: lib::llvm::PassManagerRef,
+        M: ModuleRef,
+        Output: &Path,
+        FileType: lib::llvm::FileType) {
+    let name = Output.display().to_str();
+    // The code generator verifies the module first, unless asked to be quick
+    let disable_verify = sess.quick_codegen();
+    sess.profile("write output", name, || unsafe {
+        Output.with_c_str(|Output| {
+            let result = llvm::LLVMRustWriteOutputFile(
+                    Target, PM, M, Output, FileType, disable_verify);
+            if !result {
+                llvm_err(sess, ~"Could not write output");
+            }
+        })
+    })
+}
+
+// A codegen-specific pass manager is used to generate object files for an
+// LLVM module.
+//
+// Apparently each of these pass managers is a one-shot kind of thing, so
+// we create a new one for each type of output. The pass manager passed to
+// the closure should be ensured to not escape the closure itself, and the
+// manager should only be used once. The target machine and library info
+// behind the analysis passes are shared, so that only the passes
+// themselves are created for each output.
+fn with_codegen(machine: &TargetMachine, llmod: ModuleRef,
+                f: |PassManagerRef|) {
+    unsafe {
+        let cpm = llvm::LLVMCreatePassManager();
+        llvm::LLVMRustAddAnalysisPasses(machine.tm, cpm, llmod);
+        llvm::LLVMRustAddLibraryInfo(cpm, machine.tli);
+        f(cpm);
+        llvm::LLVMDisposePassManager(cpm);
+    }
+}
+
+// The section holding the bitcode of an object file built with
+// `-C embed-bitcode=yes`, where the platform's tools look for it
+fn bitcode_section_name(os: abi::Os) -> &'static str {
+    match os {
+        abi::OsMacos => "__LLVM,__bitcode",
+        _ => ".llvmbc",
+    }
+}
+
+// Stores the bitcode of the module in a section of the object file which
+// is generated from it, for platforms which require programs to be
+// submitted with their bitcode. LLVM can only write bitcode to a file, so
+// it's read back from a temporary one.
+unsafe fn embed_bitcode(sess: Session, llcx: ContextRef, llmod: ModuleRef,
+                        output: &Path) {
+    let tmp = output.with_extension("embed.bc");
+    tmp.with_c_str(|buf| {
+        llvm::LLVMWriteBitcodeToFile(llmod, buf);
+    });
+    let bc = fs::File::open(&tmp).read_to_end();
+    fs::unlink(&tmp);
+
+    let llconst = llvm::LLVMConstStringInContext(llcx,
+                                                 vec::raw::to_ptr(bc) as *c_char,
+                                                 bc.len() as c_uint,
+                                                 lib::llvm::True);
+    let llglobal = "rustc.embedded.module".with_c_str(|buf| {
+        llvm::LLVMAddGlobal(llmod, llvm::LLVMTypeOf(llconst), buf)
+    });
+    llvm::LLVMSetInitializer(llglobal, llconst);
+    llvm::LLVMSetGlobalConstant(llglobal, lib::llvm::True);
+    lib::llvm::SetLinkage(llglobal, lib::llvm::PrivateLinkage);
+    bitcode_section_name(sess.targ_cfg.os).with_c_str(|buf| {
+        llvm::LLVMSetSection(llglobal, buf);
+    });
+}
+
+// Writes the object file of the metadata module in a task of its own, so
+// that it's generated at the same time as the crate's module. LLVM doesn't
+// let two threads use a context or a target machine at once, so the
+// module has a context of its own and the task creates its own target
+// machine. The task disposes of the module and its context when it's done.
+//
+// Under `make -jN`, a token is taken from the jobserver before the task is
+// started and held until it's done. If make didn't let us use the
+// jobserver, the object is written right away on this task instead.
+//
+// Whether or not the task was started, this is cleaned up after when it goes
+// out of scope, even if the code generation of the crate failed: the task is
+// waited for, or else the module is disposed of here.
+struct MetadataCodegen {
+    priv llcx: ContextRef,
+    priv llmod: ModuleRef,
+    priv spawned: bool,
+    // Sent whether the object was written, and the error LLVM had if it
+    // wasn't, along with when and for how long it was being written
+    priv port: Option<PortOne<(bool, Option<~str>, f64, f64)>>,
+}
+
+impl MetadataCodegen {
+    fn new(trans: &CrateTranslation) -> MetadataCodegen {
+        MetadataCodegen {
+            llcx: trans.metadata_context,
+            llmod: trans.metadata_module,
+            spawned: false,
+            port: None,
+        }
+    }
+
+    fn spawn(&mut self, sess: Session, out: &Path) {
+        let opts = target_options(sess);
+        let disable_verify = sess.quick_codegen();
+        // The LLVM handles are only ever used by the new task
+        let llcx = self.llcx as uint;
+        let llmod = self.llmod as uint;
+        let out = out.clone();
+        let (port, chan) = oneshot();
+        let token = match sess.jobserver {
+            Some(ref client) => match client.acquire() {
+                Some(token) => Some(token),
+                None => {
+                    chan.send(write_metadata_object(&opts, llcx, llmod, &out,
+                                                    disable_verify));
+                    self.spawned = true;
+                    self.port = Some(port);
+                    return
+                }
+            },
+            None => None,
+        };
+        let mut task = task::task();
+        task.name("metadata codegen");
+        task.sched_mode(task::SingleThreaded);
+        task.spawn(proc() {
+            let _token = token;
+            chan.send(write_metadata_object(&opts, llcx, llmod, &out,
+                                            disable_verify));
+        });
+        self.spawned = true;
+        self.port = Some(port);
+    }
+
+    // Waits for the task to write the object, if it was started
+    fn wait(&mut self, sess: Session, out: &Path) {
+        let port = match self.port.take() {
+            Some(port) => port,
+            None => return,
+        };
+        let (written, error, start, time) = port.recv();
+        sess.record_profile_event("write output", out.display().to_str(),
+                                  start, time);
+        if !written {
+            link::report_llvm_err(sess, ~"Could not write output", error);
+        }
+    }
+}
+
+impl Drop for MetadataCodegen {
+    fn drop(&mut self) {
+        if !self.spawned {
+            unsafe {
+                llvm::LLVMDisposeModule(self.llmod);
+                llvm::LLVMContextDispose(self.llcx);
+            }
+            return
+        }
+        match self.port.take() {
+            // The task may have failed too, which isn't reported twice
+            Some(port) => { port.try_recv(); }
+            None => {}
+        }
+    }
+}
+
+// Writes the metadata module to an object file and disposes of it and its
+// context, on whichever task it's called from
+fn write_metadata_object(opts: &TargetOptions, llcx: uint, llmod: uint,
+                         out: &Path, disable_verify: bool)
+                         -> (bool, Option<~str>, f64, f64) {
+    let llcx = llcx as ContextRef;
+    let llmod = llmod as ModuleRef;
+    let machine = TargetMachine::new(opts);
+    let start = precise_time_s();
+    let mut written = false;
+    with_codegen(&machine, llmod, |cpm| {
+        written = out.with_c_str(|out| unsafe {
+            llvm::LLVMRustWriteOutputFile(machine.tm, cpm, llmod, out,
+                                          lib::llvm::ObjectFile,
+                                          disable_verify)
+        });
+    });
+    let end = precise_time_s();
+    // Each thread has an error of its own
+    let error = if written { None } else { link::llvm_last_error() };
+    unsafe {
+        llvm::LLVMDisposeModule(llmod);
+        llvm::LLVMContextDispose(llcx);
+    }
+    (written, error, start, end - start)
+}
+
+// Cuts the LLVM IR or assembly written to `output` down to the functions
+// selected with `-Z print-fn`
+fn filter_output(sess: Session, output: &Path, ir: bool) {
+    match sess.opts.debugging_values.print_fn {
+        Some(ref pattern) => {
+            let text = str::from_utf8_owned(fs::File::open(output).read_to_end());
+            let filtered = link::filter_functions(text, *pattern, ir);
+            fs::File::create(output).write(filtered.as_bytes());
+        }
+        None => {}
+    }
+}
+
+// Interleaves the source code into the assembly written to `output`: each
+// line of source is added as a comment where the instructions generated
+// for it start. They're found through the `.file` and `.loc` directives of
+// the debug info's line tables.
+fn annotate_assembly(sess: Session, output: &Path) {
+    let comment = match sess.targ_cfg.arch {
+        abi::Arm => "@",
+        _ => "#",
+    };
+    let asm = str::from_utf8_owned(fs::File::open(output).read_to_end());
+    let mut files = HashMap::new();
+    let mut last = None;
+    let mut annotated = ~"";
+    for line in asm.lines() {
+        annotated.push_str(line);
+        annotated.push_char('\n');
+
+        let words: ~[&str] = line.words().collect();
+        if words.len() < 3 { continue }
+        if words[0] == ".file" {
+            let name = words[2].trim_chars(&'"');
+            let path = Path::new(name);
+            let file = sess.codemap.files.iter().find(|fm| {
+                fm.name.as_slice() == name ||
+                    sess.working_dir.join(fm.name.as_slice()) == path
+            });
+            match file {
+                Some(fm) => { files.insert(words[1].to_owned(), *fm); }
+                None => {}
+            }
+        } else if words[0] == ".loc" {
+            let line_no = match from_str::<int>(words[2]) {
+                Some(n) if n > 0 => n,
+                _ => continue,
+            };
+            let file = match files.find_equiv(&words[1]) {
+                Some(fm) => *fm,
+                None => continue,
+            };
+            if last == Some((words[1].to_owned(), line_no)) { continue }
+            last = Some((words[1].to_owned(), line_no));
+            if line_no as uint > file.lines.len() { continue }
+            annotated.push_str(format!("\t{} {}:{}: {}\n", comment,
+                                       file.name, line_no,
+                                       file.get_line(line_no - 1).trim()));
+        }
+    }
+    fs::File::create(output).write(annotated.as_bytes());
+}
+
+unsafe fn configure_llvm(sess: Session) {
+    // Copy what clan does by turning on loop vectorization at O2 and
+    // slp vectorization at O3
+    let vectorize_loop = !sess.no_vectorize_loops() &&
+                         (sess.opts.optimize == session::Default ||
+                          sess.opts.optimize == session::Aggressive);
+    let vectorize_slp = !sess.no_vectorize_slp() &&
+                        sess.opts.optimize == session::Aggressive;
+
+    let mut llvm_c_strs = ~[];
+    let mut llvm_args = ~[];
+    let add = |arg: &str| {
+        let s = arg.to_c_str();
+        llvm_args.push(s.with_ref(|p| p));
+        llvm_c_strs.push(s);
+    };
+    add("rustc"); // fake program name
+    add("-arm-enable-ehabi");
+    add("-arm-enable-ehabi-descriptors");
+    if vectorize_loop { add("-vectorize-loops"); }
+    if vectorize_slp  { add("-vectorize-slp");   }
+    // Instruction selection is left to FastISel wherever it can handle
+    // the code, rather than only at -O0
+    if sess.quick_codegen() { add("-fast-isel"); }
+    if sess.time_llvm_passes() || sess.opts.debugging_values.llvm_stats.is_some() {
+        add("-time-passes");
+    }
+    if sess.opts.debugging_values.llvm_stats.is_some() { add("-stats"); }
+    if sess.print_llvm_passes() { add("-debug-pass=Structure"); }
+    if sess.split_dwarf() { add("-split-dwarf=Enable"); }
+    if sess.asm_comments() { add("-asm-verbose"); }
+    if sess.opts.cg.asm_syntax == session::AsmSyntaxIntel {
+        match sess.targ_cfg.arch {
+            abi::X86 | abi::X86_64 => add("-x86-asm-syntax=intel"),
+            _ => sess.warn("`-C asm-syntax` only applies to x86 targets"),
+        }
+    }
+
+    let first = llvm_args.as_imm_buf(|p, len| {
+        llvm::LLVMRustSetLLVMOptions(len as c_int, p)
+    });
+
+    // LLVM's own parser exits the process on a value it rejects, so the
+    // options of `--llvm-args` are applied one at a time, after the ones
+    // above, and a rejected one is reported as an error
+    if first {
+        for arg in sess.opts.llvm_args.iter() {
+            if !arg.with_c_str(|s| llvm::LLVMRustAddLLVMOption(s)) {
+                sess.err(format!("LLVM rejected the option `{}`", *arg));
+            }
+        }
+        sess.abort_if_errors();
+    }
+}
+
+/// Whether LLVM has a pass of this name, for `--passes`
+pub fn is_known_pass(name: &str) -> bool {
+    name.with_c_str(|s| unsafe { llvm::LLVMRustHasPass(s) })
+}
+
+/// Whether an argument of `--llvm-args`, such as `-debug-pass=Arguments`,
+/// names an option of LLVM
+pub fn is_known_llvm_arg(arg: &str) -> bool {
+    if !arg.starts_with("-") {
+        return false;
+    }
+    let name = arg.trim_left_chars(&'-');
+    let name = match name.find('=') {
+        Some(i) => name.slice_to(i),
+        None => name,
+    };
+    !name.is_empty() &&
+        name.with_c_str(|s| unsafe { llvm::LLVMRustHasLLVMOption(s) })
+}
+
+unsafe fn populate_llvm_passes(fpm: lib::llvm::PassManagerRef,
+                               mpm: lib::llvm::PassManagerRef,
+                               tli: TargetLibraryInfoRef,
+                               opt: lib::llvm::CodeGenOptLevel,
+                               merge_functions: bool) {
+    // Create the PassManagerBuilder for LLVM. We configure it with
+    // reasonable defaults and prepare it to actually populate the pass
+    // manager.
+    let builder = llvm::LLVMPassManagerBuilderCreate();
+    match opt {
+        lib::llvm::CodeGenLevelNone => {
+            // Don't add lifetime intrinsics add O0
+            llvm::LLVMRustAddAlwaysInlinePass(builder, false);
+        }
+        lib::llvm::CodeGenLevelLess => {
+            llvm::LLVMRustAddAlwaysInlinePass(builder, true);
+        }
+        // numeric values copied from clang
+        lib::llvm::CodeGenLevelDefault => {
+            llvm::LLVMPassManagerBuilderUseInlinerWithThreshold(builder,
+                                                                225);
+        }
+        lib::llvm::CodeGenLevelAggressive => {
+            llvm::LLVMPassManagerBuilderUseInlinerWithThreshold(builder,
+                                                                275);
+        }
+    }
+    llvm::LLVMPassManagerBuilderSetOptLevel(builder, opt as c_uint);
+    llvm::LLVMRustAddBuilderLibraryInfo(builder, tli);
+
+    // Use the builder to populate the function/module pass managers.
+    llvm::LLVMPassManagerBuilderPopulateFunctionPassManager(builder, fpm);
+    llvm::LLVMPassManagerBuilderPopulateModulePassManager(builder, mpm);
+    llvm::LLVMPassManagerBuilderDispose(builder);
+
+    // Identical instantiations of generic functions and trait object
+    // shims are merged once everything else has been optimized, as that's
+    // when most of them are identical.
+    if merge_functions {
+        assert!("mergefunc".with_c_str(|s| llvm::LLVMRustAddPass(mpm, s)));
+    }
+}
+
+// The module passes of `-Z quick-codegen`: those of -O0, which only
+// inline #[inline(always)] functions, without lifetime intrinsics.
+unsafe fn populate_quick_passes(mpm: lib::llvm::PassManagerRef,
+                                tli: TargetLibraryInfoRef) {
+    let builder = llvm::LLVMPassManagerBuilderCreate();
+    llvm::LLVMRustAddAlwaysInlinePass(builder, false);
+    llvm::LLVMPassManagerBuilderSetOptLevel(builder, 0);
+    llvm::LLVMRustAddBuilderLibraryInfo(builder, tli);
+    llvm::LLVMPassManagerBuilderPopulateModulePassManager(builder, mpm);
+    llvm::LLVMPassManagerBuilderDispose(builder);
+}
@@ -0,0 +1,135 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The graph of the crates a crate depends on, as resolved by this session
+//! (`--emit dep-graph`). Each of the crates is described along with the rlib
+//! and dylib found for it and the way it's linked into the outputs, in DOT
+//! form for humans and JSON form for build tools.
+
+use back::link;
+use driver::session::{Session, OutputRlib, output_style_name};
+use metadata::common::LinkMeta;
+use metadata::cstore;
+
+use extra::json;
+use extra::sort;
+use extra::treemap::TreeMap;
+use syntax::ast;
+
+struct Node {
+    cnum: ast::CrateNum,
+    name: ~str,
+    version: ~str,
+    hash: ~str,
+    rlib: Option<Path>,
+    dylib: Option<Path>,
+    // How the crate is linked into each of the outputs which link upstream
+    // crates, by the output's type: "static" or "dynamic"
+    linkage: ~[(&'static str, &'static str)],
+    deps: ~[ast::CrateNum],
+}
+
+// The crate being compiled is node 0, followed by the upstream crates in the
+// order of their crate numbers
+fn nodes(sess: Session, lm: &LinkMeta) -> ~[Node] {
+    let cstore = sess.cstore;
+    let statics = cstore::get_used_crates(cstore, cstore::RequireStatic);
+    let dynamics = cstore::get_used_crates(cstore, cstore::RequireDynamic);
+    let linked = (*sess.outputs).iter().filter(|&&o| o != OutputRlib).map(|&o| {
+        (output_style_name(o), link::output_dynamic_crates(sess, o))
+    }).to_owned_vec();
+
+    let mut nodes = ~[Node {
+        cnum: ast::LOCAL_CRATE,
+        name: lm.pkgid.name.clone(),
+        version: lm.pkgid.version_or_default().to_owned(),
+        hash: lm.crate_hash.to_owned(),
+        rlib: None,
+        dylib: None,
+        linkage: ~[],
+        deps: ~[],
+    }];
+    for (&(cnum, ref rlib), &(_, ref dylib)) in statics.iter().zip(dynamics.iter()) {
+        let data = cstore::get_crate_data(cstore, cnum);
+        let mut deps = data.cnum_map.iter().map(|(_, &dep)| dep).to_owned_vec();
+        sort::quick_sort(deps, |a, b| *a <= *b);
+        nodes.push(Node {
+            cnum: cnum,
+            name: data.name.to_owned(),
+            version: cstore::get_crate_vers(cstore, cnum).to_owned(),
+            hash: cstore::get_crate_hash(cstore, cnum).to_owned(),
+            rlib: rlib.clone(),
+            dylib: dylib.clone(),
+            linkage: linked.iter().map(|&(output, ref dynamic)| {
+                (output, if dynamic.contains(&cnum) { "dynamic" } else { "static" })
+            }).collect(),
+            deps: deps,
+        });
+    }
+    // The crates are found in the order they were loaded in, and the local
+    // crate has the lowest number
+    sort::quick_sort(nodes, |a, b| a.cnum <= b.cnum);
+    nodes[0].deps = cstore::get_extern_mod_crates(cstore);
+    nodes
+}
+
+/// The dependency graph in the DOT language of graphviz
+pub fn to_dot(sess: Session, lm: &LinkMeta) -> ~str {
+    let nodes = nodes(sess, lm);
+    let mut dot = format!("digraph \"{}\" \\{\n", lm.pkgid.name);
+    for node in nodes.iter() {
+        let mut lines = ~[format!("{} {}", node.name, node.version),
+                          format!("hash {}", node.hash)];
+        for path in node.rlib.iter().chain(node.dylib.iter()) {
+            lines.push(path.display().to_str());
+        }
+        for &(output, linkage) in node.linkage.iter() {
+            lines.push(format!("{} linkage: {}", output, linkage));
+        }
+        let lines = lines.map(|l| l.replace("\\", "\\\\").replace("\"", "\\\""));
+        dot.push_str(format!("    c{} [label=\"{}\"];\n", node.cnum,
+                             lines.connect("\\n")));
+    }
+    for node in nodes.iter() {
+        for dep in node.deps.iter() {
+            dot.push_str(format!("    c{} -> c{};\n", node.cnum, *dep));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// The dependency graph as JSON: a list of crates, each with the crate
+/// numbers of those it depends on
+pub fn to_json(sess: Session, lm: &LinkMeta) -> ~str {
+    let nodes = nodes(sess, lm).move_iter().map(|node| {
+        let path = |p: Option<Path>| match p {
+            Some(p) => json::String(p.display().to_str()),
+            None => json::Null,
+        };
+        let mut obj = ~TreeMap::new();
+        obj.insert(~"cnum", json::Number(node.cnum as f64));
+        obj.insert(~"name", json::String(node.name));
+        obj.insert(~"version", json::String(node.version));
+        obj.insert(~"hash", json::String(node.hash));
+        obj.insert(~"rlib", path(node.rlib));
+        obj.insert(~"dylib", path(node.dylib));
+        let mut linkage = ~TreeMap::new();
+        for &(output, how) in node.linkage.iter() {
+            linkage.insert(output.to_owned(), json::String(how.to_owned()));
+        }
+        obj.insert(~"linkage", json::Object(linkage));
+        obj.insert(~"dependencies", json::List(node.deps.map(|&dep| {
+            json::Number(dep as f64)
+        })));
+        json::Object(obj)
+    }).to_owned_vec();
+    json::List(nodes).to_pretty_str() + "\n"
+}
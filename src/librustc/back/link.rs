@@ -10,15 +10,15 @@
 
 
 use back::archive::{Archive, METADATA_FILENAME};
+use back::archive;
+use back::lto;
 use back::rpath;
-use driver::driver::CrateTranslation;
+use driver::driver::{CrateAnalysis, CrateTranslation};
 use driver::session::Session;
 use driver::session;
 use lib::llvm::llvm;
-use lib::llvm::ModuleRef;
-use lib;
 use metadata::common::LinkMeta;
-use metadata::{encoder, cstore, filesearch, csearch};
+use metadata::{encoder, cstore, decoder, filesearch, csearch, loader};
 use middle::trans::context::CrateContext;
 use middle::trans::common::gensym_name;
 use middle::ty;
@@ -26,20 +26,30 @@ use util::common::time;
 use util::ppaux;
 use util::sha2::{Digest, Sha256};
 
-use std::c_str::ToCStr;
+use std::ascii::StrAsciiExt;
 use std::char;
+use std::num;
+use std::hashmap::{HashMap, HashSet};
+use std::os;
 use std::os::consts::{macos, freebsd, linux, android, win32};
 use std::ptr;
 use std::run;
 use std::str;
+use std::io;
 use std::io::fs;
+use std::io::process::ProcessExit;
+use std::io::timer;
+use extra::sort;
 use extra::tempfile::TempDir;
 use syntax::abi;
 use syntax::ast;
 use syntax::ast_map::{path, path_mod, path_name, path_pretty_name};
+use syntax::ast_map;
 use syntax::attr;
 use syntax::attr::AttrMetaMethods;
+use syntax::codemap::Span;
 use syntax::pkgid::PkgId;
+use syntax::print::pprust;
 
 #[deriving(Clone, Eq)]
 pub enum output_type {
@@ -52,339 +62,78 @@ pub enum output_type {
 }
 
 pub fn llvm_err(sess: Session, msg: ~str) -> ! {
+    report_llvm_err(sess, msg, llvm_last_error())
+}
+
+/// The last error LLVM had on the current thread, if any
+pub fn llvm_last_error() -> Option<~str> {
     unsafe {
         let cstr = llvm::LLVMRustGetLastError();
         if cstr == ptr::null() {
-            sess.fatal(msg);
+            None
         } else {
-            sess.fatal(msg + ": " + str::raw::from_c_str(cstr));
+            Some(str::raw::from_c_str(cstr))
         }
     }
 }
 
-pub fn WriteOutputFile(
-        sess: Session,
-        Target: lib::llvm::TargetMachineRef,
-        PM: lib::llvm::PassManagerRef,
-        M: ModuleRef,
-        Output: &Path,
-        FileType: lib::llvm::FileType) {
-    unsafe {
-        Output.with_c_str(|Output| {
-            let result = llvm::LLVMRustWriteOutputFile(
-                    Target, PM, M, Output, FileType);
-            if !result {
-                llvm_err(sess, ~"Could not write output");
-            }
-        })
+/// Fails with `msg` and an error LLVM had, which may have been on another
+/// thread
+pub fn report_llvm_err(sess: Session, msg: ~str, err: Option<~str>) -> ! {
+    match err {
+        None => sess.fatal(msg),
+        Some(err) => sess.fatal(msg + ": " + demangle_symbols(err)),
     }
 }
 
+/// The steps of code generation which are left to external tools, whichever
+/// backend generated the code
 pub mod write {
-
-    use back::lto;
-    use back::link::{WriteOutputFile, output_type};
-    use back::link::{output_type_assembly, output_type_bitcode};
-    use back::link::{output_type_exe, output_type_llvm_assembly};
-    use back::link::{output_type_object};
-    use driver::driver::CrateTranslation;
     use driver::session::Session;
-    use driver::session;
-    use lib::llvm::llvm;
-    use lib::llvm::{ModuleRef, TargetMachineRef, PassManagerRef};
-    use lib;
-    use util::common::time;
-
-    use std::c_str::ToCStr;
-    use std::libc::{c_uint, c_int};
-    use std::path::Path;
-    use std::run;
-    use std::str;
-
-    pub fn run_passes(sess: Session,
-                      trans: &CrateTranslation,
-                      output_type: output_type,
-                      output: &Path) {
-        let llmod = trans.module;
-        let llcx = trans.context;
-        unsafe {
-            llvm::LLVMInitializePasses();
-
-            // Only initialize the platforms supported by Rust here, because
-            // using --llvm-root will have multiple platforms that rustllvm
-            // doesn't actually link to and it's pointless to put target info
-            // into the registry that Rust can not generate machine code for.
-            llvm::LLVMInitializeX86TargetInfo();
-            llvm::LLVMInitializeX86Target();
-            llvm::LLVMInitializeX86TargetMC();
-            llvm::LLVMInitializeX86AsmPrinter();
-            llvm::LLVMInitializeX86AsmParser();
-
-            llvm::LLVMInitializeARMTargetInfo();
-            llvm::LLVMInitializeARMTarget();
-            llvm::LLVMInitializeARMTargetMC();
-            llvm::LLVMInitializeARMAsmPrinter();
-            llvm::LLVMInitializeARMAsmParser();
-
-            llvm::LLVMInitializeMipsTargetInfo();
-            llvm::LLVMInitializeMipsTarget();
-            llvm::LLVMInitializeMipsTargetMC();
-            llvm::LLVMInitializeMipsAsmPrinter();
-            llvm::LLVMInitializeMipsAsmParser();
-
-            if sess.opts.save_temps {
-                output.with_extension("no-opt.bc").with_c_str(|buf| {
-                    llvm::LLVMWriteBitcodeToFile(llmod, buf);
-                })
-            }
-
-            configure_llvm(sess);
-
-            let OptLevel = match sess.opts.optimize {
-              session::No => lib::llvm::CodeGenLevelNone,
-              session::Less => lib::llvm::CodeGenLevelLess,
-              session::Default => lib::llvm::CodeGenLevelDefault,
-              session::Aggressive => lib::llvm::CodeGenLevelAggressive,
-            };
-            let use_softfp = sess.opts.debugging_opts & session::use_softfp != 0;
-
-            let tm = sess.targ_cfg.target_strs.target_triple.with_c_str(|T| {
-                sess.opts.target_cpu.with_c_str(|CPU| {
-                    sess.opts.target_feature.with_c_str(|Features| {
-                        llvm::LLVMRustCreateTargetMachine(
-                            T, CPU, Features,
-                            lib::llvm::CodeModelDefault,
-                            lib::llvm::RelocPIC,
-                            OptLevel,
-                            true,
-                            use_softfp
-                        )
-                    })
-                })
-            });
-
-            // Create the two optimizing pass managers. These mirror what clang
-            // does, and are by populated by LLVM's default PassManagerBuilder.
-            // Each manager has a different set of passes, but they also share
-            // some common passes.
-            let fpm = llvm::LLVMCreateFunctionPassManagerForModule(llmod);
-            let mpm = llvm::LLVMCreatePassManager();
-
-            // If we're verifying or linting, add them to the function pass
-            // manager.
-            let addpass = |pass: &str| {
-                pass.with_c_str(|s| llvm::LLVMRustAddPass(fpm, s))
-            };
-            if !sess.no_verify() { assert!(addpass("verify")); }
-            if sess.lint_llvm()  { assert!(addpass("lint"));   }
-
-            if !sess.no_prepopulate_passes() {
-                llvm::LLVMRustAddAnalysisPasses(tm, fpm, llmod);
-                llvm::LLVMRustAddAnalysisPasses(tm, mpm, llmod);
-                populate_llvm_passes(fpm, mpm, llmod, OptLevel);
-            }
-
-            for pass in sess.opts.custom_passes.iter() {
-                pass.with_c_str(|s| {
-                    if !llvm::LLVMRustAddPass(mpm, s) {
-                        sess.warn(format!("Unknown pass {}, ignoring", *pass));
-                    }
-                })
-            }
-
-            // Finally, run the actual optimization passes
-            time(sess.time_passes(), "llvm function passes", (), |()|
-                 llvm::LLVMRustRunFunctionPassManager(fpm, llmod));
-            time(sess.time_passes(), "llvm module passes", (), |()|
-                 llvm::LLVMRunPassManager(mpm, llmod));
-
-            // Deallocate managers that we're now done with
-            llvm::LLVMDisposePassManager(fpm);
-            llvm::LLVMDisposePassManager(mpm);
-
-            // Emit the bytecode if we're either saving our temporaries or
-            // emitting an rlib. Whenever an rlib is create, the bytecode is
-            // inserted into the archive in order to allow LTO against it.
-            if sess.opts.save_temps ||
-               sess.outputs.iter().any(|&o| o == session::OutputRlib) {
-                output.with_extension("bc").with_c_str(|buf| {
-                    llvm::LLVMWriteBitcodeToFile(llmod, buf);
-                })
-            }
-
-            if sess.lto() {
-                time(sess.time_passes(), "all lto passes", (), |()|
-                     lto::run(sess, llmod, tm, trans.reachable));
-
-                if sess.opts.save_temps {
-                    output.with_extension("lto.bc").with_c_str(|buf| {
-                        llvm::LLVMWriteBitcodeToFile(llmod, buf);
-                    })
-                }
-            }
 
-            // A codegen-specific pass manager is used to generate object
-            // files for an LLVM module.
-            //
-            // Apparently each of these pass managers is a one-shot kind of
-            // thing, so we create a new one for each type of output. The
-            // pass manager passed to the closure should be ensured to not
-            // escape the closure itself, and the manager should only be
-            // used once.
-            fn with_codegen(tm: TargetMachineRef, llmod: ModuleRef,
-                            f: |PassManagerRef|) {
-                unsafe {
-                    let cpm = llvm::LLVMCreatePassManager();
-                    llvm::LLVMRustAddAnalysisPasses(tm, cpm, llmod);
-                    llvm::LLVMRustAddLibraryInfo(cpm, llmod);
-                    f(cpm);
-                    llvm::LLVMDisposePassManager(cpm);
-                }
-            }
-
-            time(sess.time_passes(), "codegen passes", (), |()| {
-                match output_type {
-                    output_type_none => {}
-                    output_type_bitcode => {
-                        output.with_c_str(|buf| {
-                            llvm::LLVMWriteBitcodeToFile(llmod, buf);
-                        })
-                    }
-                    output_type_llvm_assembly => {
-                        output.with_c_str(|output| {
-                            with_codegen(tm, llmod, |cpm| {
-                                llvm::LLVMRustPrintModule(cpm, llmod, output);
-                            })
-                        })
-                    }
-                    output_type_assembly => {
-                        with_codegen(tm, llmod, |cpm| {
-                            WriteOutputFile(sess, tm, cpm, llmod, output,
-                                            lib::llvm::AssemblyFile);
-                        });
-
-                        // If we're not using the LLVM assembler, this function
-                        // could be invoked specially with output_type_assembly,
-                        // so in this case we still want the metadata object
-                        // file.
-                        if sess.opts.output_type != output_type_assembly {
-                            with_codegen(tm, trans.metadata_module, |cpm| {
-                                let out = output.with_extension("metadata.o");
-                                WriteOutputFile(sess, tm, cpm,
-                                                trans.metadata_module, &out,
-                                                lib::llvm::ObjectFile);
-                            })
-                        }
-                    }
-                    output_type_exe | output_type_object => {
-                        with_codegen(tm, llmod, |cpm| {
-                            WriteOutputFile(sess, tm, cpm, llmod, output,
-                                            lib::llvm::ObjectFile);
-                        });
-                        with_codegen(tm, trans.metadata_module, |cpm| {
-                            let out = output.with_extension("metadata.o");
-                            WriteOutputFile(sess, tm, cpm,
-                                            trans.metadata_module, &out,
-                                            lib::llvm::ObjectFile);
-                        })
-                    }
-                }
-            });
-
-            llvm::LLVMRustDisposeTargetMachine(tm);
-            llvm::LLVMDisposeModule(trans.metadata_module);
-            llvm::LLVMDisposeModule(llmod);
-            llvm::LLVMContextDispose(llcx);
-            if sess.time_llvm_passes() { llvm::LLVMRustPrintPassTimings(); }
-        }
-    }
+    use std::path::Path;
 
     pub fn run_assembler(sess: Session, assembly: &Path, object: &Path) {
-        let cc = super::get_cc_prog(sess);
+        let mut cmd = super::cc_command(sess);
+        let cc = cmd.prog.clone();
+        cmd.args([~"-c", ~"-o"]);
+        cmd.arg_path("", object);
+        cmd.arg_path("", assembly);
 
-        // FIXME (#9639): This needs to handle non-utf8 paths
-        let args = [
-            ~"-c",
-            ~"-o", object.as_str().unwrap().to_owned(),
-            assembly.as_str().unwrap().to_owned()];
-
-        debug!("{} '{}'", cc, args.connect("' '"));
-        let prog = run::process_output(cc, args);
+        debug!("{}", cmd.command_line());
+        let prog = cmd.output();
 
         if !prog.status.success() {
             sess.err(format!("linking with `{}` failed: {}", cc, prog.status));
-            sess.note(format!("{} arguments: '{}'", cc, args.connect("' '")));
-            sess.note(str::from_utf8_owned(prog.error + prog.output));
+            sess.note(format!("command: {}", cmd.command_line()));
+            sess.note(super::demangle_symbols(prog.error + prog.output));
             sess.abort_if_errors();
         }
     }
 
-    unsafe fn configure_llvm(sess: Session) {
-        // Copy what clan does by turning on loop vectorization at O2 and
-        // slp vectorization at O3
-        let vectorize_loop = !sess.no_vectorize_loops() &&
-                             (sess.opts.optimize == session::Default ||
-                              sess.opts.optimize == session::Aggressive);
-        let vectorize_slp = !sess.no_vectorize_slp() &&
-                            sess.opts.optimize == session::Aggressive;
-
-        let mut llvm_c_strs = ~[];
-        let mut llvm_args = ~[];
-        let add = |arg: &str| {
-            let s = arg.to_c_str();
-            llvm_args.push(s.with_ref(|p| p));
-            llvm_c_strs.push(s);
-        };
-        add("rustc"); // fake program name
-        add("-arm-enable-ehabi");
-        add("-arm-enable-ehabi-descriptors");
-        if vectorize_loop { add("-vectorize-loops"); }
-        if vectorize_slp  { add("-vectorize-slp");   }
-        if sess.time_llvm_passes() { add("-time-passes"); }
-        if sess.print_llvm_passes() { add("-debug-pass=Structure"); }
-
-        for arg in sess.opts.llvm_args.iter() {
-            add(*arg);
-        }
-
-        llvm_args.as_imm_buf(|p, len| {
-            llvm::LLVMRustSetLLVMOptions(len as c_int, p);
-        })
-    }
-
-    unsafe fn populate_llvm_passes(fpm: lib::llvm::PassManagerRef,
-                                   mpm: lib::llvm::PassManagerRef,
-                                   llmod: ModuleRef,
-                                   opt: lib::llvm::CodeGenOptLevel) {
-        // Create the PassManagerBuilder for LLVM. We configure it with
-        // reasonable defaults and prepare it to actually populate the pass
-        // manager.
-        let builder = llvm::LLVMPassManagerBuilderCreate();
-        match opt {
-            lib::llvm::CodeGenLevelNone => {
-                // Don't add lifetime intrinsics add O0
-                llvm::LLVMRustAddAlwaysInlinePass(builder, false);
-            }
-            lib::llvm::CodeGenLevelLess => {
-                llvm::LLVMRustAddAlwaysInlinePass(builder, true);
+    // LLVM writes the DWARF meant for the .dwo file into the object itself,
+    // from where it's moved into the .dwo file named in the compile unit.
+    pub fn split_dwarf(sess: Session, object: &Path) {
+        let dwo = object.with_extension("dwo");
+        let commands = [("--extract-dwo", Some(&dwo)), ("--strip-dwo", None)];
+        for &(flag, dwo) in commands.iter() {
+            let mut cmd = super::Command::new("objcopy");
+            cmd.allow_response_file();
+            cmd.arg(flag);
+            cmd.arg_path("", object);
+            for dwo in dwo.iter() {
+                cmd.arg_path("", *dwo);
             }
-            // numeric values copied from clang
-            lib::llvm::CodeGenLevelDefault => {
-                llvm::LLVMPassManagerBuilderUseInlinerWithThreshold(builder,
-                                                                    225);
-            }
-            lib::llvm::CodeGenLevelAggressive => {
-                llvm::LLVMPassManagerBuilderUseInlinerWithThreshold(builder,
-                                                                    275);
+            debug!("{}", cmd.command_line());
+            let prog = cmd.output();
+            if !prog.status.success() {
+                sess.err(format!("splitting the debug info off `{}` \
+                                  failed: {}", object.display(), prog.status));
+                sess.note(format!("command: {}", cmd.command_line()));
+                sess.note(prog.error + prog.output);
+                sess.abort_if_errors();
             }
         }
-        llvm::LLVMPassManagerBuilderSetOptLevel(builder, opt as c_uint);
-        llvm::LLVMRustAddBuilderLibraryInfo(builder, llmod);
-
-        // Use the builder to populate the function/module pass managers.
-        llvm::LLVMPassManagerBuilderPopulateFunctionPassManager(builder, fpm);
-        llvm::LLVMPassManagerBuilderPopulateModulePassManager(builder, mpm);
-        llvm::LLVMPassManagerBuilderDispose(builder);
     }
 }
 
@@ -428,13 +177,24 @@ pub mod write {
  *    in this case. CNAME and CVERS are taken from this package id. For
  *    example, github.com/mozilla/CNAME#CVERS.
  *
- *  - Define CMH as SHA256(pkgid).
+ *  - Define CMH as SHA256(pkgid, rustc version, target triple). The crates
+ *    of another compiler or target can't be linked with ours, so they get
+ *    other file names and symbols instead of being picked up by mistake.
  *
  *  - Define CMH8 as the first 8 characters of CMH.
  *
+ *  - Define IH, the interface hash, as SHA256(CMH, interface, upstream IHs),
+ *    where the interface is made of the signatures of the crate's exported
+ *    and reachable items, along with the whole source of the items inlined
+ *    into other crates. A downstream crate built against an upstream one with
+ *    the same IH doesn't need to be rebuilt.
+ *
  *  - Compile our crate to lib CNAME-CMH8-CVERS.so
  *
- *  - Define STH(sym) as SHA256(CMH, type_str(sym))
+ *  - Define STH(sym) as SHA256(CMH, type_str(sym), type_crates(sym), path(sym)),
+ *    where type_crates(sym) covers the CMH of every crate defining a component
+ *    of the symbol's type, so that identically named types of different
+ *    crates can't collide.
  *
  *  - Suffix a mangled sym with ::STH@CVERS, so that it is unique in the
  *    name, non-name metadata, and type sense, and versioned in the way
@@ -444,12 +204,18 @@ pub mod write {
 pub fn build_link_meta(sess: Session,
                        c: &ast::Crate,
                        output: &Path,
+                       analysis: &CrateAnalysis,
                        symbol_hasher: &mut Sha256)
                        -> LinkMeta {
     // This calculates CMH as defined above
-    fn crate_hash(symbol_hasher: &mut Sha256, pkgid: &PkgId) -> @str {
+    fn crate_hash(sess: Session, symbol_hasher: &mut Sha256,
+                  pkgid: &PkgId) -> @str {
         symbol_hasher.reset();
         symbol_hasher.input_str(pkgid.to_str());
+        symbol_hasher.input_str("-");
+        symbol_hasher.input_str(encoder::rustc_version());
+        symbol_hasher.input_str("-");
+        symbol_hasher.input_str(sess.opts.target_triple);
         truncated_hash_result(symbol_hasher).to_managed()
     }
 
@@ -465,12 +231,97 @@ pub fn build_link_meta(sess: Session,
         Some(s) => s,
     };
 
-    let hash = crate_hash(symbol_hasher, &pkgid);
+    let hash = crate_hash(sess, symbol_hasher, &pkgid);
+    let interface_hash = interface_hash(analysis, hash, symbol_hasher);
 
     LinkMeta {
         pkgid: pkgid,
         crate_hash: hash,
+        interface_hash: interface_hash,
+    }
+}
+
+// This calculates IH as defined above
+fn interface_hash(analysis: &CrateAnalysis, crate_hash: &str,
+                  symbol_hasher: &mut Sha256) -> @str {
+    let tcx = analysis.ty_cx;
+    let intr = tcx.sess.intr();
+    let is_inlined = |attrs: &[ast::Attribute], generics: &ast::Generics| {
+        generics.is_type_parameterized() ||
+            attr::find_inline_attr(attrs) != attr::InlineNone
+    };
+    let signature = |path: ~str, id: ast::NodeId| {
+        format!("{}: {}", path, ppaux::ty_to_str(tcx, ty::node_id_to_type(tcx, id)))
+    };
+
+    let mut decls = ~[];
+    let ids = analysis.exported_items.iter().chain(analysis.reachable.iter());
+    for &id in ids {
+        let decl = match tcx.items.find(&id) {
+            Some(&ast_map::node_item(item, path)) => {
+                let path = ast_map::path_ident_to_str(path, item.ident, intr);
+                match item.node {
+                    ast::item_fn(_, _, _, ref generics, _)
+                            if !is_inlined(item.attrs, generics) => {
+                        signature(path, id)
+                    }
+                    // The methods of an impl are found on their own, as are the
+                    // items of a module
+                    ast::item_impl(..) | ast::item_mod(..) |
+                    ast::item_foreign_mod(..) => continue,
+                    _ => pprust::item_to_str(item, intr),
+                }
+            }
+            Some(&ast_map::node_foreign_item(item, _, _, path)) => {
+                signature(ast_map::path_ident_to_str(path, item.ident, intr), id)
+            }
+            Some(&ast_map::node_method(method, impl_did, path)) => {
+                let impl_generics = match tcx.items.find(&impl_did.node) {
+                    Some(&ast_map::node_item(@ast::item {
+                        node: ast::item_impl(ref generics, _, _, _), ..
+                    }, _)) => generics.is_type_parameterized(),
+                    _ => false,
+                };
+                let path = ast_map::path_ident_to_str(path, method.ident, intr);
+                if impl_generics || is_inlined(method.attrs, &method.generics) {
+                    format!("{}: {} {}", path,
+                            pprust::fun_to_str(method.decl, method.purity,
+                                               method.ident,
+                                               Some(method.explicit_self.node),
+                                               &method.generics, intr),
+                            pprust::block_to_str(method.body, intr))
+                } else {
+                    signature(path, id)
+                }
+            }
+            _ => continue,
+        };
+        decls.push(decl);
+    }
+    sort::quick_sort(decls, |a, b| *a <= *b);
+    decls.dedup();
+
+    // Our interface is also made of the upstream ones (the types we expose
+    // may be theirs), which are taken in the order of their crate numbers
+    let mut upstream = ~[];
+    cstore::iter_crate_data(tcx.sess.cstore, |cnum, data| {
+        let hash = decoder::get_crate_interface_hash(data.data)
+            .unwrap_or(decoder::get_crate_hash(data.data));
+        upstream.push((cnum, hash));
+    });
+    sort::quick_sort(upstream, |&(a, _), &(b, _)| a <= b);
+
+    symbol_hasher.reset();
+    symbol_hasher.input_str(crate_hash);
+    for decl in decls.iter() {
+        symbol_hasher.input_str("-");
+        symbol_hasher.input_str(*decl);
+    }
+    for &(_, hash) in upstream.iter() {
+        symbol_hasher.input_str("-");
+        symbol_hasher.input_str(hash);
     }
+    truncated_hash_result(symbol_hasher).to_managed()
 }
 
 pub fn truncated_hash_result(symbol_hasher: &mut Sha256) -> ~str {
@@ -486,12 +337,20 @@ pub fn symbol_hash(tcx: ty::ctxt,
     // NB: do *not* use abbrevs here as we want the symbol names
     // to be independent of one another in the crate.
 
+    // Regions do not make it into the generated code, so they must not
+    // affect the hash either.
+    let t = ty::erase_regions(tcx, t);
     symbol_hasher.reset();
     symbol_hasher.input_str(link_meta.pkgid.name);
     symbol_hasher.input_str("-");
     symbol_hasher.input_str(link_meta.crate_hash);
     symbol_hasher.input_str("-");
     symbol_hasher.input_str(encoder::encoded_ty(tcx, t));
+    symbol_hasher.input_str("-");
+    // The encoded type only names crates by their number in this session, so
+    // also take in the hashes of the crates defining each component of it.
+    let type_hash = ty::hash_crate_independent(tcx, t, link_meta.crate_hash);
+    symbol_hasher.input_str(format!("{:x}", type_hash));
     let mut hash = truncated_hash_result(symbol_hasher);
     // Prefix with 'h' so that it never blends into adjacent digits
     hash.unshift_char('h');
@@ -510,6 +369,20 @@ pub fn get_symbol_hash(ccx: &mut CrateContext, t: ty::t) -> @str {
     }
 }
 
+// Extends the STH of an item's type with the item's path, so that distinct
+// items of the same type are still given distinct hashes.
+fn item_symbol_hash(ccx: &mut CrateContext, path: &path, t: ty::t) -> ~str {
+    let type_hash = get_symbol_hash(ccx, t);
+    let path_str = ast_map::path_to_str(*path, ccx.sess.intr());
+    ccx.symbol_hasher.reset();
+    ccx.symbol_hasher.input_str(type_hash);
+    ccx.symbol_hasher.input_str("-");
+    ccx.symbol_hasher.input_str(path_str);
+    let mut hash = truncated_hash_result(&mut ccx.symbol_hasher);
+    hash.unshift_char('h');
+    hash
+}
+
 
 // Name sanitation. LLVM will happily accept identifiers with weird names, but
 // gas doesn't!
@@ -558,6 +431,201 @@ pub fn sanitize(s: &str) -> ~str {
     return result;
 }
 
+// The escape sequences introduced by `sanitize`. Note that both '*' and ')'
+// are escaped as `$RP$`, so the latter can't be recovered.
+static SANITIZE_ESCAPES: &'static [(&'static str, char)] = &'static [
+    ("$SP$", '@'),
+    ("$UP$", '~'),
+    ("$RP$", '*'),
+    ("$BP$", '&'),
+    ("$LT$", '<'),
+    ("$GT$", '>'),
+    ("$LP$", '('),
+    ("$C$",  ','),
+];
+
+// Reverses `sanitize` as far as is possible
+fn unsanitize(s: &str) -> ~str {
+    // Drop the underscore which qualifies names that weren't identifiers
+    let s = if s.starts_with("_$") { s.slice_from(1) } else { s };
+
+    let mut result = ~"";
+    let mut i = 0;
+    'outer: while i < s.len() {
+        let rest = s.slice_from(i);
+        for &(esc, c) in SANITIZE_ESCAPES.iter() {
+            if rest.starts_with(esc) {
+                result.push_char(c);
+                i += esc.len();
+                continue 'outer;
+            }
+        }
+
+        // Unicode escapes are `$x41`, `$u1234` or `$U00012345`
+        let width = if rest.starts_with("$x") {
+            2
+        } else if rest.starts_with("$u") {
+            4
+        } else if rest.starts_with("$U") {
+            8
+        } else {
+            0
+        };
+        if width > 0 && rest.len() >= width + 2 {
+            let n = num::from_str_radix::<u32>(rest.slice(2, width + 2), 16);
+            match n.and_then(|n| char::from_u32(n)) {
+                Some(c) => {
+                    result.push_char(c);
+                    i += width + 2;
+                    continue;
+                }
+                None => {}
+            }
+        }
+
+        let range = s.char_range_at(i);
+        result.push_char(range.ch);
+        i = range.next;
+    }
+    result
+}
+
+// Demangles the symbol at the start of `s`, returning the demangled name and
+// the number of bytes of `s` which the symbol occupied.
+fn demangle_prefix(s: &str) -> Option<(~str, uint)> {
+    fn is_hash(s: &str) -> bool {
+        s.len() == 17 && s.starts_with("h") &&
+            s.slice_from(1).chars().all(|c| char::is_digit_radix(c, 16))
+    }
+
+    if !s.starts_with("_ZN") { return None }
+
+    // Split the symbol into its <len, name> components
+    let mut elements = ~[];
+    let mut i = 3;
+    loop {
+        if i >= s.len() { return None }
+        if s[i] == 'E' as u8 { break }
+        let start = i;
+        while i < s.len() && char::is_digit(s[i] as char) { i += 1; }
+        let len: uint = match from_str(s.slice(start, i)) {
+            Some(len) => len,
+            None => return None,
+        };
+        if len == 0 || i + len > s.len() || !s.is_char_boundary(i + len) {
+            return None
+        }
+        elements.push(s.slice(i, i + len));
+        i += len;
+    }
+
+    // Drop the trailing hash component, see `mangle`
+    if elements.len() > 1 && is_hash(*elements.last()) {
+        elements.pop();
+    }
+
+    let names = elements.map(|e| unsanitize(*e));
+    Some((names.connect("::"), i + 1))
+}
+
+/// Demangles a symbol produced by `mangle`, returning `None` if the symbol
+/// isn't one of ours. The escapes introduced by `sanitize` are reversed and
+/// the hash component is dropped, so that a symbol such as
+/// `_ZN3foo3bar17h<16 hex digits>E` demangles to `foo::bar`.
+pub fn demangle(sym: &str) -> Option<~str> {
+    match demangle_prefix(sym) {
+        Some((name, len)) if len == sym.len() => Some(name),
+        _ => None,
+    }
+}
+
+/// Keeps only the functions of LLVM IR (`ir`) or of assembly whose demangled
+/// names contain `pattern`. In LLVM IR a function runs from its `define` to
+/// the closing brace, while in assembly it runs from its label to the next
+/// label which isn't local.
+pub fn filter_functions(text: &str, pattern: &str, ir: bool) -> ~str {
+    fn matches(sym: &str, pattern: &str) -> bool {
+        // OSX prefixes every symbol with an underscore
+        let name = match demangle(sym) {
+            Some(name) => name,
+            None if sym.starts_with("_") => {
+                demangle(sym.slice_from(1)).unwrap_or(sym.to_owned())
+            }
+            None => sym.to_owned(),
+        };
+        name.contains(pattern)
+    }
+
+    fn ir_symbol<'a>(line: &'a str) -> Option<&'a str> {
+        if !line.starts_with("define ") { return None }
+        let sym = match line.find('@') {
+            Some(i) => line.slice_from(i + 1),
+            None => return None,
+        };
+        if sym.starts_with("\"") {
+            let sym = sym.slice_from(1);
+            sym.find('"').map(|end| sym.slice_to(end))
+        } else {
+            sym.find('(').map(|end| sym.slice_to(end))
+        }
+    }
+
+    fn asm_symbol<'a>(line: &'a str) -> Option<&'a str> {
+        if !line.ends_with(":") || line.starts_with(" ") ||
+           line.starts_with("\t") || line.starts_with(".L") ||
+           line.starts_with("L") {
+            return None
+        }
+        Some(line.slice_to(line.len() - 1))
+    }
+
+    let mut result = ~"";
+    let mut keep = false;
+    for line in text.lines() {
+        let start = if ir { ir_symbol(line) } else { asm_symbol(line) };
+        match start {
+            Some(sym) => keep = matches(sym, pattern),
+            None => {}
+        }
+        if keep {
+            result.push_str(line);
+            result.push_char('\n');
+            if ir && line == "}" {
+                result.push_char('\n');
+                keep = false;
+            }
+        }
+    }
+    result
+}
+
+/// Demangles all of our symbols found in `text`, leaving everything else
+/// untouched. This is used to make the output of the linker and LLVM legible.
+pub fn demangle_symbols(text: &str) -> ~str {
+    let mut result = ~"";
+    let mut rest = text;
+    loop {
+        let start = match rest.find_str("_ZN") {
+            Some(start) => start,
+            None => break,
+        };
+        result.push_str(rest.slice_to(start));
+        let candidate = rest.slice_from(start);
+        match demangle_prefix(candidate) {
+            Some((name, len)) => {
+                result.push_str(name);
+                rest = candidate.slice_from(len);
+            }
+            None => {
+                result.push_str("_ZN");
+                rest = candidate.slice_from(3);
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
 pub fn mangle(sess: Session, ss: path,
               hash: Option<&str>, vers: Option<&str>) -> ~str {
     // Follow C++ namespace-mangling style, see
@@ -609,14 +677,19 @@ pub fn mangle(sess: Session, ss: path,
             _ => {}
         }
     }
-    if hash.len() > 0 {
-        push(hash);
-    }
     match vers {
-        Some(s) => push(s),
+        Some(s) => { hash.push_char('-'); hash.push_str(s); }
         None => {}
     }
 
+    // The hash and the version are folded into a single trailing component of
+    // exactly 'h' followed by 16 hex digits. Demanglers which know about our
+    // symbols (c++filt and gdb from binutils 2.32 on, as well as `--demangle`)
+    // recognize this component and drop it, so `foo::bar` is all that's shown.
+    if hash.len() > 0 {
+        push(hash_component(hash));
+    }
+
     n.push_char('E'); // End name-sequence.
     n
 }
@@ -625,21 +698,22 @@ pub fn exported_name(sess: Session,
                      path: path,
                      hash: &str,
                      vers: &str) -> ~str {
-    // The version will get mangled to have a leading '_', but it makes more
-    // sense to lead with a 'v' b/c this is a version...
-    let vers = if vers.len() > 0 && !char::is_XID_start(vers.char_at(0)) {
-        "v" + vers
-    } else {
-        vers.to_owned()
-    };
+    mangle(sess, path, Some(hash), Some(vers))
+}
 
-    mangle(sess, path, Some(hash), Some(vers.as_slice()))
+// Condenses `s` into the trailing hash component of a symbol, see `mangle`
+fn hash_component(s: &str) -> ~str {
+    let mut hasher = Sha256::new();
+    hasher.input_str(s);
+    let mut hash = hasher.result_str().slice_to(16).to_owned();
+    hash.unshift_char('h');
+    hash
 }
 
 pub fn mangle_exported_name(ccx: &mut CrateContext,
                             path: path,
                             t: ty::t) -> ~str {
-    let hash = get_symbol_hash(ccx, t);
+    let hash = item_symbol_hash(ccx, &path, t);
     return exported_name(ccx.sess, path,
                          hash,
                          ccx.link_meta.pkgid.version_or_default());
@@ -681,7 +755,60 @@ pub fn mangle_internal_name_by_path(ccx: &mut CrateContext, path: path) -> ~str
     mangle(ccx.sess, path, None, None)
 }
 
-pub fn output_lib_filename(lm: &LinkMeta) -> ~str {
+/// Returns the symbols of all reachable items which are exported under a name
+/// chosen by the user (`#[no_mangle]` or `#[export_name]`) rather than a
+/// mangled one, along with the span of the item defining them. Unlike mangled
+/// symbols, these are not unique to the crate and may collide with symbols of
+/// other crates.
+pub fn unmangled_symbols(tcx: ty::ctxt,
+                         reachable: @mut HashSet<ast::NodeId>,
+                         item_symbols: &HashMap<ast::NodeId, ~str>)
+                         -> ~[(~str, Span)] {
+    fn is_unmangled(attrs: &[ast::Attribute]) -> bool {
+        attr::contains_name(attrs, "no_mangle") ||
+            attr::contains_name(attrs, "export_name")
+    }
+
+    let mut symbols = ~[];
+    for id in reachable.iter() {
+        let sym = match item_symbols.find(id) {
+            Some(sym) => sym,
+            None => continue,
+        };
+        match tcx.items.find(id) {
+            Some(&ast_map::node_item(i, _)) if is_unmangled(i.attrs) => {
+                symbols.push((sym.clone(), i.span));
+            }
+            Some(&ast_map::node_method(m, _, _)) if is_unmangled(m.attrs) => {
+                symbols.push((sym.clone(), m.span));
+            }
+            _ => {}
+        }
+    }
+
+    // Sort so the metadata doesn't depend on hash table ordering
+    sort::quick_sort(symbols, |&(ref a, _), &(ref b, _)| *a <= *b);
+    symbols
+}
+
+/// Prints the symbols which a library built from this crate exports, one per
+/// line, along with their demangled names. This is what `--print
+/// exported-symbols` shows.
+pub fn print_exported_symbols(trans: &CrateTranslation) {
+    for sym in trans.exported_symbols.iter() {
+        match demangle(*sym) {
+            Some(name) => println!("{} ({})", *sym, name),
+            None => println(*sym),
+        }
+    }
+}
+
+pub fn output_lib_filename(sess: Session, lm: &LinkMeta) -> ~str {
+    // The hash is still recorded in the metadata, which is what dependencies
+    // are resolved with, so it isn't needed to tell libraries apart.
+    if sess.opts.cg.no_hash_in_filename {
+        return format!("{}-{}", lm.pkgid.name, lm.pkgid.version_or_default());
+    }
     format!("{}-{}-{}",
             lm.pkgid.name,
             lm.crate_hash.slice_chars(0, 8),
@@ -690,10 +817,122 @@ pub fn output_lib_filename(lm: &LinkMeta) -> ~str {
 
 pub fn get_cc_prog(sess: Session) -> ~str {
     match sess.opts.linker {
-        Some(ref linker) => return linker.to_owned(),
+        Some(ref linker) => {
+            let prog = linker[0].as_slice();
+            if find_program(prog).is_none() {
+                sess.err(format!("the linker `{}` was not found", prog));
+                if linker.len() > 1 {
+                    sess.note("--linker is split into words as by a shell, so \
+                               a path with spaces in it has to be quoted");
+                }
+                sess.abort_if_errors();
+            }
+            return prog.to_owned()
+        }
+        None => {}
+    }
+
+    let cc = default_cc_prog(sess);
+    if find_program(cc.as_slice()).is_some() {
+        return cc
+    }
+
+    sess.err(format!("the C compiler `{}` was not found, which rustc links \
+                      with", cc));
+    if cfg!(windows) {
+        sess.note("install mingw-w64 and put its bin directory on the PATH, \
+                   or name a linker with --linker");
+    } else {
+        sess.note("install gcc or clang, or name a linker with --linker");
+    }
+    sess.abort_if_errors();
+    unreachable!()
+}
+
+/// The command which runs the C compiler: its program, along with the
+/// arguments given with it to --linker
+pub fn cc_command(sess: Session) -> Command {
+    let mut cmd = Command::new(get_cc_prog(sess));
+    cmd.allow_response_file();
+    match sess.opts.linker {
+        Some(ref linker) => cmd.args(linker.slice_from(1)),
         None => {}
     }
+    cmd
+}
+
+/// Splits a command line into its words the way a POSIX shell does, with
+/// quotes and backslashes, except that on Windows backslashes are taken as they
+/// are, since paths are full of them
+pub fn split_command_line(line: &str) -> Result<~[~str], ~str> {
+    split_command_line_for(line, cfg!(windows))
+}
+
+fn split_command_line_for(line: &str, windows: bool) -> Result<~[~str], ~str> {
+    let mut words = ~[];
+    let mut word = ~"";
+    // Whether a word was started, which may be an empty one like `''`
+    let mut in_word = false;
+    let mut chars = line.chars();
+    loop {
+        let c = match chars.next() {
+            Some(c) => c,
+            None => break,
+        };
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_word {
+                    words.push(word);
+                    word = ~"";
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push_char(c),
+                        None => return Err(~"unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if !windows => match chars.next() {
+                            Some(c) if c == '"' || c == '\\' => word.push_char(c),
+                            Some(c) => { word.push_char('\\'); word.push_char(c); }
+                            None => return Err(~"unterminated double quote"),
+                        },
+                        Some(c) => word.push_char(c),
+                        None => return Err(~"unterminated double quote"),
+                    }
+                }
+            }
+            '\\' if !windows => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push_char(c),
+                    None => return Err(~"trailing backslash"),
+                }
+            }
+            c => {
+                in_word = true;
+                word.push_char(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
 
+// The C compiler which links for the target unless --linker says otherwise
+fn default_cc_prog(sess: Session) -> ~str {
     // In the future, FreeBSD will use clang as default compiler.
     // It would be flexible to use cc (system's default C compiler)
     // instead of hard-coded gcc.
@@ -714,60 +953,585 @@ pub fn get_cc_prog(sess: Session) -> ~str {
     }
 }
 
-/// Perform the linkage portion of the compilation phase. This will generate all
-/// of the requested outputs for this compilation session.
-pub fn link_binary(sess: Session,
-                   trans: &CrateTranslation,
-                   obj_filename: &Path,
-                   out_filename: &Path,
-                   lm: &LinkMeta) {
-    // If we're generating a test executable, then ignore all other output
-    // styles at all other locations
-    let outputs = if sess.opts.test {
-        ~[session::OutputExecutable]
-    } else {
-        (*sess.outputs).clone()
-    };
+// The mingw which the Windows installer of rustc ships along with the libraries
+// of the target, in `lib/rustlib/<triple>/mingw`. Its `lib` directory holds
+// the startup objects and runtime libraries that programs are linked with.
+fn bundled_mingw_dir(sess: Session) -> Path {
+    sess.filesearch.get_target_lib_path().dir_path().join("mingw")
+}
 
-    for output in outputs.move_iter() {
-        link_binary_output(sess, trans, output, obj_filename, out_filename, lm);
+// Where `prog` is run from: itself if it's a path, otherwise the first
+// directory of PATH holding it. Running a program which can't be found fails
+// with an error that doesn't say which program it was, so this is looked up
+// beforehand.
+fn find_program(prog: &str) -> Option<Path> {
+    let exists = |path: Path| {
+        if path.exists() {
+            Some(path)
+        } else if cfg!(windows) && path.extension().is_none() {
+            let exe = path.with_extension("exe");
+            if exe.exists() { Some(exe) } else { None }
+        } else {
+            None
+        }
+    };
+    if prog.contains_char('/') || (cfg!(windows) && prog.contains_char('\\')) {
+        return exists(Path::new(prog))
     }
+    let sep = if cfg!(windows) { ';' } else { ':' };
+    match os::getenv("PATH") {
+        Some(paths) => {
+            for dir in paths.split(sep).filter(|p| !p.is_empty()) {
+                match exists(Path::new(dir).join(prog)) {
+                    Some(path) => return Some(path),
+                    None => {}
+                }
+            }
+            None
+        }
+        None => None,
+    }
+}
+
+/// What's been found out about a C compiler driver by running it. Each flag
+/// is only tried once per driver in a session.
+pub struct CcProbe {
+    // Whether a program can be linked with each of the flags tried so far
+    flags: HashMap<~str, bool>,
+}
 
-    // Remove the temporary object file and metadata if we aren't saving temps
-    if !sess.opts.save_temps {
-        fs::unlink(obj_filename);
-        fs::unlink(&obj_filename.with_extension("metadata.o"));
+// The probe of the C compiler driver run by `cc`
+fn cc_probe(sess: Session, cc: &Command) -> @mut CcProbe {
+    let key = cc.command_line();
+    match sess.cc_probes.find(&key) {
+        Some(&probe) => return probe,
+        None => {}
     }
+    let probe = @mut CcProbe { flags: HashMap::new() };
+    sess.cc_probes.insert(key, probe);
+    probe
 }
 
-fn is_writeable(p: &Path) -> bool {
-    use std::io;
+// The first line of what `cc --version` prints, if it succeeds. It's only
+// needed to tell which driver failed to link, so it isn't looked up before.
+fn cc_version(cc: &Command) -> Option<~str> {
+    let mut cmd = cc.clone();
+    cmd.arg("--version");
+    let prog = cmd.output();
+    if prog.status.success() {
+        prog.output.lines().next().map(|line| line.trim().to_owned())
+    } else {
+        None
+    }
+}
 
-    match io::result(|| p.stat()) {
-        Err(..) => true,
-        Ok(m) => m.perm & io::UserWrite == io::UserWrite
+// The ones of `flags` which `cc` accepts when linking a program for the
+// target. Drivers and linkers which aren't GNU ones, or are old, don't know
+// all of the flags we'd like to give them.
+//
+// An empty program is linked with all of the flags first, which is all that's
+// needed with a toolchain that takes them. Otherwise each one is tried on its
+// own, unless the program can't be linked without any of them either, in
+// which case the probe can't tell and they're all kept.
+//
+// The probes bypass `-C link-wrapper`, as the program they link is only on
+// this machine. They're run with `--print link-args` as well, so that the
+// command printed is the one rustc would run.
+fn cc_accepted_flags(sess: Session, cc: &Command, flags: &[~str]) -> ~[~str] {
+    let probe = cc_probe(sess, cc);
+    let untried = flags.iter().filter(|f| !probe.flags.contains_key(*f))
+                       .map(|f| f.clone()).to_owned_vec();
+    if !untried.is_empty() {
+        // The probes are run while the link waits for them, but under
+        // `make -jN` they're still processes of their own, so they're run
+        // with a token from the jobserver.
+        let _token = match sess.jobserver {
+            Some(ref client) => client.acquire(),
+            None => None,
+        };
+        let tmpdir = TempDir::new("rustc").expect("needs a temp dir");
+        let src = tmpdir.path().join("probe.c");
+        fs::File::create(&src).write(bytes!("int main(void) { return 0; }\n"));
+        let links = |flags: &[~str]| {
+            let mut cmd = cc.clone();
+            cmd.args(sess.targ_cfg.target_strs.cc_args);
+            cmd.args(flags);
+            cmd.arg_path("", &src);
+            cmd.arg("-o");
+            cmd.arg_path("", &tmpdir.path().join("probe"));
+            cmd.output().status.success()
+        };
+        if links(untried.as_slice()) || !links(&[]) {
+            for flag in untried.move_iter() {
+                probe.flags.insert(flag, true);
+            }
+        } else {
+            for flag in untried.move_iter() {
+                let accepted = links([flag.clone()]);
+                if !accepted {
+                    debug!("{} doesn't accept {}", cc.command_line(), flag);
+                }
+                probe.flags.insert(flag, accepted);
+            }
+        }
     }
+    flags.iter().filter(|f| *probe.flags.get(*f)).map(|f| f.clone()).collect()
 }
 
-fn link_binary_output(sess: Session,
-                      trans: &CrateTranslation,
-                      output: session::OutputStyle,
-                      obj_filename: &Path,
-                      out_filename: &Path,
-                      lm: &LinkMeta) {
-    let libname = output_lib_filename(lm);
-    let out_filename = match output {
-        session::OutputRlib => {
-            out_filename.with_filename(format!("lib{}.rlib", libname))
+/// A tool to run, like the linker or windres, along with its arguments
+#[deriving(Clone)]
+pub struct Command {
+    prog: ~str,
+    // Paths are passed as the bytes they consist of, which needn't be UTF-8
+    args: ~[~[u8]],
+    // Whether the program reads its arguments from a response file (`@file`)
+    response_file: bool,
+    // How many of the arguments are kept on the command line when the others
+    // are passed in a response file: those naming the programs run by
+    // wrappers, which only pass the response file on to them
+    wrapped: uint,
+}
+
+/// What a `Command` printed, decoded to text
+pub struct CommandOutput {
+    status: ProcessExit,
+    output: ~str,
+    error: ~str,
+}
+
+impl Command {
+    pub fn new(prog: &str) -> Command {
+        Command {
+            prog: prog.to_owned(),
+            args: ~[],
+            response_file: false,
+            wrapped: 0,
         }
-        session::OutputDylib => {
-            let (prefix, suffix) = match sess.targ_cfg.os {
-                abi::OsWin32 => (win32::DLL_PREFIX, win32::DLL_SUFFIX),
-                abi::OsMacos => (macos::DLL_PREFIX, macos::DLL_SUFFIX),
+    }
+
+    /// Lets the arguments be passed in a response file (`@file`) where they
+    /// can't be passed as they are, for programs which are known to read one,
+    /// like gcc, clang and the binutils
+    pub fn allow_response_file(&mut self) {
+        self.response_file = true;
+    }
+
+    pub fn arg(&mut self, arg: &str) {
+        self.args.push(arg.as_bytes().to_owned());
+    }
+
+    pub fn args(&mut self, args: &[~str]) {
+        for arg in args.iter() {
+            self.arg(arg.as_slice());
+        }
+    }
+
+    /// Adds `path` as an argument, following `prefix` (like `-L`)
+    pub fn arg_path(&mut self, prefix: &str, path: &Path) {
+        self.args.push(path_arg(prefix, path));
+    }
+
+    /// Adds arguments which needn't be UTF-8, like those made by `path_arg`
+    pub fn raw_args(&mut self, args: &[~[u8]]) {
+        self.args.push_all(args);
+    }
+
+    /// Runs the command to completion.
+    ///
+    /// The command line of Windows is limited to 32k characters, which large
+    /// crates exceed when linking, and arguments which aren't UTF-8 (paths,
+    /// that is) can't be passed as they are on any platform. Nor can the
+    /// arguments which the command lines made for Windows garble (see
+    /// `garbled_on_windows`). A program which reads a response file (see
+    /// `allow_response_file`) is given its arguments in one in those cases,
+    /// and only then. A wrapper of such a program (see `wrapped`) is given
+    /// the program followed by the response file.
+    pub fn output(&self) -> CommandOutput {
+        let utf8 = self.args.iter().all(|a| str::is_utf8(*a));
+        let garbled = cfg!(windows) &&
+                      self.args.iter().any(|a| garbled_on_windows(*a));
+        let len = self.args.iter().fold(self.prog.len(), |len, a| {
+            // Room for the quotes and the space around each argument
+            len + a.len() + 3
+        });
+        let prog = if self.response_file &&
+                      (!utf8 || garbled || len > MAX_COMMAND_LINE) {
+            let tmpdir = TempDir::new("rustc").expect("needs a temp dir");
+            let path = tmpdir.path().join("arguments");
+            self.write_response_file(&path);
+            let mut args = self.args.slice_to(self.wrapped)
+                                    .map(|a| lossy_utf8(*a));
+            // The temp dir is the one place whose path is known to be usable
+            args.push(~"@" + path.as_str().unwrap());
+            run::process_output(self.prog, args)
+        } else {
+            // FIXME (#9639): Programs which don't read response files can't
+            // be given paths which aren't UTF-8
+            let args = self.args.map(|a| lossy_utf8(*a));
+            run::process_output(self.prog, args)
+        };
+        CommandOutput {
+            status: prog.status,
+            output: decode_output(prog.output),
+            error: decode_output(prog.error),
+        }
+    }
+
+    /// The command which runs `wrapper` with this one: the program as its
+    /// first argument, followed by the arguments of the program. Wrappers
+    /// pass a response file on to the program as it is, so one is used where
+    /// the program would be given one itself.
+    pub fn wrapped(&self, wrapper: &str) -> Command {
+        let mut args = ~[self.prog.as_bytes().to_owned()];
+        args.push_all(self.args);
+        Command {
+            prog: wrapper.to_owned(),
+            args: args,
+            response_file: self.response_file,
+            wrapped: self.wrapped + 1,
+        }
+    }
+
+    /// The command line for a build system to run later (see `--print
+    /// link-args`). Arguments which aren't UTF-8 can't be written out as
+    /// text, so a program which reads a response file is given them in the
+    /// file `path`. `None` if the program can't be given them.
+    pub fn dry_run_line(&self, path: &Path) -> Option<~str> {
+        if self.args.iter().all(|a| str::is_utf8(*a)) {
+            return Some(self.command_line())
+        }
+        let response_file = match path.as_str() {
+            Some(file) if self.response_file => ~"@" + file,
+            _ => return None,
+        };
+        self.write_response_file(path);
+        let mut cmd = Command::new(self.prog);
+        cmd.args(self.args.slice_to(self.wrapped).map(|a| lossy_utf8(*a)));
+        cmd.arg(response_file);
+        Some(cmd.command_line())
+    }
+
+    // Writes the arguments after those of the wrappers into a response file
+    fn write_response_file(&self, path: &Path) {
+        let mut file = ~[];
+        for arg in self.args.slice_from(self.wrapped).iter() {
+            file.push_all(quote_response_file_arg(*arg));
+            file.push('\n' as u8);
+        }
+        fs::File::create(path).write(file);
+    }
+
+    /// The command line, quoted as the shell of the host would need it, to
+    /// show in messages
+    pub fn command_line(&self) -> ~str {
+        let quote: fn(&str) -> ~str = if cfg!(windows) {
+            quote_windows_arg
+        } else {
+            quote_posix_arg
+        };
+        let mut line = quote(self.prog);
+        for arg in self.args.iter() {
+            line.push_char(' ');
+            line.push_str(quote(lossy_utf8(*arg)));
+        }
+        line
+    }
+}
+
+// The longest command line which is passed as it is rather than in a response
+// file. Windows doesn't take more than 32k characters, and other systems take
+// as much at least.
+static MAX_COMMAND_LINE: uint = 32 * 1024 - 1;
+
+/// The argument of `path` following `prefix`, as the bytes of the path
+pub fn path_arg(prefix: &str, path: &Path) -> ~[u8] {
+    prefix.as_bytes() + path.as_vec()
+}
+
+// Quotes an argument the way the Microsoft C runtime splits up command lines:
+// backslashes are only special right before a double quote
+fn quote_windows_arg(arg: &str) -> ~str {
+    if !arg.is_empty() && !arg.chars().any(|c| c == ' ' || c == '\t' || c == '"') {
+        return arg.to_owned()
+    }
+    let mut quoted = ~"\"";
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue
+        }
+        let escapes = if c == '"' { 2 * backslashes + 1 } else { backslashes };
+        for _ in range(0, escapes) { quoted.push_char('\\') }
+        quoted.push_char(c);
+        backslashes = 0;
+    }
+    for _ in range(0, 2 * backslashes) { quoted.push_char('\\') }
+    quoted.push_char('"');
+    quoted
+}
+
+fn quote_posix_arg(arg: &str) -> ~str {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_alphanumeric() ||
+                                              "-_./=,:+@".contains_char(c)) {
+        return arg.to_owned()
+    }
+    format!("'{}'", arg.replace("'", "'\\''"))
+}
+
+// Whether std would garble the argument in the command line it makes for a
+// Windows program: it drops empty arguments, and a quoted argument which ends
+// in backslashes has its closing quote escaped by them. Such arguments are
+// only shown right by `quote_windows_arg`, which isn't used for spawning.
+fn garbled_on_windows(arg: &[u8]) -> bool {
+    if arg.is_empty() { return true }
+    let quoted = arg.iter().any(|&b| b == ' ' as u8 || b == '\t' as u8);
+    quoted && arg[arg.len() - 1] == '\\' as u8
+}
+
+// Response files are split up by libiberty, where a backslash escapes any
+// byte, inside quotes as well
+fn quote_response_file_arg(arg: &[u8]) -> ~[u8] {
+    let mut quoted = ~['"' as u8];
+    for &b in arg.iter() {
+        if b == '\\' as u8 || b == '"' as u8 {
+            quoted.push('\\' as u8);
+        }
+        quoted.push(b);
+    }
+    quoted.push('"' as u8);
+    quoted
+}
+
+// Windows tools print text in the code page of the console rather than in
+// UTF-8. Output which is valid UTF-8 is taken as such all the same, as that's
+// what tools built against newer runtimes may print.
+#[cfg(windows)]
+fn decode_output(bytes: &[u8]) -> ~str {
+    use std::libc::{c_int, c_uint, DWORD};
+    use std::vec;
+
+    extern "system" {
+        fn GetConsoleOutputCP() -> c_uint;
+        fn MultiByteToWideChar(code_page: c_uint, flags: DWORD,
+                               bytes: *u8, len: c_int,
+                               wide: *mut u16, wide_len: c_int) -> c_int;
+    }
+
+    match str::from_utf8_opt(bytes) {
+        Some(s) => return s.to_owned(),
+        None => {}
+    }
+    unsafe {
+        // Without a console this is 0, which is the ANSI code page
+        let code_page = GetConsoleOutputCP();
+        bytes.as_imm_buf(|buf, len| {
+            let len = len as c_int;
+            let wide_len = MultiByteToWideChar(code_page, 0, buf, len,
+                                               ptr::mut_null(), 0);
+            let mut wide = vec::from_elem(wide_len as uint, 0u16);
+            wide.as_mut_buf(|wbuf, _| {
+                MultiByteToWideChar(code_page, 0, buf, len, wbuf, wide_len)
+            });
+            str::from_utf16(wide)
+        })
+    }
+}
+
+#[cfg(not(windows))]
+fn decode_output(bytes: &[u8]) -> ~str {
+    lossy_utf8(bytes)
+}
+
+// Tools print UTF-8 outside of Windows, but any byte which isn't is kept as the
+// Latin-1 character it would be, rather than failing on it. Arguments which
+// aren't UTF-8 are shown that way as well.
+pub fn lossy_utf8(bytes: &[u8]) -> ~str {
+    match str::from_utf8_opt(bytes) {
+        Some(s) => s.to_owned(),
+        None => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Perform the linkage portion of the compilation phase. This will generate all
+/// of the requested outputs for this compilation session.
+pub fn link_binary(sess: Session,
+                   trans: &CrateTranslation,
+                   obj_filename: &Path,
+                   out_filename: &Path,
+                   lm: &LinkMeta) {
+    // If we're generating a test executable, then ignore all other output
+    // styles at all other locations
+    let outputs = if sess.opts.test {
+        ~[session::OutputExecutable]
+    } else {
+        (*sess.outputs).clone()
+    };
+
+    // Only rlibs are produced without consulting upstream crates, every other
+    // output is a candidate for symbol collisions.
+    if outputs.iter().any(|&o| o != session::OutputRlib) {
+        check_duplicate_symbols(sess, trans);
+        check_float_abi(sess);
+    }
+
+    let mut unlinked_libs = ~[];
+    for &output in outputs.iter() {
+        let libs = link_binary_output(sess, trans, output, obj_filename,
+                                      out_filename, lm);
+        if output == session::OutputStaticlib {
+            unlinked_libs = libs;
+        }
+    }
+
+    // A dylib and a staticlib of the same crate share their pkg-config file
+    if sess.opts.cg.pkg_config {
+        let dylib = outputs.contains(&session::OutputDylib);
+        let staticlib = outputs.contains(&session::OutputStaticlib);
+        if dylib || staticlib {
+            write_pkg_config(sess, lm, dylib, unlinked_libs, out_filename);
+        }
+    }
+
+    // Remove the temporary object file and metadata if we aren't saving temps.
+    // Unpacked debug info on OSX is only found in the object file.
+    let keep_obj = sess.targ_cfg.os == abi::OsMacos && sess.opts.debuginfo &&
+                   sess.split_debuginfo() == session::SplitDebuginfoUnpacked;
+    // `--print link-args` leaves them for the linker to be run on later
+    if !sess.opts.save_temps && !sess.opts.dry_run_link {
+        if !keep_obj {
+            fs::unlink(obj_filename);
+        }
+        // `-Z quick-codegen` only writes it for dylibs
+        let metadata = obj_filename.with_extension("metadata.o");
+        if metadata.exists() {
+            fs::unlink(&metadata);
+        }
+    }
+}
+
+// Crates built with `-C soft-float` pass floating point values in integer
+// registers, so they can't call into crates which expect them in floating point
+// registers (or vice versa). Each crate records which ABI it was built for.
+fn check_float_abi(sess: Session) {
+    fn abi_name(soft: bool) -> &'static str { if soft { "soft" } else { "hard" } }
+
+    let soft_float = sess.opts.cg.soft_float;
+    let mut mismatch = false;
+    cstore::iter_crate_data(sess.cstore, |cnum, data| {
+        let upstream = csearch::get_soft_float(sess.cstore, cnum);
+        if upstream != soft_float {
+            mismatch = true;
+            sess.err(format!("crate `{}` was built with the {} float ABI, \
+                              which is incompatible with the {} float ABI of \
+                              this crate", data.name, abi_name(upstream),
+                             abi_name(soft_float)));
+        }
+    });
+    if mismatch {
+        sess.note("the float ABI is chosen with `-C soft-float`");
+    }
+}
+
+// Rust symbols are mangled with the hash of the crate defining them, so they
+// never collide with one another. Symbols exported under an unmangled name
+// (and the contents of bundled native archives), however, all live in the
+// global namespace of the linker. When two crates define the same one, the
+// linker's complaint rarely says where the definitions came from, so look for
+// collisions ahead of time and report them in terms of crates.
+fn check_duplicate_symbols(sess: Session, trans: &CrateTranslation) {
+    let cstore = sess.cstore;
+    let local = trans.link.pkgid.name.to_managed();
+
+    let mut crates = ~[];
+    cstore::iter_crate_data(cstore, |cnum, data| crates.push((cnum, data.name)));
+    sort::quick_sort(crates, |&(a, _), &(b, _)| a <= b);
+
+    let mut symbols: HashMap<~str, @str> = HashMap::new();
+    let mut archives: HashMap<~str, @str> = HashMap::new();
+    for &(ref lib, kind) in cstore::get_used_libraries(cstore).iter() {
+        if kind == cstore::NativeStatic {
+            archives.insert(lib.clone(), local);
+        }
+    }
+
+    for &(cnum, name) in crates.iter() {
+        for sym in csearch::get_exported_symbols(cstore, cnum).move_iter() {
+            match symbols.find(&sym) {
+                Some(&other) => {
+                    sess.err(format!("symbol `{}` is defined in both crate \
+                                      `{}` and crate `{}`", sym, other, name));
+                    continue
+                }
+                None => {}
+            }
+            symbols.insert(sym, name);
+        }
+
+        let libs = csearch::get_native_libraries(cstore, cnum);
+        for &(kind, ref lib) in libs.iter() {
+            if kind != cstore::NativeStatic { continue }
+            match archives.find(lib) {
+                Some(&other) => {
+                    sess.warn(format!("native static library `{}` is bundled \
+                                       into both crate `{}` and crate `{}`, \
+                                       its symbols may be defined twice",
+                                      *lib, other, name));
+                    continue
+                }
+                None => {}
+            }
+            archives.insert(lib.clone(), name);
+        }
+    }
+
+    for &(ref sym, sp) in trans.unmangled_symbols.iter() {
+        match symbols.find(sym) {
+            Some(&other) => {
+                sess.span_err(sp, format!("symbol `{}` is already defined in \
+                                           crate `{}`", *sym, other));
+            }
+            None => {}
+        }
+    }
+
+    sess.abort_if_errors();
+}
+
+fn is_writeable(p: &Path) -> bool {
+    match io::result(|| p.stat()) {
+        Err(..) => true,
+        Ok(m) => m.perm & io::UserWrite == io::UserWrite
+    }
+}
+
+// Links a single output of the crate, returning the native libraries which
+// weren't linked into it and which its consumer has to link instead.
+fn link_binary_output(sess: Session,
+                      trans: &CrateTranslation,
+                      output: session::OutputStyle,
+                      obj_filename: &Path,
+                      out_filename: &Path,
+                      lm: &LinkMeta)
+                      -> ~[(cstore::NativeLibaryKind, ~str)] {
+    let libname = output_lib_filename(sess, lm);
+    let out_filename = match output {
+        session::OutputRlib => {
+            out_filename.with_filename(format!("lib{}.rlib", libname))
+        }
+        session::OutputDylib | session::OutputCdylib => {
+            let (prefix, suffix) = match sess.targ_cfg.os {
+                abi::OsWin32 => (win32::DLL_PREFIX, win32::DLL_SUFFIX),
+                abi::OsMacos => (macos::DLL_PREFIX, macos::DLL_SUFFIX),
                 abi::OsLinux => (linux::DLL_PREFIX, linux::DLL_SUFFIX),
                 abi::OsAndroid => (android::DLL_PREFIX, android::DLL_SUFFIX),
                 abi::OsFreebsd => (freebsd::DLL_PREFIX, freebsd::DLL_SUFFIX),
             };
+            // C programs link to a cdylib by its plain name
+            let libname = match output {
+                session::OutputCdylib => lm.pkgid.name.clone(),
+                _ => libname.clone(),
+            };
             out_filename.with_filename(format!("{}{}{}", prefix, libname, suffix))
         }
         session::OutputStaticlib => {
@@ -791,20 +1555,78 @@ fn link_binary_output(sess: Session,
                            obj_filename.display()));
     }
 
-    match output {
+    let unlinked_libs = match output {
         session::OutputRlib => {
             link_rlib(sess, Some(trans), obj_filename, &out_filename);
+            ~[]
         }
         session::OutputStaticlib => {
-            link_staticlib(sess, obj_filename, &out_filename);
-        }
-        session::OutputExecutable => {
-            link_natively(sess, false, obj_filename, &out_filename);
+            link_staticlib(sess, obj_filename, &out_filename)
         }
+        session::OutputExecutable |
+        session::OutputCdylib |
         session::OutputDylib => {
-            link_natively(sess, true, obj_filename, &out_filename);
+            link_natively(sess, trans, output, obj_filename, &out_filename);
+            ~[]
+        }
+    };
+    sess.output_files.push(out_filename);
+    unlinked_libs
+}
+
+// Writes a pkg-config file named after the crate next to the library outputs
+// so that C programs can find out how to link against them. Dylibs take care
+// of their own dependencies, but staticlibs also need the native libraries
+// which weren't bundled into them (`native_libs`) to be linked. When both are
+// built, the dylib is what's linked by default and the staticlib's libraries
+// are only listed for `pkg-config --static`.
+fn write_pkg_config(sess: Session,
+                    lm: &LinkMeta,
+                    dylib: bool,
+                    native_libs: &[(cstore::NativeLibaryKind, ~str)],
+                    out_filename: &Path) {
+    // The directory is written as the bytes of its path, which needn't be
+    // UTF-8 any more than pkg-config needs them to be
+    let libdir = match sess.opts.cg.pkg_config_libdir {
+        Some(ref dir) => dir.as_bytes().to_owned(),
+        None => os::make_absolute(&out_filename.dir_path()).as_vec().to_owned(),
+    };
+
+    let mut private = ~[];
+    for &(kind, ref lib) in native_libs.iter() {
+        let flag = match kind {
+            cstore::NativeStatic | cstore::NativeStaticWholeArchive => continue,
+            cstore::NativeUnknown | cstore::NativeStaticNobundle => "-l" + *lib,
+            cstore::NativeFramework => "-framework " + *lib,
+            cstore::NativeFrameworkWeak => "-weak_framework " + *lib,
+        };
+        if !private.contains(&flag) {
+            private.push(flag);
         }
     }
+
+    let mut libs = ~[~"-L${libdir}", "-l" + output_lib_filename(sess, lm)];
+    if !dylib {
+        libs.push_all_move(private);
+        private = ~[];
+    }
+
+    let mut contents = format!("\n\
+                                Name: {}\n\
+                                Description: The {} Rust library\n\
+                                Version: {}\n\
+                                Libs: {}\n",
+                               lm.pkgid.name, lm.pkgid.name,
+                               lm.pkgid.version_or_default(), libs.connect(" "));
+    if private.len() > 0 {
+        contents.push_str(format!("Libs.private: {}\n", private.connect(" ")));
+    }
+    let path = out_filename.with_filename(format!("{}.pc", lm.pkgid.name));
+    let mut file = fs::File::create(&path);
+    file.write(bytes!("libdir="));
+    file.write(libdir);
+    file.write(bytes!("\n"));
+    file.write(contents.as_bytes());
 }
 
 // Create an 'rlib'
@@ -813,18 +1635,59 @@ fn link_binary_output(sess: Session,
 // rlib primarily contains the object file of the crate, but it also contains
 // all of the object files from native libraries. This is done by unzipping
 // native libraries and inserting all of the contents into this archive.
+// Libraries with the `static-nobundle` kind are the exception; they're only
+// recorded in the metadata and linked along with the final binary.
 fn link_rlib(sess: Session,
              trans: Option<&CrateTranslation>, // None == no metadata/bytecode
              obj_filename: &Path,
              out_filename: &Path) -> Archive {
-    let mut a = Archive::create(sess, out_filename, obj_filename);
+    // The metadata is written out for `ar` among the other intermediate files
+    // of the crate (see `CrateTranslation::tmpdir`), as its name isn't unique
+    // to the crate.
+    let metadata = match trans {
+        Some(trans) => {
+            let path = trans.tmpdir.path().join(METADATA_FILENAME);
+            let mut file = fs::File::create(&path);
+            file.write(encoder::metadata_header());
+            file.write(trans.metadata);
+            Some(path)
+        }
+        None => None,
+    };
+
+    // An rlib built with `-C bitcode-only-rlib` has no object file, so it
+    // starts with the objects of its native libraries, if any. Downstream
+    // crates generate the machine code from its bitcode when they're linked
+    // (see `link_upstream_rlib`).
+    let bitcode_only = trans.is_some() && sess.bitcode_only_rlib();
+    let mut a = if bitcode_only {
+        Archive::create_empty(sess, out_filename)
+    } else {
+        Archive::create(sess, out_filename, obj_filename)
+    };
 
     for &(ref l, kind) in cstore::get_used_libraries(sess.cstore).iter() {
         match kind {
             cstore::NativeStatic => {
                 a.add_native_library(l.as_slice());
             }
-            cstore::NativeFramework | cstore::NativeUnknown => {}
+            // Bundling its objects would leave it up to the linker which of
+            // them are pulled in, so the library is bundled as a whole below
+            cstore::NativeStaticWholeArchive => {}
+            cstore::NativeStaticNobundle => {}
+            cstore::NativeFramework | cstore::NativeFrameworkWeak |
+            cstore::NativeUnknown => {}
+        }
+    }
+
+    // Objects and archives given with `-C link-objects` are bundled like
+    // native static libraries.
+    for obj in sess.opts.cg.link_objects.iter() {
+        let path = Path::new(obj.as_slice());
+        if obj.ends_with(".a") {
+            a.add_static_archive(&path);
+        } else {
+            a.add_file(&path);
         }
     }
 
@@ -851,20 +1714,41 @@ fn link_rlib(sess: Session,
     // code above.
     match trans {
         Some(trans) => {
+            // Native libraries linked whole are bundled as archives of their
+            // own, which downstream crates link with the right flags (see
+            // `add_upstream_native_libraries`)
+            for &(ref l, kind) in cstore::get_used_libraries(sess.cstore).iter() {
+                if kind == cstore::NativeStaticWholeArchive {
+                    a.add_whole_archive(l.as_slice());
+                }
+            }
+
             // Instead of putting the metadata in an object file section, rlibs
             // contain the metadata in a separate file.
-            let metadata = obj_filename.with_filename(METADATA_FILENAME);
-            fs::File::create(&metadata).write(trans.metadata);
-            a.add_file(&metadata);
-            fs::unlink(&metadata);
+            let mut files = ~[metadata.unwrap()];
 
             // For LTO purposes, the bytecode of this library is also inserted
-            // into the archive.
-            let bc = obj_filename.with_extension("bc");
-            a.add_file(&bc);
-            if !sess.opts.save_temps {
-                fs::unlink(&bc);
+            // into the archive: a member for each codegen unit, and the index
+            // of them.
+            let name = trans.link.pkgid.name.as_slice();
+            let dir = trans.tmpdir.path();
+            let index = dir.join(lto::bytecode_index_name(name));
+            let units = fs::File::open(&index).read_to_end();
+            let mut bytecode = ~[];
+            for unit in lto::read_bytecode_index(str::from_utf8(units)).iter() {
+                bytecode.push(dir.join(unit.member.as_slice()));
+            }
+            bytecode.push(index);
+
+            // The temp dir goes away with the translation, so the bytecode
+            // is kept next to the output instead
+            if sess.opts.save_temps {
+                for bc in bytecode.iter() {
+                    fs::copy(bc, &obj_filename.with_filename(bc.filename().unwrap()));
+                }
             }
+            files.push_all_move(bytecode);
+            a.add_files(files);
         }
 
         None => {}
@@ -884,10 +1768,40 @@ fn link_rlib(sess: Session,
 // There's no need to include metadata in a static archive, so ensure to not
 // link in the metadata object file (and also don't prepare the archive with a
 // metadata file).
-fn link_staticlib(sess: Session, obj_filename: &Path, out_filename: &Path) {
+//
+// The native libraries which couldn't be bundled into the archive, and so must
+// be linked along with it, are returned.
+fn link_staticlib(sess: Session, obj_filename: &Path,
+                  out_filename: &Path) -> ~[(cstore::NativeLibaryKind, ~str)] {
     let mut a = link_rlib(sess, None, obj_filename, out_filename);
-    a.add_native_library("morestack");
+    let mut crates = ~[];
+    cstore::iter_crate_data(sess.cstore, |cnum, _| crates.push(cnum));
+
+    // libmorestack ships with rustc, so the consumer of a staticlib couldn't
+    // find it and it's bundled. Any other runtime library is left to them.
+    let mut native_libs = ~[];
+    for lib in runtime_libraries(sess, crates).move_iter() {
+        if "morestack" == lib.as_slice() {
+            a.add_native_library(lib);
+        } else {
+            native_libs.push((cstore::NativeUnknown, lib));
+        }
+    }
+
+    // Libraries which must be linked whole can't be forced on the consumer of
+    // a staticlib, so they're bundled like the other static libraries.
+    for &(ref lib, kind) in cstore::get_used_libraries(sess.cstore).iter() {
+        match kind {
+            cstore::NativeStatic => {}
+            cstore::NativeStaticWholeArchive => a.add_native_library(*lib),
+            cstore::NativeUnknown | cstore::NativeFramework |
+            cstore::NativeFrameworkWeak | cstore::NativeStaticNobundle => {
+                native_libs.push((kind, lib.clone()));
+            }
+        }
+    }
 
+    let tmpdir = TempDir::new("rustc").expect("needs a temp dir");
     let crates = cstore::get_used_crates(sess.cstore, cstore::RequireStatic);
     for &(cnum, ref path) in crates.iter() {
         let name = cstore::get_crate_data(sess.cstore, cnum).name;
@@ -898,142 +1812,895 @@ fn link_staticlib(sess: Session, obj_filename: &Path, out_filename: &Path) {
             }
         };
         a.add_rlib(&p, name, sess.lto());
-        let native_libs = csearch::get_native_libraries(sess.cstore, cnum);
-        for &(kind, ref lib) in native_libs.iter() {
+        if !sess.lto() && csearch::get_bitcode_only(sess.cstore, cnum) {
+            let obj = tmpdir.path().join(format!("r-{}-{}.o", name, name));
+            compile_bitcode_only(sess, name, &p, &obj);
+            a.add_file(&obj);
+        }
+        let libs = csearch::get_native_libraries(sess.cstore, cnum);
+        for &(kind, ref lib) in libs.iter() {
             let name = match kind {
-                cstore::NativeStatic => "static library",
+                // already bundled in the rlib added above
+                cstore::NativeStatic => continue,
+                cstore::NativeStaticWholeArchive => {
+                    let path = unpack_whole_archive(sess, &p, name, *lib,
+                                                    tmpdir.path());
+                    a.add_static_archive(&path);
+                    continue
+                }
                 cstore::NativeUnknown => "library",
                 cstore::NativeFramework => "framework",
+                cstore::NativeFrameworkWeak => "weak framework",
+                cstore::NativeStaticNobundle => "static library",
             };
             sess.warn(format!("unlinked native {}: {}", name, *lib));
+            native_libs.push((kind, lib.clone()));
         }
     }
+    native_libs
+}
+
+fn compile_bitcode_only(sess: Session, name: &str, rlib: &Path, obj: &Path) {
+    time(sess.time_passes(), format!("compiling {}.rlib", name), (), |()| {
+        sess.codegen_backend.compile_rlib_bitcode(sess, name, rlib, obj)
+    });
+}
+
+// Whether cc links its default libraries, which OS kernels and other code
+// running without a libc can't have
+fn default_linker_libraries(sess: Session) -> bool {
+    match sess.opts.cg.default_linker_libraries {
+        Some(default_libs) => default_libs,
+        None => !sess.targ_cfg.target_strs.no_default_libraries,
+    }
 }
 
 // Create a dynamic library or executable
 //
 // This will invoke the system linker/cc to create the resulting file. This
 // links to all upstream files as well.
-fn link_natively(sess: Session, dylib: bool, obj_filename: &Path,
-                 out_filename: &Path) {
-    let tmpdir = TempDir::new("rustc").expect("needs a temp dir");
+fn link_natively(sess: Session, trans: &CrateTranslation,
+                 output: session::OutputStyle,
+                 obj_filename: &Path, out_filename: &Path) {
+    // With `--print link-args` the commands are printed for the build system
+    // to run, in the order rustc would run them, so the files they refer to
+    // are kept in a directory next to the output rather than a temporary one
+    let tmpdir = if sess.opts.dry_run_link {
+        None
+    } else {
+        Some(TempDir::new("rustc").expect("needs a temp dir"))
+    };
+    let link_dir = match tmpdir {
+        Some(ref tmpdir) => tmpdir.path().clone(),
+        None => {
+            let dir = Path::new(out_filename.as_vec() + bytes!(".link-files"));
+            if dir.exists() {
+                fs::rmdir_recursive(&dir);
+            }
+            fs::mkdir(&dir, io::UserRWX);
+            dir
+        }
+    };
     // The invocations of cc share some flags across platforms
     let cc_prog = get_cc_prog(sess);
-    let mut cc_args = sess.targ_cfg.target_strs.cc_args.clone();
-    cc_args.push_all_move(link_args(sess, dylib, tmpdir.path(),
-                                    obj_filename, out_filename));
+    let mut cmd = cc_command(sess);
+    cmd.args(sess.targ_cfg.target_strs.cc_args);
+    let resources = resource_objects(sess, output, &link_dir);
+    link_args(sess, trans, output, &link_dir, resources, obj_filename,
+              out_filename, &mut cmd);
     if (sess.opts.debugging_opts & session::print_link_args) != 0 {
-        println!("{} link args: '{}'", cc_prog, cc_args.connect("' '"));
+        let args = cmd.args.map(|a| lossy_utf8(*a));
+        println!("{} link args: '{}'", cc_prog, args.connect("' '"));
     }
 
     // May have not found libraries in the right formats.
     sess.abort_if_errors();
 
+    // Distributed build systems run the linker through a program of their own,
+    // which may run it on another machine. Only the link itself goes through
+    // it: the C compiler is probed locally (see `cc_accepted_flags` and
+    // `native_library_search_dirs`).
+    let cmd = match sess.opts.cg.link_wrapper {
+        Some(ref wrapper) => {
+            if find_program(wrapper.as_slice()).is_none() {
+                sess.fatal(format!("the link wrapper `{}` was not found",
+                                   *wrapper));
+            }
+            cmd.wrapped(wrapper.as_slice())
+        }
+        None => cmd,
+    };
+
     // Invoke the system linker
-    debug!("{} {}", cc_prog, cc_args.connect(" "));
-    let prog = time(sess.time_passes(), "running linker", (), |()|
-                    run::process_output(cc_prog, cc_args));
+    debug!("{}", cmd.command_line());
+    if sess.opts.dry_run_link {
+        print_link_step(sess, &cmd, &link_dir);
+    } else {
+        let prog = time(sess.time_passes(), "running linker", (), |()| {
+            sess.profile("linker", out_filename.display().to_str(), || {
+                run_linker(sess, &cmd)
+            })
+        });
+
+        if !prog.status.success() {
+            sess.err(format!("linking with `{}` failed: {}", cc_prog,
+                             prog.status));
+            sess.note(format!("command: {}", cmd.command_line()));
+            match cc_version(&cc_command(sess)) {
+                Some(version) => {
+                    sess.note(format!("`{}` is {}", cc_prog, version))
+                }
+                None => {}
+            }
+            sess.note(demangle_symbols(prog.error + prog.output));
+            sess.abort_if_errors();
+        }
+    }
+
+    // Packed debug info is gathered from the object files (OSX) or from the
+    // .dwo files (ELF) into a single file next to the output
+    if sess.opts.debuginfo &&
+       sess.split_debuginfo() == session::SplitDebuginfoPacked {
+        if sess.targ_cfg.os == abi::OsMacos {
+            let mut cmd = Command::new("dsymutil");
+            cmd.allow_response_file();
+            cmd.arg_path("", out_filename);
+            run_link_step(sess, &cmd, &link_dir);
+        } else if sess.split_dwarf() {
+            let dwp = Path::new(out_filename.as_vec() + bytes!(".dwp"));
+            let mut cmd = Command::new("dwp");
+            cmd.allow_response_file();
+            cmd.arg_path("-e", out_filename);
+            cmd.arg("-o");
+            cmd.arg_path("", &dwp);
+            match run_link_step(sess, &cmd, &link_dir) {
+                Some(prog) if !prog.status.success() => {
+                    sess.err(format!("packing the debug info of `{}` failed: \
+                                      {}", out_filename.display(),
+                                     prog.status));
+                    sess.note(prog.error + prog.output);
+                    sess.abort_if_errors();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The OSX linker can't strip the output itself (see `link_args`). Only
+    // the local symbols are stripped, as dylibs need their global ones.
+    if sess.targ_cfg.os == abi::OsMacos &&
+       sess.opts.cg.strip != session::StripNone {
+        let mut cmd = Command::new("strip");
+        cmd.allow_response_file();
+        cmd.arg(if sess.opts.cg.strip == session::StripDebuginfo { "-S" }
+                else { "-x" });
+        cmd.arg_path("", out_filename);
+        match run_link_step(sess, &cmd, &link_dir) {
+            Some(prog) if !prog.status.success() => {
+                sess.err(format!("stripping `{}` failed: {}",
+                                 out_filename.display(), prog.status));
+                sess.note(prog.error + prog.output);
+                sess.abort_if_errors();
+            }
+            _ => {}
+        }
+    }
+}
 
-    if !prog.status.success() {
-        sess.err(format!("linking with `{}` failed: {}", cc_prog, prog.status));
-        sess.note(format!("{} arguments: '{}'", cc_prog, cc_args.connect("' '")));
-        sess.note(str::from_utf8_owned(prog.error + prog.output));
-        sess.abort_if_errors();
+// Runs a step of the link after the linker, or prints it with `--print
+// link-args`, in which case there's nothing to check.
+fn run_link_step(sess: Session, cmd: &Command,
+                 link_dir: &Path) -> Option<CommandOutput> {
+    if sess.opts.dry_run_link {
+        print_link_step(sess, cmd, link_dir);
+        None
+    } else {
+        Some(cmd.output())
     }
+}
 
+// Prints a step of the link for `--print link-args`. The arguments which
+// aren't UTF-8 are passed in a response file among the files of the link,
+// named after the program.
+fn print_link_step(sess: Session, cmd: &Command, link_dir: &Path) {
+    let prog = Path::new(cmd.prog.as_slice());
+    let name = format!("{}.args", prog.filestem_str().unwrap_or("command"));
+    match cmd.dry_run_line(&link_dir.join(name)) {
+        Some(line) => println!("{}", line),
+        None => {
+            sess.fatal(format!("`--print link-args` can't print the \
+                                arguments of `{}` which aren't UTF-8, as it \
+                                doesn't read a response file", cmd.prog));
+        }
+    }
+}
 
-    // On OSX, debuggers need this utility to get run to do some munging of
-    // the symbols
-    if sess.targ_cfg.os == abi::OsMacos && sess.opts.debuginfo {
-        // FIXME (#9639): This needs to handle non-utf8 paths
-        run::process_status("dsymutil",
-                            [out_filename.as_str().unwrap().to_owned()]);
+// Antivirus and indexing services on Windows briefly lock the files which have
+// just been written, which makes the linker fail to open the objects or to
+// write the output every now and then. Those failures are retried a few times,
+// backing off a little longer each time, before they are reported.
+static LINKER_RETRIES: uint = 5;
+
+fn run_linker(sess: Session, cmd: &Command) -> CommandOutput {
+    let mut prog = cmd.output();
+    if sess.targ_cfg.os != abi::OsWin32 { return prog }
+    let mut delay = 100;
+    for _ in range(0, LINKER_RETRIES) {
+        if prog.status.success() { break }
+        if !is_file_locking_error(prog.error + prog.output) { break }
+        debug!("linker failed on a locked file, retrying in {}ms", delay);
+        timer::sleep(delay);
+        delay *= 2;
+        prog = cmd.output();
     }
+    prog
+}
+
+// Whether the linker's output tells of a file which was locked by some other
+// process, rather than of an actual error. Only sharing and lock violations
+// are, as the messages of ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+// go; a file which can't be accessed at all won't be any time soon. mingw's
+// ld reports a sharing violation on its output as "Permission denied" though,
+// which is only taken for one on the line about the output file.
+fn is_file_locking_error(output: &str) -> bool {
+    static MESSAGES: &'static [&'static str] = &[
+        "being used by another process",
+        "another process has locked a portion of the file",
+    ];
+    MESSAGES.iter().any(|m| output.contains(*m)) ||
+        output.lines().any(|line| {
+            line.contains("cannot open output file") &&
+                line.contains("Permission denied")
+        })
 }
 
 fn link_args(sess: Session,
-             dylib: bool,
+             trans: &CrateTranslation,
+             output: session::OutputStyle,
              tmpdir: &Path,
+             resources: &[Path],
              obj_filename: &Path,
-             out_filename: &Path) -> ~[~str] {
+             out_filename: &Path,
+             cmd: &mut Command) {
+    let cdylib = output == session::OutputCdylib;
+    let dylib = cdylib || output == session::OutputDylib;
+    let static_exe = static_executable(sess, output);
 
     // The default library location, we need this to find the runtime.
     // The location of crates will be determined as needed.
-    // FIXME (#9639): This needs to handle non-utf8 paths
-    let lib_path = sess.filesearch.get_target_lib_path();
-    let stage: ~str = ~"-L" + lib_path.as_str().unwrap();
-
-    let mut args = ~[stage];
+    cmd.arg_path("-L", &sess.filesearch.get_target_lib_path());
+
+    // The mingw runtime shipped with rustc is linked in rather than that of
+    // whichever gcc is installed, so that the programs built don't depend on
+    // it. `-B` has gcc look for its startup objects and libraries there before
+    // anywhere else.
+    if sess.targ_cfg.os == abi::OsWin32 {
+        let runtime = bundled_mingw_dir(sess).join("lib");
+        if runtime.exists() {
+            cmd.arg_path("-B", &runtime);
+        }
+    }
 
-    // FIXME (#9639): This needs to handle non-utf8 paths
-    args.push_all([
-        ~"-o", out_filename.as_str().unwrap().to_owned(),
-        obj_filename.as_str().unwrap().to_owned()]);
+    cmd.arg("-o");
+    cmd.arg_path("", out_filename);
+    cmd.arg_path("", obj_filename);
 
     // When linking a dynamic library, we put the metadata into a section of the
     // executable. This metadata is in a separate object file from the main
-    // object file, so we link that in here.
-    if dylib {
-        let metadata = obj_filename.with_extension("metadata.o");
-        args.push(metadata.as_str().unwrap().to_owned());
+    // object file, so we link that in here. A cdylib isn't meant to be used by
+    // rust crates, so it goes without.
+    if dylib && !cdylib {
+        cmd.arg_path("", &obj_filename.with_extension("metadata.o"));
     }
 
-    if sess.targ_cfg.os == abi::OsLinux {
-        // GNU-style linkers will use this to omit linking to libraries which
-        // don't actually fulfill any relocations, but only for libraries which
-        // follow this flag. Thus, use it before specifing libraries to link to.
-        args.push(~"-Wl,--as-needed");
+    // Objects and archives given with `-C link-objects` are linked right along
+    // with the crate's own object.
+    cmd.args(sess.opts.cg.link_objects);
 
-        // GNU-style linkers support optimization with -O. --gc-sections
-        // removes metadata and potentially other useful things, so don't
-        // include it. GNU ld doesn't need a numeric argument, but other linkers
-        // do.
-        if sess.opts.optimize == session::Default ||
-           sess.opts.optimize == session::Aggressive {
-            args.push(~"-Wl,-O1");
-        }
+    // Windows resources (icons, version info and the like) and manifests are
+    // linked as objects of their own (see `resource_objects`)
+    for obj in resources.iter() {
+        cmd.arg_path("", obj);
     }
 
-    add_local_native_libraries(&mut args, sess);
-    add_upstream_rust_crates(&mut args, sess, dylib, tmpdir);
-    add_upstream_native_libraries(&mut args, sess);
-
-    // # Telling the linker what we're doing
+    // Have the C compiler pick the static versions of libc and of every other
+    // native library (libmorestack is always linked statically).
+    if static_exe {
+        cmd.arg("-static");
+    }
 
-    if dylib {
-        // On mac we need to tell the linker to let this library be rpathed
-        if sess.targ_cfg.os == abi::OsMacos {
-            args.push(~"-dynamiclib");
-            args.push(~"-Wl,-dylib");
-            // FIXME (#9639): This needs to handle non-utf8 paths
-            args.push(~"-Wl,-install_name,@rpath/" +
-                      out_filename.filename_str().unwrap());
-        } else {
-            args.push(~"-shared")
+    // All code is already generated as PIC, so all it takes to load an
+    // executable at a random address is to link it as a PIE. Static
+    // executables can't be, as there's no loader to relocate them.
+    if sess.opts.cg.pie && output == session::OutputExecutable && !static_exe {
+        match sess.targ_cfg.os {
+            abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => cmd.arg("-pie"),
+            abi::OsMacos => cmd.arg("-Wl,-pie"),
+            abi::OsWin32 => {}
         }
     }
 
-    if sess.targ_cfg.os == abi::OsFreebsd {
-        args.push_all([~"-L/usr/local/lib",
-                       ~"-L/usr/local/lib/gcc46",
-                       ~"-L/usr/local/lib/gcc44"]);
+    // Programs which do their own startup are linked without the C runtime's
+    // and start wherever they say. Nothing calls the `main` generated for
+    // `fn main` or `#[start]` then, unless it's the entry point itself.
+    if output == session::OutputExecutable {
+        if sess.opts.cg.no_startfiles {
+            cmd.arg("-nostartfiles");
+            if sess.opts.cg.entry.is_none() &&
+               *sess.entry_type != Some(session::EntryNone) {
+                sess.warn("the program's `main` isn't called without the C \
+                           runtime's startup objects, use #[no_main] and \
+                           -C entry to give the function it starts at");
+            }
+        }
+        match sess.opts.cg.entry {
+            Some(ref entry) if sess.targ_cfg.os == abi::OsMacos => {
+                cmd.arg("-Wl,-e,_" + *entry);
+            }
+            Some(ref entry) => cmd.arg("-Wl,-e," + *entry),
+            None => {}
+        }
+    }
+
+    // Starting an executable of the console subsystem opens a console window,
+    // which GUI applications are linked for the windows subsystem to avoid
+    if sess.opts.cg.subsystem == session::SubsystemWindows {
+        if sess.targ_cfg.os != abi::OsWin32 {
+            sess.warn("-C subsystem only applies to Windows targets");
+        } else if output == session::OutputExecutable {
+            cmd.arg("-Wl,--subsystem,windows");
+        }
+    }
+
+    cmd.args(hardening_args(sess, output));
+    cmd.args(gc_sections_args(sess, output));
+
+    // The linker hashes the output into the GNU build id, which debuggers and
+    // symbol servers use to match a stripped binary with its debug file. When
+    // build ids are disabled, any id the toolchain adds by default has to be
+    // turned off as well.
+    match sess.targ_cfg.os {
+        abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => {
+            if sess.opts.cg.build_id.unwrap_or(true) {
+                let build_id = ~[~"-Wl,--build-id=sha1"];
+                cmd.args(cc_accepted_flags(sess, &cc_command(sess), build_id));
+            } else {
+                cmd.arg("-Wl,--build-id=none");
+            }
+        }
+        abi::OsMacos | abi::OsWin32 => {}
+    }
+
+    // CodeView debug info is gathered into a .pdb next to the output, named
+    // after it as MSVC does. Only lld writes one: its MinGW driver hands the
+    // link to lld-link, its MSVC-flavor linker, which is asked for the .pdb.
+    // GNU ld would silently drop the debug info, so lld is required.
+    if sess.opts.cg.debuginfo_format == session::DebuginfoCodeView {
+        if sess.targ_cfg.os != abi::OsWin32 {
+            sess.warn("-C debuginfo-format=codeview only applies to Windows \
+                       targets");
+        } else if sess.codeview() {
+            let use_lld = ~[~"-fuse-ld=lld"];
+            if cc_accepted_flags(sess, &cc_command(sess), use_lld).is_empty() {
+                sess.err(format!("-C debuginfo-format=codeview needs the lld \
+                                  linker, which `{}` can't use",
+                                 get_cc_prog(sess)));
+            } else {
+                cmd.args(use_lld);
+                cmd.arg_path("-Wl,--pdb=", &out_filename.with_extension("pdb"));
+            }
+        }
+    }
+
+    // GNU-style linkers strip the output as they write it
+    if sess.targ_cfg.os != abi::OsMacos {
+        match sess.opts.cg.strip {
+            session::StripNone => {}
+            session::StripDebuginfo => cmd.arg("-Wl,-S"),
+            session::StripSymbols => cmd.arg("-Wl,-s"),
+        }
+    }
+
+    // Monomorphization leaves many functions with identical code behind, which
+    // gold can fold into one. The `safe` mode leaves alone the functions whose
+    // address is taken, as those have to stay distinct.
+    if sess.opts.cg.icf {
+        match sess.targ_cfg.os {
+            abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => {
+                cmd.arg("-fuse-ld=gold");
+                cmd.arg("-Wl,--icf=safe");
+            }
+            abi::OsMacos | abi::OsWin32 => {}
+        }
+    }
+
+    // The canaries of `-C stack-protector` need support code from libssp on
+    // some platforms, which the C compiler knows to link in.
+    if sess.opts.cg.stack_protector != session::SspNone {
+        cmd.arg("-fstack-protector");
     }
 
-    // Stack growth requires statically linking a __morestack function
-    args.push(~"-lmorestack");
+    if sess.targ_cfg.os == abi::OsLinux {
+        // GNU-style linkers will use this to omit linking to libraries which
+        // don't actually fulfill any relocations, but only for libraries which
+        // follow this flag. Thus, use it before specifing libraries to link to.
+        let mut flags = ~[~"-Wl,--as-needed"];
+
+        // GNU-style linkers support optimization with -O. GNU ld doesn't
+        // need a numeric argument, but other linkers do.
+        if sess.opts.optimize == session::Default ||
+           sess.opts.optimize == session::Aggressive {
+            flags.push(~"-Wl,-O1");
+        }
+
+        // Neither is needed for a correct link, so they're left out for the
+        // drivers and linkers which don't know them
+        let accepted = cc_accepted_flags(sess, &cc_command(sess), flags);
+        cmd.args(accepted);
+    }
+
+    // The native libraries of the local crate come before the rust crates and
+    // those of upstream crates after them. A library needed in both places is
+    // only linked in the latter, where it satisfies both.
+    let mut local_libs = ~[];
+    add_local_native_libraries(cmd, &mut local_libs, sess);
+    let mut crate_args = ~[];
+    let static_crates = add_upstream_rust_crates(&mut crate_args, sess, output,
+                                                 static_exe, tmpdir);
+    let mut upstream_libs = ~[];
+    add_upstream_native_libraries(&mut upstream_libs, sess, static_crates,
+                                  tmpdir);
+    let upstream_libs = dedup_libraries(upstream_libs);
+    if sess.opts.cg.check_native_libs {
+        check_native_libraries(sess, static_crates.as_slice());
+    }
+    for lib in dedup_libraries(local_libs).move_iter() {
+        if !upstream_libs.contains(&lib) {
+            cmd.raw_args(lib);
+        }
+    }
+    cmd.raw_args(crate_args);
+    cmd.args(kept_symbol_args(sess, trans));
+    cmd.raw_args(group_libraries(sess, upstream_libs));
+
+    // # Telling the linker what we're doing
+
+    // Otherwise the linker marks the output as requiring the OSX release it's
+    // built on
+    match sess.targ_cfg.macos_min_version {
+        Some(ref version) => cmd.arg(format!("-mmacosx-version-min={}", *version)),
+        None => {}
+    }
+
+    if dylib {
+        // On mac we need to tell the linker to let this library be rpathed
+        if sess.targ_cfg.os == abi::OsMacos {
+            cmd.arg("-dynamiclib");
+            cmd.arg("-Wl,-dylib");
+            cmd.arg_path("-Wl,-install_name,@rpath/",
+                         &Path::new(out_filename.filename().unwrap()));
+        } else {
+            cmd.arg("-shared");
+
+            // Record the name the library is installed under rather than
+            // leaving dependents to refer to it by its path at link time
+            if sess.targ_cfg.os != abi::OsWin32 {
+                match sess.opts.cg.soname {
+                    Some(ref soname) => cmd.arg("-Wl,-soname," + *soname),
+                    None => cmd.arg_path("-Wl,-soname,",
+                                         &Path::new(out_filename.filename().unwrap())),
+                }
+            }
+        }
+
+        cmd.raw_args(export_list_args(sess, trans, cdylib, tmpdir,
+                                      out_filename));
+    }
+
+    if sess.targ_cfg.os == abi::OsFreebsd {
+        cmd.args([~"-L/usr/local/lib",
+                  ~"-L/usr/local/lib/gcc46",
+                  ~"-L/usr/local/lib/gcc44"]);
+    }
+
+    // The libraries the runtime needs, by default those of the target (stack
+    // growth requires statically linking a __morestack function). Without the
+    // C compiler's default libraries, these are all that's linked besides the
+    // crates and their native libraries.
+    if !default_linker_libraries(sess) {
+        cmd.arg("-nodefaultlibs");
+    }
+    for lib in runtime_libraries(sess, static_crates).iter() {
+        cmd.arg("-l" + *lib);
+    }
 
     // FIXME (#2397): At some point we want to rpath our guesses as to
     // where extern libraries might live, based on the
     // addl_lib_search_paths
-    args.push_all(rpath::get_rpath_flags(sess, out_filename));
+    //
+    // Rpaths can be turned off with `-C rpath=no`. They're off by default for
+    // a cdylib, whose rust dependencies are all linked statically, and a
+    // static executable doesn't load any libraries at all.
+    let use_rpath = match sess.opts.cg.rpath {
+        Some(use_rpath) => use_rpath,
+        None => !cdylib,
+    };
+    if use_rpath && !static_exe {
+        cmd.raw_args(rpath::get_rpath_flags(sess, out_filename));
+    }
 
     // Finally add all the linker arguments provided on the command line along
     // with any #[link_args] attributes found inside the crate
-    args.push_all(sess.opts.linker_args);
+    cmd.args(sess.opts.linker_args);
     for arg in cstore::get_used_link_args(sess.cstore).iter() {
-        args.push(arg.clone());
+        cmd.arg(arg.as_slice());
     }
-    return args;
+}
+
+// Compiles the resources given with `-C link-resource`, and the manifest given
+// with `-C manifest`, for the linker. The manifest is embedded as a resource
+// of the RT_MANIFEST type (24), with the ID which Windows looks for in
+// executables (1) or in DLLs (2).
+//
+// windres compiles them into COFF objects. MSVC's rc compiles them into .res
+// files instead, which the linker takes as they are, and so are the .res
+// files given with `-C link-resource`.
+fn resource_objects(sess: Session, output: session::OutputStyle,
+                    tmpdir: &Path) -> ~[Path] {
+    if sess.opts.cg.link_resource.is_empty() && sess.opts.cg.manifest.is_none() {
+        return ~[];
+    }
+    if sess.targ_cfg.os != abi::OsWin32 {
+        sess.warn("-C link-resource and -C manifest only apply to Windows \
+                   targets");
+        return ~[];
+    }
+
+    let mut inputs = sess.opts.cg.link_resource.map(|r| Path::new(r.as_slice()));
+    match sess.opts.cg.manifest {
+        Some(ref manifest) => {
+            let id = if output == session::OutputExecutable { 1 } else { 2 };
+            let manifest = os::make_absolute(&Path::new(manifest.as_slice()));
+            let mut script = format!("{} 24 \"", id).into_bytes();
+            for &b in manifest.as_vec().iter() {
+                if b == '\\' as u8 {
+                    script.push(b);
+                }
+                script.push(b);
+            }
+            script.push_all(bytes!("\"\n"));
+            let rc = tmpdir.join("manifest.rc");
+            fs::File::create(&rc).write(script);
+            inputs.push(rc);
+        }
+        None => {}
+    }
+
+    let prog = resource_compiler(sess);
+    let msvc = Path::new(prog.as_slice()).filestem_str()
+                   .map_default(false, |stem| stem.eq_ignore_ascii_case("rc"));
+    let mut objects = ~[];
+    for (i, input) in inputs.iter().enumerate() {
+        let mut cmd = Command::new(prog.as_slice());
+        cmd.allow_response_file();
+        let obj = if msvc {
+            if input.extension_str() == Some("res") {
+                objects.push(input.clone());
+                continue
+            }
+            let res = tmpdir.join(format!("resource{}.res", i));
+            cmd.arg("/nologo");
+            cmd.arg_path("/fo", &res);
+            cmd.arg_path("", input);
+            res
+        } else {
+            let obj = tmpdir.join(format!("resource{}.o", i));
+            cmd.args([~"-O", ~"coff", ~"-i"]);
+            cmd.arg_path("", input);
+            cmd.arg("-o");
+            cmd.arg_path("", &obj);
+            obj
+        };
+        debug!("{}", cmd.command_line());
+        let out = cmd.output();
+        if !out.status.success() {
+            sess.err(format!("compiling the resource `{}` failed: {}",
+                             input.display(), out.status));
+            sess.note(format!("command: {}", cmd.command_line()));
+            sess.note(out.error + out.output);
+            sess.abort_if_errors();
+        }
+        objects.push(obj);
+    }
+    objects
+}
+
+// The resource compiler for the target: the one given with `-C rc`, or else
+// the windres of the toolchain that the C compiler belongs to. A cross
+// compiler like `i686-w64-mingw32-gcc` comes with `i686-w64-mingw32-windres`,
+// and when cross compiling with any other, the one prefixed with the target
+// triple is used.
+fn resource_compiler(sess: Session) -> ~str {
+    match sess.opts.cg.rc {
+        Some(ref rc) => return rc.clone(),
+        None => {}
+    }
+    let cc = get_cc_prog(sess);
+    let cc = Path::new(cc.as_slice());
+    let stem = cc.filestem_str().unwrap_or("");
+    for suffix in ["-gcc", "-g++", "-clang", "-cc"].iter() {
+        if stem.ends_with(*suffix) {
+            let prefix = stem.slice_to(stem.len() - suffix.len());
+            let windres = format!("{}-windres", prefix);
+            if cc.dir_path() == Path::new(".") {
+                return windres;
+            }
+            return cc.dir_path().join(windres).display().to_str();
+        }
+    }
+    if cfg!(windows) {
+        ~"windres"
+    } else {
+        format!("{}-windres", sess.opts.target_triple)
+    }
+}
+
+// With every function and static in a section of its own, the linker can
+// drop all of the code and data which the program never references. This is
+// only done for executables: nothing references the metadata of a library,
+// yet it has to stay in the output.
+//
+// The symbols the linker has to keep (see `kept_symbol_args`) are roots of the
+// collection, so their sections survive it.
+fn gc_sections_args(sess: Session, output: session::OutputStyle) -> ~[~str] {
+    let cg = &sess.opts.cg;
+    if output != session::OutputExecutable ||
+       !(cg.function_sections || cg.data_sections) {
+        return ~[];
+    }
+    match sess.targ_cfg.os {
+        abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => {
+            ~[~"-Wl,--gc-sections"]
+        }
+        abi::OsMacos => ~[~"-Wl,-dead_strip"],
+        abi::OsWin32 => ~[],
+    }
+}
+
+// Nothing refers to the items placed in sections of their own (interrupt
+// vectors and the like) or to static constructors, so the linker is told that
+// their symbols are needed. That way the archive members defining them are
+// pulled out of rlibs and static libraries, and their sections are kept by
+// `--gc-sections`.
+fn kept_symbol_args(sess: Session, trans: &CrateTranslation) -> ~[~str] {
+    let mut kept = trans.kept_symbols.clone();
+    cstore::iter_crate_data(sess.cstore, |cnum, _| {
+        kept.push_all_move(csearch::get_kept_symbols(sess.cstore, cnum));
+    });
+    kept.map(|sym| {
+        match sess.targ_cfg.os {
+            abi::OsMacos => "-Wl,-u,_" + *sym,
+            // Like Mach-O, 32-bit COFF prefixes C symbols with an underscore
+            abi::OsWin32 if sess.targ_cfg.arch == abi::X86 => {
+                "-Wl,--undefined=_" + *sym
+            }
+            abi::OsLinux | abi::OsAndroid | abi::OsFreebsd | abi::OsWin32 => {
+                "-Wl,--undefined=" + *sym
+            }
+        }
+    })
+}
+
+// Hardening flags. On ELF targets, the stack is never executable, and the
+// data which is only written by the dynamic loader is made read-only after
+// relocation (RELRO). With full RELRO, all symbols are bound at load time
+// (BIND_NOW) so that the GOT can be made read-only as well. That's the
+// default for executables; libraries default to partial RELRO, sparing the
+// programs which load them the binding of every symbol up front.
+//
+// Windows binaries opt into ASLR (with the full 64-bit address space on x86_64)
+// and DEP by default, which each have a `-C` option to opt out. These are the
+// `/DYNAMICBASE`, `/HIGHENTROPYVA` and `/NXCOMPAT` of the MSVC linker.
+fn hardening_args(sess: Session, output: session::OutputStyle) -> ~[~str] {
+    let cg = &sess.opts.cg;
+    if sess.targ_cfg.os != abi::OsWin32 &&
+       (cg.dynamic_base.is_some() || cg.nx_compat.is_some() ||
+        cg.high_entropy_va.is_some()) {
+        sess.warn("-C dynamic-base, -C nx-compat and -C high-entropy-va only \
+                   apply to Windows targets");
+    }
+    match sess.targ_cfg.os {
+        abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => {}
+        abi::OsMacos => return ~[],
+        abi::OsWin32 => return windows_hardening_args(sess),
+    }
+
+    let mut args = ~[~"-Wl,-z,noexecstack"];
+    let level = match sess.opts.cg.relro_level {
+        Some(level) => level,
+        None if output == session::OutputExecutable => session::RelroFull,
+        None => session::RelroPartial,
+    };
+    match level {
+        session::RelroOff => args.push(~"-Wl,-z,norelro"),
+        session::RelroPartial => args.push(~"-Wl,-z,relro"),
+        session::RelroFull => {
+            args.push(~"-Wl,-z,relro");
+            args.push(~"-Wl,-z,now");
+        }
+    }
+    args
+}
+
+// The linkers which don't know one of the flags (`--high-entropy-va` came
+// with binutils 2.25) go without it, with a warning. Before binutils 2.36, ld
+// also wrote no relocations into executables, without which Windows loads
+// them at their preferred address after all; that can't be told from the
+// flags the linker accepts, so it isn't checked.
+fn windows_hardening_args(sess: Session) -> ~[~str] {
+    let cg = &sess.opts.cg;
+    let mut args = ~[];
+    let dynamic_base = cg.dynamic_base.unwrap_or(true);
+    if dynamic_base {
+        args.push(~"-Wl,--dynamicbase");
+    }
+    if cg.nx_compat.unwrap_or(true) {
+        args.push(~"-Wl,--nxcompat");
+    }
+    // A high entropy address is only of use to relocatable 64-bit images
+    let high_entropy_va = cg.high_entropy_va.unwrap_or(true);
+    if high_entropy_va && !dynamic_base && cg.high_entropy_va.is_some() {
+        sess.warn("-C high-entropy-va has no effect without -C dynamic-base");
+    } else if high_entropy_va && dynamic_base &&
+              sess.targ_cfg.arch == abi::X86_64 {
+        args.push(~"-Wl,--high-entropy-va");
+    }
+
+    let accepted = cc_accepted_flags(sess, &cc_command(sess), args);
+    for arg in args.iter() {
+        if !accepted.contains(arg) {
+            sess.warn(format!("the linker doesn't support `{}`, so the output \
+                               is linked without it", *arg));
+        }
+    }
+    accepted
+}
+
+// Whether `-C static-executable` applies to the output being linked. An
+// executable without any shared libraries at all isn't possible on OSX, whose
+// libSystem only comes as a dylib.
+fn static_executable(sess: Session, output: session::OutputStyle) -> bool {
+    if !sess.opts.cg.static_executable ||
+       output != session::OutputExecutable {
+        return false
+    }
+    if sess.targ_cfg.os == abi::OsMacos {
+        sess.err("static executables are not supported on OSX");
+        return false
+    }
+    true
+}
+
+// Rather than exporting all of its symbols, a dylib only exports those which
+// downstream crates may refer to (see `exported_symbols` in trans) and hides
+// the rest. This keeps the dynamic symbol table small, which in turn speeds up
+// loading the library. A cdylib is only used from C, so it just exports the
+// symbols which aren't mangled.
+//
+// The symbols of native static libraries which are linked into the dylib may
+// be needed downstream as well, but we don't know what they are, so in that
+// case everything is exported as before.
+//
+// Where version scripts are supported, the exported symbols are also versioned
+// (see `version_script`) unless a script was given with `-C version-script`.
+//
+// On Windows, the exports are listed in a .def file next to the DLL, and the
+// linker writes the import library which consumers link against next to it as
+// well, named like MSVC's (`foo.lib`), which the GNU linker looks for too.
+fn export_list_args(sess: Session, trans: &CrateTranslation, cdylib: bool,
+                    tmpdir: &Path, out_filename: &Path) -> ~[~[u8]] {
+    let libs = cstore::get_used_libraries(sess.cstore);
+    let export_all = !cdylib &&
+        libs.iter().any(|&(_, kind)| {
+            match kind {
+                cstore::NativeStatic | cstore::NativeStaticWholeArchive |
+                cstore::NativeStaticNobundle => true,
+                cstore::NativeUnknown | cstore::NativeFramework |
+                cstore::NativeFrameworkWeak => false,
+            }
+        });
+    let symbols = if cdylib {
+        trans.unmangled_symbols.map(|&(ref sym, _)| sym.clone())
+    } else {
+        trans.exported_symbols.clone()
+    };
+
+    match sess.targ_cfg.os {
+        abi::OsMacos => {
+            if export_all { return ~[] }
+            let path = tmpdir.join("exported_symbols");
+            let mut list = ~"";
+            for sym in symbols.iter() {
+                list.push_str(format!("_{}\n", *sym));
+            }
+            fs::File::create(&path).write(list.as_bytes());
+            ~[path_arg("-Wl,-exported_symbols_list,", &path)]
+        }
+        abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => {
+            match sess.opts.cg.version_script {
+                Some(ref script) => {
+                    return ~[("-Wl,--version-script=" + *script).into_bytes()];
+                }
+                None => {}
+            }
+            let symbols = if export_all {
+                None
+            } else {
+                Some(symbols.as_slice())
+            };
+            let path = tmpdir.join("version_script");
+            let script = version_script(&trans.link, symbols);
+            fs::File::create(&path).write(script.as_bytes());
+            ~[path_arg("-Wl,--version-script=", &path)]
+        }
+        abi::OsWin32 => {
+            let mut args = ~[];
+            if !export_all {
+                let def = out_filename.with_extension("def");
+                let def_text = def_file(out_filename, symbols,
+                                        trans.exported_statics);
+                fs::File::create(&def).write(def_text.as_bytes());
+                args.push(path_arg("", &def));
+            }
+            let implib = out_filename.with_extension("lib");
+            args.push(path_arg("-Wl,--out-implib,", &implib));
+            args
+        }
+    }
+}
+
+// Generates a module-definition (.def) file exporting the given symbols from
+// the DLL at `dll`. Those among `statics` are marked as data, so that the
+// import library doesn't provide a call thunk for them, and they have to be
+// imported (see `set_dllimport` in trans).
+fn def_file(dll: &Path, symbols: &[~str], statics: &[~str]) -> ~str {
+    let mut def = format!("LIBRARY {}\nEXPORTS\n", dll.filename_str().unwrap());
+    for sym in symbols.iter() {
+        if statics.contains(sym) {
+            def.push_str(format!("    {} DATA\n", *sym));
+        } else {
+            def.push_str(format!("    {}\n", *sym));
+        }
+    }
+    def
+}
+
+// Generates a linker version script placing the given symbols (or all of them
+// if `None`) in a version node named after the crate and its version, such as
+// `FOO_0.9`. The remaining symbols are hidden. Versioning the symbols allows
+// several versions of a library to coexist in one process.
+fn version_script(lm: &LinkMeta, symbols: Option<&[~str]>) -> ~str {
+    let mut node = ~"";
+    for c in lm.pkgid.name.to_ascii_upper().chars() {
+        match c {
+            'A' .. 'Z' | '0' .. '9' | '_' => node.push_char(c),
+            _ => node.push_char('_'),
+        }
+    }
+    node.push_char('_');
+    node.push_str(lm.pkgid.version_or_default());
+
+    let mut script = format!("{} \\{\n  global:\n", node);
+    match symbols {
+        Some(symbols) => {
+            for sym in symbols.iter() {
+                script.push_str(format!("    {};\n", *sym));
+            }
+        }
+        None => script.push_str("    *;\n"),
+    }
+    script.push_str("  local:\n    *;\n};\n");
+    script
 }
 
 // # Native library linking
@@ -1047,29 +2714,225 @@ fn link_args(sess: Session,
 // Also note that the native libraries linked here are only the ones located
 // in the current crate. Upstream crates with native library dependencies
 // may have their native library pulled in above.
-fn add_local_native_libraries(args: &mut ~[~str], sess: Session) {
-    for path in sess.opts.addl_lib_search_paths.iter() {
-        // FIXME (#9639): This needs to handle non-utf8 paths
-        args.push("-L" + path.as_str().unwrap().to_owned());
+//
+// The arguments for each library are pushed onto `libs` as a unit so that
+// they can be deduplicated later on.
+fn add_local_native_libraries(cmd: &mut Command, libs: &mut ~[~[~[u8]]],
+                              sess: Session) {
+    for path in sess.native_search_paths().iter() {
+        cmd.arg_path("-L", path);
+    }
+
+    // Frameworks are looked for in the directories given with
+    // `--framework-path` before the system ones
+    if sess.targ_cfg.os == abi::OsMacos {
+        for path in sess.framework_search_paths().iter() {
+            cmd.arg_path("-F", path);
+        }
     }
 
-    let rustpath = filesearch::rust_path();
-    for path in rustpath.iter() {
-        // FIXME (#9639): This needs to handle non-utf8 paths
-        args.push("-L" + path.as_str().unwrap().to_owned());
+    for path in filesearch::rust_path_lib_paths(sess.opts.target_triple).iter() {
+        cmd.arg_path("-L", path);
     }
 
     for &(ref l, kind) in cstore::get_used_libraries(sess.cstore).iter() {
         match kind {
-            cstore::NativeUnknown | cstore::NativeStatic => {
-                args.push("-l" + *l);
+            cstore::NativeUnknown | cstore::NativeStatic |
+            cstore::NativeStaticNobundle => {
+                libs.push(~[lib_arg(*l)]);
+            }
+            cstore::NativeStaticWholeArchive => {
+                libs.push(whole_archive_args(sess, *l));
             }
             cstore::NativeFramework => {
-                args.push(~"-framework");
-                args.push(l.to_owned());
+                libs.push(framework_args(*l, false));
             }
+            cstore::NativeFrameworkWeak => {
+                libs.push(framework_args(*l, true));
+            }
+        }
+    }
+}
+
+// The argument which links the native library `name`
+fn lib_arg(name: &str) -> ~[u8] {
+    ("-l" + name).into_bytes()
+}
+
+// A weak framework is linked with `-weak_framework`, so that the program still
+// loads where it's missing (its symbols are then null)
+fn framework_args(name: &str, weak: bool) -> ~[~[u8]] {
+    let flag = if weak { "-weak_framework" } else { "-framework" };
+    ~[flag.as_bytes().to_owned(), name.as_bytes().to_owned()]
+}
+
+// Removes all but the last occurrence of each library. Single pass linkers
+// only resolve symbols against libraries that come after the reference, so
+// the last use is the one which has to stay.
+fn dedup_libraries<T: Eq + Clone>(libs: ~[T]) -> ~[T] {
+    let mut ret = ~[];
+    for (i, lib) in libs.iter().enumerate() {
+        if !libs.slice_from(i + 1).contains(lib) {
+            ret.push(lib.clone());
         }
     }
+    ret
+}
+
+// With `-C check-native-libs`, every native library about to be handed to the
+// linker is looked for first. A misspelled library name otherwise only turns
+// up as an error from the linker, long after the crate was compiled.
+fn check_native_libraries(sess: Session, static_crates: &[ast::CrateNum]) {
+    let search = native_library_search_dirs(sess);
+    let mut missing = false;
+    for &(ref lib, kind) in cstore::get_used_libraries(sess.cstore).iter() {
+        if !native_library_exists(sess, search, *lib, kind) {
+            sess.err(format!("could not find native library `{}`", *lib));
+            missing = true;
+        }
+    }
+    cstore::iter_crate_data(sess.cstore, |cnum, data| {
+        let libs = csearch::get_native_libraries(sess.cstore, cnum);
+        for &(kind, ref lib) in libs.iter() {
+            // Bundled into the upstream crate, or only linked when the
+            // upstream crate is static
+            match kind {
+                cstore::NativeStatic | cstore::NativeStaticWholeArchive => {
+                    continue
+                }
+                cstore::NativeStaticNobundle
+                    if !static_crates.contains(&cnum) => continue,
+                _ => {}
+            }
+            if !native_library_exists(sess, search, *lib, kind) {
+                sess.err(format!("could not find native library `{}`, which \
+                                  crate `{}` links to", *lib, data.name));
+                missing = true;
+            }
+        }
+    });
+    if missing {
+        let dirs = search.map(|p| p.display().to_str());
+        sess.note(format!("searched for native libraries in: {}",
+                          dirs.connect(", ")));
+    }
+}
+
+fn native_library_exists(sess: Session, search: &[Path], name: &str,
+                         kind: cstore::NativeLibaryKind) -> bool {
+    let mut candidates = ~[format!("lib{}.a", name)];
+    if sess.targ_cfg.os == abi::OsWin32 {
+        candidates.push(format!("{}.lib", name));
+    }
+    match kind {
+        // The linker has its own notion of where frameworks live
+        cstore::NativeFramework | cstore::NativeFrameworkWeak => return true,
+        cstore::NativeUnknown => {
+            candidates.push_all(match sess.targ_cfg.os {
+                abi::OsWin32 => ~[format!("{}.dll", name),
+                                  format!("lib{}.dll", name),
+                                  format!("lib{}.dll.a", name)],
+                abi::OsMacos => ~[format!("lib{}.dylib", name)],
+                abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => {
+                    ~[format!("lib{}.so", name)]
+                }
+            });
+        }
+        cstore::NativeStatic | cstore::NativeStaticWholeArchive |
+        cstore::NativeStaticNobundle => {}
+    }
+    search.iter().any(|dir| {
+        candidates.iter().any(|file| dir.join(file.as_slice()).exists())
+    })
+}
+
+// The directories the linker will look for native libraries in: those given
+// with -L, the rust path, and the ones the C compiler searches by default.
+fn native_library_search_dirs(sess: Session) -> ~[Path] {
+    let mut dirs = sess.native_search_paths();
+    dirs.push_all(filesearch::rust_path_lib_paths(sess.opts.target_triple));
+    dirs.push(sess.filesearch.get_target_lib_path());
+
+    // The paths are those of this machine, whatever the target
+    let sep = if cfg!(windows) { ';' } else { ':' };
+    match os::getenv("LIBRARY_PATH") {
+        Some(paths) => {
+            for p in paths.split(sep).filter(|p| !p.is_empty()) {
+                dirs.push(Path::new(p));
+            }
+        }
+        None => {}
+    }
+
+    // GCC and clang both list their library directories as
+    // `libraries: =DIR:DIR`. They're searched here, so the C compiler is run
+    // on this machine, not through `-C link-wrapper`.
+    let mut cmd = cc_command(sess);
+    cmd.arg("-print-search-dirs");
+    let prog = cmd.output();
+    if prog.status.success() {
+        for line in prog.output.lines() {
+            if !line.starts_with("libraries: ") { continue }
+            let line = line.slice_from("libraries: ".len());
+            let line = if line.starts_with("=") { line.slice_from(1) } else { line };
+            for p in line.split(sep).filter(|p| !p.is_empty()) {
+                dirs.push(Path::new(p));
+            }
+        }
+    }
+    dirs
+}
+
+// Native libraries of different crates may well depend on one another, in
+// which case no single order satisfies a GNU-style linker. Grouping them has
+// the linker search them repeatedly until no new symbols are resolved.
+fn group_libraries(sess: Session, libs: ~[~[~[u8]]]) -> ~[~[u8]] {
+    let mut args = ~[];
+    // The OSX linker already searches all libraries for every symbol
+    let group = libs.len() > 1 && sess.targ_cfg.os != abi::OsMacos;
+    if group {
+        args.push(bytes!("-Wl,--start-group").to_owned());
+    }
+    for lib in libs.move_iter() {
+        args.push_all_move(lib);
+    }
+    if group {
+        args.push(bytes!("-Wl,--end-group").to_owned());
+    }
+    args
+}
+
+// Links all of the objects of the native static library `name`, rather than
+// just those resolving some undefined symbol. This is needed for libraries
+// relying on static constructors, which nothing else refers to.
+fn whole_archive_args(sess: Session, name: &str) -> ~[~[u8]] {
+    match sess.targ_cfg.os {
+        abi::OsMacos => {
+            whole_archive_path_args(sess, &archive::find_library(sess, name))
+        }
+        _ => ~[bytes!("-Wl,--whole-archive").to_owned(), lib_arg(name),
+               bytes!("-Wl,--no-whole-archive").to_owned()],
+    }
+}
+
+// Like `whole_archive_args`, for the static library at `path`
+fn whole_archive_path_args(sess: Session, path: &Path) -> ~[~[u8]] {
+    match sess.targ_cfg.os {
+        abi::OsMacos => ~[path_arg("-Wl,-force_load,", path)],
+        _ => ~[bytes!("-Wl,--whole-archive").to_owned(), path_arg("", path),
+               bytes!("-Wl,--no-whole-archive").to_owned()],
+    }
+}
+
+// Writes the native static library `lib`, which the rlib of crate `name`
+// bundles to be linked whole, out into `tmpdir` and returns its path
+fn unpack_whole_archive(sess: Session, rlib: &Path, name: &str, lib: &str,
+                        tmpdir: &Path) -> Path {
+    let member = archive::whole_archive_member(lib);
+    let data = Archive::open(sess, rlib.clone()).read(member);
+    let path = tmpdir.join(format!("{}-{}", name, member));
+    fs::File::create(&path).write(data);
+    path
 }
 
 // # Rust Crate linking
@@ -1077,11 +2940,14 @@ fn add_local_native_libraries(args: &mut ~[~str], sess: Session) {
 // Rust crates are not considered at all when creating an rlib output. All
 // dependencies will be linked when producing the final output (instead of
 // the intermediate rlib version)
-fn add_upstream_rust_crates(args: &mut ~[~str], sess: Session,
-                            dylib: bool, tmpdir: &Path) {
+//
+// Returns the upstream crates which were linked statically.
+fn add_upstream_rust_crates(args: &mut ~[~[u8]], sess: Session,
+                            output: session::OutputStyle, static_exe: bool,
+                            tmpdir: &Path) -> ~[ast::CrateNum] {
     // Converts a library file-stem into a cc -l argument
-    fn unlib(config: @session::config, stem: &str) -> ~str {
-        if stem.starts_with("lib") &&
+    fn unlib(config: @session::config, stem: &[u8]) -> ~[u8] {
+        if stem.starts_with(bytes!("lib")) &&
             config.os != abi::OsWin32 {
             stem.slice(3, stem.len()).to_owned()
         } else {
@@ -1089,93 +2955,435 @@ fn add_upstream_rust_crates(args: &mut ~[~str], sess: Session,
         }
     }
 
+    // Just need to tell the linker about where the library lives and what
+    // its name is
+    fn link_dylib(args: &mut ~[~[u8]], sess: Session, cratepath: &Path) {
+        let dir = cratepath.dirname();
+        if !dir.is_empty() { args.push(bytes!("-L") + dir); }
+        let libarg = unlib(sess.targ_cfg, cratepath.filestem().unwrap());
+        args.push(bytes!("-l") + libarg);
+    }
+
+    // When performing LTO on an executable output, all of the bytecode from
+    // the upstream libraries has already been included in our object file
+    // output. We need to modify all of the upstream archives to remove their
+    // corresponding object file to make sure we don't pull the same code in
+    // twice.
+    //
+    // We must continue to link to the upstream archives to be sure to pull in
+    // native static dependencies. As the final caveat, on linux it is
+    // apparently illegal to link to a blank archive, so if an archive no
+    // longer has any object files in it after we remove `lib.o`, then don't
+    // link against it at all.
+    fn link_lto_rlib(args: &mut ~[~[u8]], sess: Session, tmpdir: &Path,
+                     cnum: ast::CrateNum, cratepath: &Path) {
+        check_linked_crate(sess, cnum, cratepath);
+        let name = cstore::get_crate_data(sess.cstore, cnum).name;
+        time(sess.time_passes(), format!("altering {}.rlib", name), (), |()| {
+            let dst = tmpdir.join(cratepath.filename().unwrap());
+            fs::copy(cratepath, &dst);
+            let dst_arg = path_arg("", &dst);
+            let mut archive = Archive::open(sess, dst);
+            // A bitcode-only rlib has no object file to remove
+            if !csearch::get_bitcode_only(sess.cstore, cnum) {
+                archive.remove_file(format!("{}.o", name));
+            }
+            if archive.has_objects() {
+                args.push(dst_arg);
+            }
+        });
+    }
+
+    // Without LTO, the rlib is just linked against. A bitcode-only rlib has
+    // its machine code generated first, and is then only linked for the native
+    // libraries bundled into it, if any (see above).
+    fn link_upstream_rlib(args: &mut ~[~[u8]], sess: Session, tmpdir: &Path,
+                          cnum: ast::CrateNum, cratepath: &Path) {
+        check_linked_crate(sess, cnum, cratepath);
+        let name = cstore::get_crate_data(sess.cstore, cnum).name;
+        if !csearch::get_bitcode_only(sess.cstore, cnum) {
+            args.push(path_arg("", cratepath));
+            return
+        }
+        let obj = tmpdir.join(format!("{}.bitcode.o", name));
+        compile_bitcode_only(sess, name, cratepath, &obj);
+        args.push(path_arg("", &obj));
+        if Archive::open(sess, cratepath.clone()).has_objects() {
+            args.push(path_arg("", cratepath));
+        }
+    }
+
     let cstore = sess.cstore;
-    if !dylib && !sess.prefer_dynamic() {
-        // With an executable, things get a little interesting. As a limitation
-        // of the current implementation, we require that everything must be
-        // static, or everything must be dynamic. The reasons for this are a
-        // little subtle, but as with the above two cases, the goal is to
-        // prevent duplicate copies of the same library showing up. For example,
-        // a static immediate dependency might show up as an upstream dynamic
-        // dependency and we currently have no way of knowing that. We know that
-        // all dynamic libaries require dynamic dependencies (see above), so
-        // it's satisfactory to include either all static libraries or all
-        // dynamic libraries.
+    let cdylib = output == session::OutputCdylib;
+    let dylib = output == session::OutputDylib;
+
+    // A cdylib has to be usable without any rust libraries around, so all of
+    // its upstream crates are linked statically, like in an executable.
+    if cdylib || !dylib {
+        // With an executable, things get a little interesting. Upstream crates
+        // are linked statically unless asked otherwise (or impossible), but the
+        // goal is still to prevent duplicate copies of the same library showing
+        // up. A dylib links to all of its own dependencies dynamically (see
+        // above), so once any crate is linked dynamically, so are all of the
+        // crates it depends on. See `upstream_dylibs`.
         let crates = cstore::get_used_crates(cstore, cstore::RequireStatic);
-        if crates.iter().all(|&(_, ref p)| p.is_some()) {
+        let dylibs = if cdylib || static_exe {
+            HashSet::new()
+        } else {
+            upstream_dylibs(sess)
+        };
+        if dylibs.is_empty() && crates.iter().all(|&(_, ref p)| p.is_some()) {
+            let cnums = crates.map(|&(cnum, _)| cnum);
             for (cnum, path) in crates.move_iter() {
                 let cratepath = path.unwrap();
 
-                // When performing LTO on an executable output, all of the
-                // bytecode from the upstream libraries has already been
-                // included in our object file output. We need to modify all of
-                // the upstream archives to remove their corresponding object
-                // file to make sure we don't pull the same code in twice.
-                //
-                // We must continue to link to the upstream archives to be sure
-                // to pull in native static dependencies. As the final caveat,
-                // on linux it is apparently illegal to link to a blank archive,
-                // so if an archive no longer has any object files in it after
-                // we remove `lib.o`, then don't link against it at all.
-                //
-                // If we're not doing LTO, then our job is simply to just link
-                // against the archive.
                 if sess.lto() {
-                    let name = cstore::get_crate_data(sess.cstore, cnum).name;
-                    time(sess.time_passes(), format!("altering {}.rlib", name),
-                         (), |()| {
-                        let dst = tmpdir.join(cratepath.filename().unwrap());
-                        fs::copy(&cratepath, &dst);
-                        let dst_str = dst.as_str().unwrap().to_owned();
-                        let mut archive = Archive::open(sess, dst);
-                        archive.remove_file(format!("{}.o", name));
-                        let files = archive.files();
-                        if files.iter().any(|s| s.ends_with(".o")) {
-                            args.push(dst_str);
-                        }
-                    });
+                    link_lto_rlib(args, sess, tmpdir, cnum, &cratepath);
                 } else {
-                    args.push(cratepath.as_str().unwrap().to_owned());
+                    link_upstream_rlib(args, sess, tmpdir, cnum, &cratepath);
                 }
             }
-            return;
+            return cnums;
         }
+
+        if cdylib || static_exe {
+            let what = if cdylib { "a cdylib" } else { "a static executable" };
+            for &(cnum, ref path) in crates.iter() {
+                if path.is_none() {
+                    sess.err(format!("could not find rlib for: `{}`, which \
+                                      {} must link statically",
+                                     cstore::get_crate_data(cstore, cnum).name,
+                                     what));
+                }
+            }
+            return ~[];
+        }
+
+        // With LTO, crates are only linked dynamically under -Z lto-partial,
+        // in which case the others have been optimized into our object file.
+        assert!(!sess.lto() || sess.lto_partial());
+
+        // Link the rlibs first, the dylibs after them. GNU-style linkers drop
+        // a shared library when nothing before it on the command line refers
+        // to it (see `--as-needed` above).
+        let dynamic = cstore::get_used_crates(cstore, cstore::RequireDynamic);
+        let roots = dylibs.iter().map(|&cnum| cnum).to_owned_vec();
+        check_dylib_chains(sess, roots);
+        let mut statics = ~[];
+        for &(cnum, ref path) in crates.iter() {
+            if dylibs.contains(&cnum) { continue }
+            if sess.lto() {
+                link_lto_rlib(args, sess, tmpdir, cnum, path.get_ref());
+            } else {
+                link_upstream_rlib(args, sess, tmpdir, cnum, path.get_ref());
+            }
+            statics.push(cnum);
+        }
+        for &(cnum, ref path) in dynamic.iter() {
+            if !dylibs.contains(&cnum) { continue }
+            match *path {
+                Some(ref p) => {
+                    check_linked_crate(sess, cnum, p);
+                    link_dylib(args, sess, p);
+                }
+                None => {} // reported by `check_dylib_chains`
+            }
+        }
+        return statics;
     }
 
     // If we're performing LTO, then it should have been previously required
     // that all upstream rust depenencies were available in an rlib format.
     assert!(!sess.lto());
 
-    // This is a fallback of three different  cases of linking:
-    //
-    // * When creating a dynamic library, all inputs are required to be dynamic
-    //   as well
-    // * If an executable is created with a preference on dynamic linking, then
-    //   this case is the fallback
-    // * If an executable is being created, and one of the inputs is missing as
-    //   a static library, then this is the fallback case.
+    // When creating a dynamic library, all inputs are required to be dynamic
+    // as well
     let crates = cstore::get_used_crates(cstore, cstore::RequireDynamic);
+    check_dylib_chains(sess, crates.map(|&(cnum, _)| cnum));
     for &(cnum, ref path) in crates.iter() {
-        let cratepath = match *path {
-            Some(ref p) => p.clone(),
-            None => {
-                sess.err(format!("could not find dynamic library for: `{}`",
-                                 cstore::get_crate_data(sess.cstore, cnum).name));
+        match *path {
+            Some(ref p) => {
+                check_linked_crate(sess, cnum, p);
+                link_dylib(args, sess, p);
+            }
+            None => return ~[] // reported by `check_dylib_chains`
+        }
+    }
+    ~[]
+}
+
+// Verifies that the file about to be linked for an upstream crate is the build
+// of the crate which was loaded, and that it was built against the crates
+// linked along with it. The rlib and the dylib of a crate are found as a pair,
+// and a stale one would otherwise only show up as undefined symbols from the
+// linker.
+fn check_linked_crate(sess: Session, cnum: ast::CrateNum, path: &Path) {
+    fn paths(sess: Session, cnum: ast::CrateNum) -> ~str {
+        let src = cstore::get_used_crate_sources(sess.cstore).iter()
+                        .find(|src| src.cnum == cnum);
+        let mut paths = ~[];
+        for src in src.iter() {
+            for p in src.rlib.iter().chain(src.dylib.iter()) {
+                paths.push(p.display().to_str());
+            }
+        }
+        paths.connect(" and ")
+    }
+
+    let cstore = sess.cstore;
+    let os = session::sess_os_to_meta_os(sess.targ_cfg.os);
+    let metadata = match loader::read_file_metadata(sess, os, path) {
+        Ok(metadata) => metadata,
+        Err(e) => { sess.err(e); return }
+    };
+    let data = cstore::get_crate_data(cstore, cnum);
+    let expected = cstore::get_crate_hash(cstore, cnum);
+    let hash = decoder::get_crate_hash(metadata);
+    if hash != expected {
+        sess.err(format!("{} is another build of crate `{}` than the one \
+                          which was loaded; recompile it",
+                         path.display(), data.name));
+        sess.note(format!("expected hash {}, found hash {}", expected, hash));
+        sess.note(format!("crate `{}` was found at {}", data.name,
+                          paths(sess, cnum)));
+    }
+    for dep in decoder::get_crate_deps(metadata).iter() {
+        let resolved = match data.cnum_map.find(&dep.cnum) {
+            Some(&resolved) => resolved,
+            None => continue,
+        };
+        let found = cstore::get_crate_hash(cstore, resolved);
+        if dep.hash != found {
+            let name = cstore::get_crate_data(cstore, resolved).name;
+            sess.err(format!("{} was built against a version of crate `{}` \
+                              other than the one being linked; recompile it",
+                             path.display(), name));
+            sess.note(format!("expected hash {}, found hash {} at {}",
+                              dep.hash, found, paths(sess, resolved)));
+        }
+    }
+}
+
+// Verifies that each of the given crates, which are about to be linked
+// dynamically, has a dylib, as do all of the crates they depend on (which the
+// dylibs in turn link to dynamically). When one can't be found, the error
+// names the chain of crates which led to it, as the linker wouldn't.
+fn check_dylib_chains(sess: Session, dynamic: &[ast::CrateNum]) {
+    fn name(sess: Session, cnum: ast::CrateNum) -> @str {
+        cstore::get_crate_data(sess.cstore, cnum).name
+    }
+
+    fn describe(sess: Session, chain: &[ast::CrateNum]) -> ~str {
+        chain.map(|&c| format!("`{}`", name(sess, c))).connect(" -> ")
+    }
+
+    fn walk(sess: Session, paths: &HashMap<ast::CrateNum, bool>,
+            cnum: ast::CrateNum, chain: &mut ~[ast::CrateNum],
+            done: &mut HashSet<ast::CrateNum>) {
+        match chain.iter().position(|&c| c == cnum) {
+            Some(i) => {
+                let mut cycle = chain.slice_from(i).to_owned();
+                cycle.push(cnum);
+                sess.err(format!("cyclic dependency between dylibs: {}",
+                                 describe(sess, cycle)));
                 return
             }
+            None => {}
+        }
+        if done.contains(&cnum) { return }
+
+        chain.push(cnum);
+        if !paths.find(&cnum).map_default(false, |&found| found) {
+            sess.err(format!("could not find dynamic library for: `{}`",
+                             name(sess, cnum)));
+            if chain.len() > 1 {
+                sess.note(format!("it is required by the dependency chain {}",
+                                  describe(sess, *chain)));
+            }
+        }
+        let data = cstore::get_crate_data(sess.cstore, cnum);
+        let mut deps = data.cnum_map.values().map(|&c| c).to_owned_vec();
+        sort::quick_sort(deps, |a, b| *a <= *b);
+        for &dep in deps.iter() {
+            walk(sess, paths, dep, chain, done);
+        }
+        chain.pop();
+        done.insert(cnum);
+    }
+
+    let crates = cstore::get_used_crates(sess.cstore, cstore::RequireDynamic);
+    let mut paths = HashMap::new();
+    for &(cnum, ref path) in crates.iter() {
+        paths.insert(cnum, path.is_some());
+    }
+
+    // Start from the crates which none of the others depend on, so that the
+    // chains are as long as possible. Crates in a cycle have no such root, so
+    // the remaining ones are walked afterwards.
+    let mut depended = HashSet::new();
+    for &cnum in dynamic.iter() {
+        let data = cstore::get_crate_data(sess.cstore, cnum);
+        for (_, &dep) in data.cnum_map.iter() {
+            depended.insert(dep);
+        }
+    }
+    let mut done = HashSet::new();
+    let mut roots = ~[];
+    for &cnum in dynamic.iter() {
+        if !depended.contains(&cnum) { roots.push(cnum) }
+    }
+    for &cnum in dynamic.iter() {
+        if depended.contains(&cnum) { roots.push(cnum) }
+    }
+    for &cnum in roots.iter() {
+        walk(sess, &paths, cnum, &mut ~[], &mut done);
+    }
+}
+
+// Determines which upstream crates of an executable are linked dynamically:
+// those for which there's no rlib, those preferred dynamic which have a dylib,
+// and everything they depend on. A crate linked both statically and through
+// one of its dependents' dylibs would otherwise have two copies in the
+// process, each with its own statics.
+//
+// Crates named with `-C prefer-dynamic` or `-C prefer-static` override the
+// global preference of `-Z prefer-dynamic`. LTO needs every crate as an rlib,
+// so it ignores the preferences.
+fn upstream_dylibs(sess: Session) -> HashSet<ast::CrateNum> {
+    let mut dylibs = HashSet::new();
+    for (&cnum, _) in upstream_dylib_reasons(sess).iter() {
+        dylibs.insert(cnum);
+    }
+    dylibs
+}
+
+/// The upstream crates which `output` links dynamically: all of them for a
+/// dylib, those chosen by `upstream_dylibs` for an executable which isn't
+/// static, and none for the other outputs.
+pub fn output_dynamic_crates(sess: Session, output: session::OutputStyle)
+                             -> HashSet<ast::CrateNum> {
+    match output {
+        session::OutputDylib => {
+            let mut crates = HashSet::new();
+            for &(cnum, _) in cstore::get_used_crates(sess.cstore,
+                                                      cstore::RequireDynamic).iter() {
+                crates.insert(cnum);
+            }
+            crates
+        }
+        session::OutputExecutable if !static_executable(sess, output) => {
+            upstream_dylibs(sess)
+        }
+        _ => HashSet::new(),
+    }
+}
+
+/// The upstream crates which are linked dynamically into the outputs of this
+/// session. Translation needs to know them ahead of linking, as their statics
+/// are imported differently on Windows, and the outputs all share the one
+/// translation. Outputs which would link the crates differently, like a dylib
+/// and an executable linking rlibs, can't be built at once then.
+pub fn dynamic_crates(sess: Session) -> HashSet<ast::CrateNum> {
+    let outputs = (*sess.outputs).clone();
+    let mut linking = outputs.move_iter().filter(|&o| o != session::OutputRlib);
+    let first = match linking.next() {
+        Some(output) => output,
+        None => return HashSet::new(),
+    };
+    let crates = output_dynamic_crates(sess, first);
+    for output in linking {
+        if output_dynamic_crates(sess, output) != crates {
+            sess.fatal(format!("the `{}` and `{}` outputs link their upstream \
+                                crates differently, so they can't be built at \
+                                once for Windows targets",
+                               session::output_style_name(first),
+                               session::output_style_name(output)));
+        }
+    }
+    crates
+}
+
+/// Why an upstream crate of an executable is linked dynamically
+pub enum DylibReason {
+    NoRlib,
+    PreferDynamic,
+    // A crate which is linked dynamically depends on it
+    DependencyOf(ast::CrateNum),
+}
+
+/// The upstream crates which `upstream_dylibs` links dynamically, with the
+/// reason for each of them. Under `-Z lto`, crates are only preferred dynamic
+/// along with `-Z lto-partial`.
+pub fn upstream_dylib_reasons(sess: Session)
+                              -> HashMap<ast::CrateNum, DylibReason> {
+    let cstore = sess.cstore;
+    let statics = cstore::get_used_crates(cstore, cstore::RequireStatic);
+    let dynamics = cstore::get_used_crates(cstore, cstore::RequireDynamic);
+    let mut stack = ~[];
+    for (&(cnum, ref rlib), &(_, ref dylib)) in statics.iter().zip(dynamics.iter()) {
+        let name = cstore::get_crate_data(cstore, cnum).name;
+        let named = |names: &[~str]| names.iter().any(|n| n.as_slice() == name);
+        let prefer_dynamic = if named(sess.opts.cg.prefer_dynamic) {
+            true
+        } else if named(sess.opts.cg.prefer_static) {
+            false
+        } else {
+            sess.prefer_dynamic()
         };
-        // Just need to tell the linker about where the library lives and what
-        // its name is
-        let dir = cratepath.dirname_str().unwrap();
-        if !dir.is_empty() { args.push("-L" + dir); }
-        let libarg = unlib(sess.targ_cfg, cratepath.filestem_str().unwrap());
-        args.push("-l" + libarg);
+        if rlib.is_none() {
+            stack.push((cnum, NoRlib));
+        } else if prefer_dynamic && dylib.is_some() &&
+                  (!sess.lto() || sess.lto_partial()) {
+            stack.push((cnum, PreferDynamic));
+        }
+    }
+
+    let mut dylibs = HashMap::new();
+    while !stack.is_empty() {
+        let (cnum, reason) = stack.pop();
+        if dylibs.contains_key(&cnum) { continue }
+        dylibs.insert(cnum, reason);
+        let data = cstore::get_crate_data(cstore, cnum);
+        for (_, &dep) in data.cnum_map.iter() {
+            stack.push((dep, DependencyOf(cnum)));
+        }
+    }
+    dylibs
+}
+
+// The runtime libraries to link an output with, either those given with
+// `-C runtime-libraries` or those of the target. libmorestack has no use when
+// no function linked in has a split-stack prologue, which upstream crates
+// linked statically (`crates`) may well have.
+fn runtime_libraries(sess: Session, crates: &[ast::CrateNum]) -> ~[~str] {
+    match sess.opts.cg.runtime_libraries {
+        Some(ref libs) => libs.clone(),
+        None => {
+            let morestack = needs_morestack(sess, crates);
+            sess.targ_cfg.target_strs.runtime_libraries.iter().filter(|lib| {
+                morestack || "morestack" != lib.as_slice()
+            }).map(|lib| lib.clone()).collect()
+        }
     }
 }
 
+// Whether the local crate or any of `crates`, the upstream crates linked into
+// the output, has functions with split-stack prologues calling __morestack
+fn needs_morestack(sess: Session, crates: &[ast::CrateNum]) -> bool {
+    !*sess.no_split_stack ||
+        crates.iter().any(|&cnum| csearch::get_split_stack(sess.cstore, cnum))
+}
+
+// The libraries which provide unwinding, the C++ runtime the runtime's
+// unwinding is built on included
+static UNWINDER_LIBRARIES: &'static [&'static str] = &[
+    "gcc_s", "gcc_eh", "unwind", "stdc++", "supc++", "gnustl_shared",
+];
+
 // Link in all of our upstream crates' native dependencies. Remember that
-// all of these upstream native depenencies are all non-static
-// dependencies. We've got two cases then:
+// upstream static dependencies have already been bundled into the upstream
+// crate itself, so only the non-static dependencies are linked here. We've
+// got two cases then:
 //
 // 1. The upstream crate is an rlib. In this case we *must* link in the
 //    native dependency because the rlib is just an archive.
@@ -1191,21 +3399,229 @@ fn add_upstream_rust_crates(args: &mut ~[~str], sess: Session,
 // generic function calls a native function, then the generic function must
 // be instantiated in the target crate, meaning that the native symbol must
 // also be resolved in the target crate.
-fn add_upstream_native_libraries(args: &mut ~[~str], sess: Session) {
+//
+// Static libraries with the `static-nobundle` kind aren't bundled into rlibs,
+// and those which must be linked whole are bundled as archives of their own
+// the linker doesn't look into, so they're linked here for the upstream crates
+// which are linked statically. A dylib already contains them.
+//
+// Code compiled with `-Z abort-on-failure` doesn't unwind, but upstream
+// crates weren't necessarily compiled that way. The unwinder libraries are
+// still linked for the crates which are linked statically, std's runtime
+// included, as their landing pads need them; they're only left out for the
+// dylibs, which load them themselves.
+fn add_upstream_native_libraries(libs: &mut ~[~[~[u8]]], sess: Session,
+                                 static_crates: &[ast::CrateNum],
+                                 tmpdir: &Path) {
     let cstore = sess.cstore;
-    cstore::iter_crate_data(cstore, |cnum, _| {
-        let libs = csearch::get_native_libraries(cstore, cnum);
-        for &(kind, ref lib) in libs.iter() {
+    let rlibs = cstore::get_used_crates(cstore, cstore::RequireStatic);
+    cstore::iter_crate_data(cstore, |cnum, data| {
+        let native_libs = csearch::get_native_libraries(cstore, cnum);
+        for &(kind, ref lib) in native_libs.iter() {
+            if sess.abort_on_failure() && !static_crates.contains(&cnum) &&
+               UNWINDER_LIBRARIES.contains(&lib.as_slice()) {
+                continue
+            }
             match kind {
-                cstore::NativeUnknown => args.push("-l" + *lib),
+                cstore::NativeUnknown => libs.push(~[lib_arg(*lib)]),
                 cstore::NativeFramework => {
-                    args.push(~"-framework");
-                    args.push(lib.to_owned());
+                    libs.push(framework_args(*lib, false));
                 }
-                cstore::NativeStatic => {
-                    sess.bug("statics shouldn't be propagated");
+                cstore::NativeFrameworkWeak => {
+                    libs.push(framework_args(*lib, true));
                 }
+                cstore::NativeStaticWholeArchive => {
+                    if static_crates.contains(&cnum) {
+                        let rlib = rlibs.iter().find(|&&(c, _)| c == cnum)
+                                        .and_then(|&(_, ref p)| p.clone());
+                        match rlib {
+                            Some(rlib) => {
+                                let path = unpack_whole_archive(sess, &rlib,
+                                                                data.name, *lib,
+                                                                tmpdir);
+                                libs.push(whole_archive_path_args(sess, &path));
+                            }
+                            None => {
+                                sess.err(format!("could not find rlib for: \
+                                                  `{}`, which bundles the \
+                                                  native library `{}`",
+                                                 data.name, *lib));
+                            }
+                        }
+                    }
+                }
+                cstore::NativeStaticNobundle => {
+                    if static_crates.contains(&cnum) {
+                        libs.push(~[lib_arg(*lib)]);
+                    }
+                }
+                cstore::NativeStatic => {}
             }
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use back::link::{demangle, demangle_symbols, hash_component};
+    use back::link::{version_script, dedup_libraries, filter_functions};
+    use back::link::{def_file, is_file_locking_error, garbled_on_windows};
+    use back::link::{quote_windows_arg, quote_posix_arg, quote_response_file_arg};
+    use back::link::{split_command_line, split_command_line_for};
+    use metadata::common::LinkMeta;
+
+    #[test]
+    fn test_demangle() {
+        assert_eq!(demangle("_ZN3foo3barE"), Some(~"foo::bar"));
+        assert_eq!(demangle("_ZN3foo3bar17h0123456789abcdefE"),
+                   Some(~"foo::bar"));
+        assert_eq!(demangle("_ZN3foo3bar18h0123456789abcdef0E"),
+                   Some(~"foo::bar::h0123456789abcdef0"));
+        assert_eq!(demangle("_ZN3foo3bar"), None);
+        assert_eq!(demangle("_ZN3foo3barE1"), None);
+        assert_eq!(demangle("foo"), None);
+    }
+
+    #[test]
+    fn test_hash_component() {
+        let h = hash_component("h1234-0.9-pre");
+        assert_eq!(h.len(), 17);
+        assert!(h.starts_with("h"));
+        assert!(h != hash_component("h1234-0.9"));
+    }
+
+    #[test]
+    fn test_version_script() {
+        let lm = LinkMeta {
+            pkgid: from_str("foo-bar#0.9").unwrap(),
+            crate_hash: @"",
+            interface_hash: @"",
+        };
+        assert_eq!(version_script(&lm, Some([~"a", ~"b"])),
+                   ~"FOO_BAR_0.9 {\n  global:\n    a;\n    b;\n  \
+                     local:\n    *;\n};\n");
+        assert_eq!(version_script(&lm, None),
+                   ~"FOO_BAR_0.9 {\n  global:\n    *;\n  local:\n    *;\n};\n");
+    }
+
+    #[test]
+    fn test_quote_windows_arg() {
+        assert_eq!(quote_windows_arg("foo.o"), ~"foo.o");
+        assert_eq!(quote_windows_arg(""), ~"\"\"");
+        assert_eq!(quote_windows_arg("C:\\Program Files\\a.o"),
+                   ~"\"C:\\Program Files\\a.o\"");
+        assert_eq!(quote_windows_arg("a b\\"), ~"\"a b\\\\\"");
+        assert_eq!(quote_windows_arg("a\\\"b"), ~"\"a\\\\\\\"b\"");
+    }
+
+    #[test]
+    fn test_quote_posix_arg() {
+        assert_eq!(quote_posix_arg("-Wl,--as-needed"), ~"-Wl,--as-needed");
+        assert_eq!(quote_posix_arg("a b"), ~"'a b'");
+        assert_eq!(quote_posix_arg("it's"), ~"'it'\\''s'");
+    }
+
+    #[test]
+    fn test_quote_response_file_arg() {
+        assert_eq!(quote_response_file_arg(bytes!("a b")), bytes!("\"a b\"").to_owned());
+        assert_eq!(quote_response_file_arg(bytes!("C:\\a\"b")),
+                   bytes!("\"C:\\\\a\\\"b\"").to_owned());
+        assert_eq!(quote_response_file_arg([0xff, 0x20]), ~[0x22, 0xff, 0x20, 0x22]);
+    }
+
+    #[test]
+    fn test_garbled_on_windows() {
+        assert!(garbled_on_windows([]));
+        assert!(garbled_on_windows(bytes!("C:\\Program Files\\")));
+        assert!(!garbled_on_windows(bytes!("C:\\out\\")));
+        assert!(!garbled_on_windows(bytes!("C:\\Program Files\\foo.o")));
+    }
+
+    #[test]
+    fn test_is_file_locking_error() {
+        assert!(is_file_locking_error("The process cannot access the file \
+                                       because it is being used by another \
+                                       process."));
+        assert!(is_file_locking_error("The process cannot access the file \
+                                       because another process has locked a \
+                                       portion of the file."));
+        assert!(is_file_locking_error("ld: cannot open output file foo.exe: \
+                                       Permission denied"));
+        assert!(!is_file_locking_error("ld: cannot find -lfoo\n\
+                                        ld: foo.o: Permission denied"));
+        assert!(!is_file_locking_error("undefined reference to `foo'"));
+    }
+
+    #[test]
+    fn test_def_file() {
+        assert_eq!(def_file(&Path::new("out/foo-1.0.dll"), [~"a", ~"b"], []),
+                   ~"LIBRARY foo-1.0.dll\nEXPORTS\n    a\n    b\n");
+        assert_eq!(def_file(&Path::new("foo.dll"), [~"a", ~"b"], [~"b"]),
+                   ~"LIBRARY foo.dll\nEXPORTS\n    a\n    b DATA\n");
+    }
+
+    #[test]
+    fn test_demangle_escapes() {
+        assert_eq!(demangle("_ZN13$UP$$LT$T$GT$4dropE"),
+                   Some(~"~<T>::drop"));
+        assert_eq!(demangle("_ZN5_$x413fooE"), Some(~"A::foo"));
+        assert_eq!(demangle("_ZN9a$C$b$BP$E"), Some(~"a,b&"));
+    }
+
+    #[test]
+    fn test_demangle_symbols() {
+        let text = "undefined reference to `_ZN3foo3barE' in _ZN3baz";
+        assert_eq!(demangle_symbols(text),
+                   ~"undefined reference to `foo::bar' in _ZN3baz");
+    }
+
+    #[test]
+    fn test_filter_functions() {
+        let ir = "; ModuleID = 'foo.rc'\n\
+                  define void @_ZN3foo17h0123456789abcdef4v0_0E() {\n\
+                  \x20 ret void\n\
+                  }\n\n\
+                  define void @_ZN3bar17h0123456789abcdef4v0_0E() {\n\
+                  \x20 ret void\n\
+                  }\n";
+        assert_eq!(filter_functions(ir, "bar", true),
+                   ~"define void @_ZN3bar17h0123456789abcdef4v0_0E() {\n  \
+                     ret void\n}\n\n");
+
+        let asm = "\t.text\n\
+                   _ZN3foo4mainE:\n\
+                   .LBB0_1:\n\
+                   \tretq\n\
+                   _ZN3bar4mainE:\n\
+                   \tretq\n";
+        assert_eq!(filter_functions(asm, "foo::main", false),
+                   ~"_ZN3foo4mainE:\n.LBB0_1:\n\tretq\n");
+    }
+
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(split_command_line("zig cc"), Ok(~[~"zig", ~"cc"]));
+        assert_eq!(split_command_line("  cc\t-m32 "), Ok(~[~"cc", ~"-m32"]));
+        assert_eq!(split_command_line("'my cc' \"-I a b\" ''"),
+                   Ok(~[~"my cc", ~"-I a b", ~""]));
+        assert_eq!(split_command_line("a'b'\"c\""), Ok(~[~"abc"]));
+        assert!(split_command_line("'cc").is_err());
+        assert!(split_command_line("\"cc").is_err());
+        assert_eq!(split_command_line(""), Ok(~[]));
+
+        // Backslashes escape outside of Windows only
+        assert_eq!(split_command_line_for("a\\ b \"c\\\"d\"", false),
+                   Ok(~[~"a b", ~"c\"d"]));
+        assert!(split_command_line_for("cc\\", false).is_err());
+        assert_eq!(split_command_line_for("C:\\mingw\\gcc.exe \"C:\\a b\\\"", true),
+                   Ok(~[~"C:\\mingw\\gcc.exe", ~"C:\\a b\\"]));
+        assert_eq!(split_command_line_for("cc\\", true), Ok(~[~"cc\\"]));
+    }
+
+    #[test]
+    fn test_dedup_libraries() {
+        let libs = ~[~[~"-la"], ~[~"-lb"], ~[~"-framework", ~"c"], ~[~"-la"]];
+        assert_eq!(dedup_libraries(libs),
+                   ~[~[~"-lb"], ~[~"-framework", ~"c"], ~[~"-la"]]);
+    }
+}
@@ -8,15 +8,198 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use back::archive::Archive;
+use back::archive::{Archive, Member};
 use back::link;
 use driver::session;
-use lib::llvm::{ModuleRef, TargetMachineRef, llvm, True, False};
-use metadata::cstore;
+use lib::llvm::{ModuleRef, TargetMachineRef, ValueRef, llvm, True, False};
+use lib;
+use metadata::{csearch, cstore};
 use util::common::time;
 
+use std::hashmap::{HashMap, HashSet};
+use std::io::fs;
 use std::libc;
+use std::str;
 use std::vec;
+use extra::sort;
+use syntax::ast;
+
+/// A member of an rlib holding the bitcode of one of its codegen units,
+/// along with the symbols the unit defines
+pub struct BytecodeUnit {
+    member: ~str,
+    symbols: ~[~str],
+}
+
+/// The name of the rlib member which lists the bitcode units of the crate
+/// `name`
+pub fn bytecode_index_name(name: &str) -> ~str {
+    format!("{}.bc.index", name)
+}
+
+/// Parses an index of bitcode units, which has a line for each unit with the
+/// name of its member followed by the symbols it defines
+pub fn read_bytecode_index(index: &str) -> ~[BytecodeUnit] {
+    index.lines().filter_map(|line| {
+        let mut words = line.words();
+        words.next().map(|member| {
+            BytecodeUnit {
+                member: member.to_owned(),
+                symbols: words.map(|s| s.to_owned()).collect(),
+            }
+        })
+    }).collect()
+}
+
+/// Writes the bitcode of the crate `name` into `dir`, for it to be put into an
+/// rlib: a file for each codegen unit, and the index of them. There's only
+/// ever the one unit for now, the whole module.
+pub fn write_bytecode(llmod: ModuleRef, name: &str, dir: &Path) {
+    let member = format!("{}.0.bc", name);
+    dir.join(member.as_slice()).with_c_str(|buf| unsafe {
+        llvm::LLVMWriteBitcodeToFile(llmod, buf);
+    });
+
+    let mut index = member;
+    for symbol in module_symbols(llmod, false).iter() {
+        index.push_char(' ');
+        index.push_str(*symbol);
+    }
+    index.push_char('\n');
+    let path = dir.join(bytecode_index_name(name));
+    fs::File::create(&path).write(index.as_bytes());
+}
+
+// The names of the functions and globals that a module declares, or of those
+// it defines for other modules to use
+fn module_symbols(llmod: ModuleRef, declarations: bool) -> ~[~str] {
+    fn add(v: ValueRef, declarations: bool, symbols: &mut ~[~str]) {
+        unsafe {
+            let declared = llvm::LLVMIsDeclaration(v) == True;
+            let linkage = llvm::LLVMGetLinkage(v);
+            let local = linkage == lib::llvm::InternalLinkage as libc::c_uint ||
+                        linkage == lib::llvm::PrivateLinkage as libc::c_uint;
+            if declared == declarations && !local {
+                symbols.push(str::raw::from_c_str(llvm::LLVMGetValueName(v)));
+            }
+        }
+    }
+
+    let mut symbols = ~[];
+    unsafe {
+        let mut f = llvm::LLVMGetFirstFunction(llmod);
+        while f.is_not_null() {
+            add(f, declarations, &mut symbols);
+            f = llvm::LLVMGetNextFunction(f);
+        }
+        let mut g = llvm::LLVMGetFirstGlobal(llmod);
+        while g.is_not_null() {
+            add(g, declarations, &mut symbols);
+            g = llvm::LLVMGetNextGlobal(g);
+        }
+    }
+    symbols
+}
+
+// Reads the file of `-Z lto-keep-symbols`, which has a symbol on each line.
+// Blank lines and lines starting with `#` are skipped.
+fn read_keep_symbols(sess: session::Session, path: &Path) -> ~[~str] {
+    if !path.exists() {
+        sess.fatal(format!("the file of symbols to keep, `{}`, doesn't exist",
+                           path.display()));
+    }
+    let contents = fs::File::open(path).read_to_end();
+    let contents = match str::from_utf8_opt(contents) {
+        Some(s) => s,
+        None => sess.fatal(format!("`{}` isn't valid UTF-8", path.display())),
+    };
+    contents.lines().map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with("#"))
+            .map(|l| l.to_owned())
+            .collect()
+}
+
+/// Reads the bitcode of each codegen unit in the rlib of the crate `name`,
+/// reading the rlib just once
+pub fn read_bytecode(sess: session::Session, rlib: &Path,
+                     name: &str) -> ~[(BytecodeUnit, ~[u8])] {
+    debug!("reading {}", rlib.display());
+    let mut members = time(sess.time_passes(), format!("read {}", name), (), |_|
+                           Archive::open(sess, rlib.clone()).into_members());
+    let index = take_member(sess, rlib, &mut members, bytecode_index_name(name));
+    let mut bytecode = ~[];
+    for unit in read_bytecode_index(str::from_utf8(index)).move_iter() {
+        let bc = take_member(sess, rlib, &mut members, unit.member);
+        bytecode.push((unit, bc));
+    }
+    bytecode
+}
+
+// Takes the data of the member `name` out of the members of `rlib`
+fn take_member(sess: session::Session, rlib: &Path, members: &mut ~[Member],
+               name: &str) -> ~[u8] {
+    match members.iter().position(|m| m.name.as_slice() == name) {
+        Some(i) => members.swap_remove(i).data,
+        None => {
+            sess.fatal(format!("archive {} has no member `{}`",
+                               rlib.display(), name));
+        }
+    }
+}
+
+pub fn link_unit(sess: session::Session, llmod: ModuleRef, unit: &BytecodeUnit,
+                 bc: &[u8]) {
+    let name = unit.member.as_slice();
+    let ptr = vec::raw::to_ptr(bc);
+    debug!("linking {}", name);
+    time(sess.time_passes(), format!("ll link {}", name), (), |()| unsafe {
+        if !llvm::LLVMRustLinkInExternalBitcode(llmod,
+                                                ptr as *libc::c_char,
+                                                bc.len() as libc::size_t) {
+            link::llvm_err(sess, format!("failed to load bc of `{}`", name));
+        }
+    });
+}
+
+// Lists the upstream crates which are left out of LTO because they're linked
+// dynamically, along with the reason for each of them. This is an error
+// unless -Z lto-partial was given.
+fn report_excluded_crates(sess: session::Session,
+                          dylibs: &HashMap<ast::CrateNum, link::DylibReason>) {
+    fn name(sess: session::Session, cnum: ast::CrateNum) -> @str {
+        cstore::get_crate_data(sess.cstore, cnum).name
+    }
+
+    let mut excluded = dylibs.iter().map(|(&cnum, reason)| {
+        let why = match *reason {
+            link::NoRlib => ~"no rlib was found for it",
+            link::PreferDynamic => ~"it is preferred dynamic",
+            link::DependencyOf(parent) => {
+                format!("it is a dependency of `{}`, which is linked \
+                         dynamically", name(sess, parent))
+            }
+        };
+        (name(sess, cnum), why)
+    }).to_owned_vec();
+    sort::quick_sort(excluded, |&(ref a, _), &(ref b, _)| *a <= *b);
+
+    if sess.lto_partial() {
+        sess.warn(format!("lto is skipping {} upstream crate(s) which are \
+                           linked dynamically", excluded.len()));
+    } else {
+        sess.err(format!("lto requires all upstream crates to be linked \
+                          statically, but {} of them are linked dynamically",
+                         excluded.len()));
+    }
+    for &(ref name, ref why) in excluded.iter() {
+        sess.note(format!("excluded `{}`: {}", *name, *why));
+    }
+    if !sess.lto_partial() {
+        sess.note("pass -Z lto-partial to optimize the other crates and \
+                   link these dynamically");
+        sess.abort_if_errors();
+    }
+}
 
 pub fn run(sess: session::Session, llmod: ModuleRef,
            tm: TargetMachineRef, reachable: &[~str]) {
@@ -31,11 +214,41 @@ pub fn run(sess: session::Session, llmod: ModuleRef,
         }
     }
 
+    // The symbols which are kept are the reachable ones of the current
+    // module, along with any that the user asked for
+    let mut keep = reachable.to_owned();
+    match sess.opts.debugging_values.lto_keep_symbols {
+        Some(ref path) => keep.push_all_move(read_keep_symbols(sess, path)),
+        None => {}
+    }
+
+    // The crates which an executable links dynamically can't be optimized
+    // along with it, which is only allowed with -Z lto-partial
+    let exe_only = sess.outputs.iter().all(|o| *o == session::OutputExecutable);
+    let dylibs = if exe_only && !sess.opts.cg.static_executable {
+        link::upstream_dylib_reasons(sess)
+    } else {
+        HashMap::new()
+    };
+    if !dylibs.is_empty() {
+        report_excluded_crates(sess, &dylibs);
+    }
+
     // For each of our upstream dependencies, find the corresponding rlib and
-    // load the bitcode from the archive. Then merge it into the current LLVM
-    // module that we've got.
+    // the index of its bitcode units. The units which define a symbol that
+    // the current module refers to are merged into it, then the units which
+    // define a symbol that those refer to, and so on, so that only the code
+    // which is actually used gets optimized.
+    //
+    // The native static libraries bundled into an rlib can't be seen into
+    // though, and may call back into any of the crates, not just their own,
+    // through a symbol which isn't mangled. When there are any, all such
+    // symbols are kept, which merges in the units that define them too.
     let crates = cstore::get_used_crates(sess.cstore, cstore::RequireStatic);
+    let mut pending = ~[];
+    let mut bundles_native = false;
     for (cnum, path) in crates.move_iter() {
+        if dylibs.contains_key(&cnum) { continue }
         let name = cstore::get_crate_data(sess.cstore, cnum).name;
         let path = match path {
             Some(p) => p,
@@ -44,23 +257,40 @@ pub fn run(sess: session::Session, llmod: ModuleRef,
             }
         };
 
-        let archive = Archive::open(sess, path);
-        debug!("reading {}", name);
-        let bc = time(sess.time_passes(), format!("read {}.bc", name), (), |_|
-                      archive.read(format!("{}.bc", name)));
-        let ptr = vec::raw::to_ptr(bc);
-        debug!("linking {}", name);
-        time(sess.time_passes(), format!("ll link {}", name), (), |()| unsafe {
-            if !llvm::LLVMRustLinkInExternalBitcode(llmod,
-                                                    ptr as *libc::c_char,
-                                                    bc.len() as libc::size_t) {
-                link::llvm_err(sess, format!("failed to load bc of `{}`", name));
+        bundles_native = bundles_native ||
+            csearch::get_native_libraries(sess.cstore, cnum)
+                .iter().any(|&(kind, _)| kind == cstore::NativeStatic);
+        pending.push_all_move(read_bytecode(sess, &path, name));
+    }
+    if bundles_native {
+        for &(ref unit, _) in pending.iter() {
+            for symbol in unit.symbols.iter() {
+                if !symbol.starts_with("_ZN") {
+                    keep.push(symbol.clone());
+                }
             }
+        }
+    }
+    loop {
+        let mut undefined = HashSet::new();
+        for symbol in module_symbols(llmod, true).move_iter() {
+            undefined.insert(symbol);
+        }
+        for symbol in keep.iter() {
+            undefined.insert(symbol.clone());
+        }
+        let (wanted, rest) = pending.partition(|&(ref unit, _)| {
+            unit.symbols.iter().any(|s| undefined.contains(s))
         });
+        if wanted.is_empty() { break }
+        for &(ref unit, ref bc) in wanted.iter() {
+            link_unit(sess, llmod, unit, bc.as_slice());
+        }
+        pending = rest;
     }
 
-    // Internalize everything but the reachable symbols of the current module
-    let cstrs = reachable.map(|s| s.to_c_str());
+    // Internalize everything but the symbols to keep
+    let cstrs = keep.map(|s| s.to_c_str());
     let arr = cstrs.map(|c| c.with_ref(|p| p));
     let ptr = vec::raw::to_ptr(arr);
     unsafe {
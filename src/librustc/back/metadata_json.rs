@@ -0,0 +1,219 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A machine-readable description of a crate for external tooling (`--emit
+//! metadata-json`): its name, version and hashes, the items making up its
+//! public API, the crates it depends on and the native libraries it links.
+//!
+//! Unlike the metadata encoded into the crate itself, the format of this file
+//! is meant to be stable enough for other programs to read.
+//!
+//! The description can also be generated from the metadata of an existing
+//! library, which lacks the types of the items.
+
+use metadata::{cstore, decoder};
+use middle::privacy::ExportedItems;
+use middle::trans::context::CrateContext;
+use middle::ty;
+use util::ppaux;
+
+use std::hashmap::HashMap;
+use extra::json;
+use extra::sort;
+use extra::treemap::TreeMap;
+use syntax::ast;
+use syntax::ast_map;
+use syntax::ast_util;
+use syntax::attr;
+use syntax::parse::token::ident_interner;
+
+/// Generates the description of the crate being translated
+pub fn generate(ccx: &CrateContext, exported_items: &ExportedItems) -> ~str {
+    let mut crate = ~TreeMap::new();
+    crate.insert(~"name", json::String(ccx.link_meta.pkgid.name.clone()));
+    crate.insert(~"version",
+                 json::String(ccx.link_meta.pkgid.version_or_default().to_owned()));
+    crate.insert(~"hash", json::String(ccx.link_meta.crate_hash.to_owned()));
+    crate.insert(~"interface_hash",
+                 json::String(ccx.link_meta.interface_hash.to_owned()));
+    crate.insert(~"items", json::List(items(ccx, exported_items)));
+    crate.insert(~"dependencies", json::List(dependencies(ccx)));
+    crate.insert(~"native_libraries", json::List(native_libraries(ccx)));
+    json::Object(crate).to_pretty_str() + "\n"
+}
+
+fn items(ccx: &CrateContext, exported_items: &ExportedItems) -> ~[json::Json] {
+    let intr = ccx.sess.intr();
+    let mut items = ~[];
+    // The impls whose methods are exported, with their paths
+    let mut impls = HashMap::new();
+    for id in exported_items.iter() {
+        let (path, kind) = match ccx.tcx.items.find(id) {
+            Some(&ast_map::node_item(item, path)) => {
+                let kind = match item.node {
+                    ast::item_fn(..) => "fn",
+                    ast::item_static(..) => "static",
+                    ast::item_mod(..) => "mod",
+                    ast::item_ty(..) => "type",
+                    ast::item_enum(..) => "enum",
+                    ast::item_struct(..) => "struct",
+                    ast::item_trait(..) => "trait",
+                    // impls are listed along with their exported methods
+                    _ => continue,
+                };
+                (ast_map::path_ident_to_str(path, item.ident, intr), kind)
+            }
+            Some(&ast_map::node_foreign_item(item, _, _, path)) => {
+                let kind = match item.node {
+                    ast::foreign_item_fn(..) => "fn",
+                    ast::foreign_item_static(..) => "static",
+                };
+                (ast_map::path_ident_to_str(path, item.ident, intr), kind)
+            }
+            Some(&ast_map::node_method(method, impl_did, path)) => {
+                impls.insert(impl_did.node, ast_map::path_to_str(*path, intr));
+                (ast_map::path_ident_to_str(path, method.ident, intr), "method")
+            }
+            Some(&ast_map::node_trait_method(method, _, path)) => {
+                let ident = match *method {
+                    ast::required(ref m) => m.ident,
+                    ast::provided(m) => m.ident,
+                };
+                (ast_map::path_ident_to_str(path, ident, intr), "method")
+            }
+            _ => continue,
+        };
+        let mut item = ~TreeMap::new();
+        item.insert(~"path", json::String(path.clone()));
+        item.insert(~"kind", json::String(kind.to_owned()));
+        let t = match kind {
+            "fn" | "static" => Some(ty::node_id_to_type(ccx.tcx, *id)),
+            "method" => {
+                let method = ty::method(ccx.tcx, ast_util::local_def(*id));
+                Some(ty::mk_bare_fn(ccx.tcx, method.fty.clone()))
+            }
+            _ => None,
+        };
+        match t {
+            Some(t) => {
+                item.insert(~"type", json::String(ppaux::ty_to_str(ccx.tcx, t)));
+            }
+            None => {}
+        }
+        match ccx.item_symbols.find(id) {
+            Some(sym) => { item.insert(~"symbol", json::String(sym.clone())); }
+            None => {}
+        }
+        items.push((path, item));
+    }
+    for (&id, path) in impls.iter() {
+        let did = ast_util::local_def(id);
+        let mut item = ~TreeMap::new();
+        item.insert(~"path", json::String(path.clone()));
+        item.insert(~"kind", json::String(~"impl"));
+        let self_ty = ty::lookup_item_type(ccx.tcx, did).ty;
+        item.insert(~"type", json::String(ppaux::ty_to_str(ccx.tcx, self_ty)));
+        for trait_ref in ty::impl_trait_ref(ccx.tcx, did).iter() {
+            item.insert(~"trait",
+                        json::String(ty::item_path_str(ccx.tcx, trait_ref.def_id)));
+        }
+        items.push((path.clone(), item));
+    }
+    sort_items(items)
+}
+
+fn sort_items(mut items: ~[(~str, ~TreeMap<~str, json::Json>)]) -> ~[json::Json] {
+    sort::quick_sort(items, |&(ref a, _), &(ref b, _)| *a <= *b);
+    items.move_iter().map(|(_, item)| json::Object(item)).collect()
+}
+
+fn dependencies(ccx: &CrateContext) -> ~[json::Json] {
+    let cstore = ccx.sess.cstore;
+    let mut crates = ~[];
+    cstore::iter_crate_data(cstore, |cnum, data| crates.push((cnum, data.name)));
+    sort::quick_sort(crates, |&(a, _), &(b, _)| a <= b);
+    crates.move_iter().map(|(cnum, name)| {
+        let mut dep = ~TreeMap::new();
+        dep.insert(~"name", json::String(name.to_owned()));
+        dep.insert(~"version",
+                   json::String(cstore::get_crate_vers(cstore, cnum).to_owned()));
+        dep.insert(~"hash",
+                   json::String(cstore::get_crate_hash(cstore, cnum).to_owned()));
+        let data = cstore::get_crate_data(cstore, cnum).data;
+        match decoder::get_crate_interface_hash(data) {
+            Some(hash) => {
+                dep.insert(~"interface_hash", json::String(hash.to_owned()));
+            }
+            None => {}
+        }
+        json::Object(dep)
+    }).collect()
+}
+
+fn native_libraries(ccx: &CrateContext) -> ~[json::Json] {
+    let libs = cstore::get_used_libraries(ccx.sess.cstore);
+    libs.iter().map(|&(ref name, kind)| native_library(name.clone(), kind)).collect()
+}
+
+fn native_library(name: ~str, kind: cstore::NativeLibaryKind) -> json::Json {
+    let mut lib = ~TreeMap::new();
+    lib.insert(~"name", json::String(name));
+    lib.insert(~"kind", json::String(kind.name().to_owned()));
+    json::Object(lib)
+}
+
+/// Generates the description of a library from its metadata
+pub fn generate_from_metadata(intr: @ident_interner, data: @~[u8]) -> ~str {
+    let mut crate = ~TreeMap::new();
+    match attr::find_pkgid(decoder::get_crate_attributes(data)) {
+        Some(pkgid) => {
+            crate.insert(~"name", json::String(pkgid.name.clone()));
+            crate.insert(~"version",
+                         json::String(pkgid.version_or_default().to_owned()));
+        }
+        None => {}
+    }
+    crate.insert(~"hash",
+                 json::String(decoder::get_crate_hash(data).to_owned()));
+    match decoder::get_crate_interface_hash(data) {
+        Some(hash) => {
+            crate.insert(~"interface_hash", json::String(hash.to_owned()));
+        }
+        None => {}
+    }
+
+    let items = decoder::get_public_items(intr, data).move_iter().map(|it| {
+        let mut item = ~TreeMap::new();
+        item.insert(~"path", json::String(it.path.clone()));
+        item.insert(~"kind", json::String(it.kind.to_owned()));
+        for sym in it.symbol.move_iter() {
+            item.insert(~"symbol", json::String(sym));
+        }
+        (it.path, item)
+    }).collect();
+    crate.insert(~"items", json::List(sort_items(items)));
+
+    let deps = decoder::get_crate_deps(data).move_iter().map(|d| {
+        let mut dep = ~TreeMap::new();
+        dep.insert(~"name", json::String(intr.get(d.name.name).to_owned()));
+        dep.insert(~"version", json::String(d.vers.to_owned()));
+        dep.insert(~"hash", json::String(d.hash.to_owned()));
+        for hash in d.interface_hash.iter() {
+            dep.insert(~"interface_hash", json::String(hash.to_owned()));
+        }
+        json::Object(dep)
+    }).collect();
+    crate.insert(~"dependencies", json::List(deps));
+
+    let libs = decoder::get_crate_native_libraries(data);
+    let libs = libs.move_iter().map(|(kind, name)| native_library(name, kind));
+    crate.insert(~"native_libraries", json::List(libs.collect()));
+    json::Object(crate).to_pretty_str() + "\n"
+}
@@ -21,7 +21,9 @@ fn not_win32(os: abi::Os) -> bool {
   os != abi::OsWin32
 }
 
-pub fn get_rpath_flags(sess: session::Session, out_filename: &Path) -> ~[~str] {
+// The rpaths are kept as the bytes of the paths they're made of, which needn't
+// be UTF-8.
+pub fn get_rpath_flags(sess: session::Session, out_filename: &Path) -> ~[~[u8]] {
     let os = sess.targ_cfg.os;
 
     // No rpath on windows
@@ -32,9 +34,9 @@ pub fn get_rpath_flags(sess: session::Session, out_filename: &Path) -> ~[~str] {
     let mut flags = ~[];
 
     if sess.targ_cfg.os == abi::OsFreebsd {
-        flags.push_all([~"-Wl,-rpath,/usr/local/lib/gcc46",
-                        ~"-Wl,-rpath,/usr/local/lib/gcc44",
-                        ~"-Wl,-z,origin"]);
+        flags.push_all([bytes!("-Wl,-rpath,/usr/local/lib/gcc46").to_owned(),
+                        bytes!("-Wl,-rpath,/usr/local/lib/gcc44").to_owned(),
+                        bytes!("-Wl,-z,origin").to_owned()]);
     }
 
     debug!("preparing the RPATH!");
@@ -48,8 +50,22 @@ pub fn get_rpath_flags(sess: session::Session, out_filename: &Path) -> ~[~str] {
     let libs = vec::append_one(libs, get_sysroot_absolute_rt_lib(sess));
 
     let rpaths = get_rpaths(os, sysroot, output, libs,
-                            sess.opts.target_triple);
-    flags.push_all(rpaths_to_flags(rpaths));
+                            sess.opts.target_triple,
+                            sess.opts.cg.relative_rpath);
+
+    // The ELF loaders split the rpath at colons, and there's no escaping them
+    if os != abi::OsMacos {
+        for rpath in rpaths.iter() {
+            if rpath.contains(&(':' as u8)) {
+                sess.err(format!("rpath `{}` contains a colon, which would \
+                                  split it into several search paths",
+                                 Path::new(rpath.as_slice()).display()));
+            }
+        }
+        sess.abort_if_errors();
+    }
+
+    flags.push_all(rpaths_to_flags(os, rpaths));
     flags
 }
 
@@ -60,19 +76,32 @@ fn get_sysroot_absolute_rt_lib(sess: session::Session) -> Path {
     p
 }
 
-pub fn rpaths_to_flags(rpaths: &[~str]) -> ~[~str] {
-    let mut ret = ~[];
-    for rpath in rpaths.iter() {
-        ret.push("-Wl,-rpath," + *rpath);
+// All of the rpaths go into a single colon separated flag, which is why none
+// of them may contain a colon (see `get_rpath_flags`). The OSX linker takes
+// each rpath on its own and doesn't split them up.
+pub fn rpaths_to_flags(os: abi::Os, rpaths: &[~[u8]]) -> ~[~[u8]] {
+    if os == abi::OsMacos {
+        return rpaths.map(|rpath| bytes!("-Wl,-rpath,") + *rpath);
+    }
+    if rpaths.is_empty() {
+        return ~[];
     }
-    return ret;
+    let mut merged = bytes!("-Wl,-rpath,").to_owned();
+    for (i, rpath) in rpaths.iter().enumerate() {
+        if i > 0 {
+            merged.push(':' as u8);
+        }
+        merged.push_all(*rpath);
+    }
+    ~[merged]
 }
 
 fn get_rpaths(os: abi::Os,
               sysroot: &Path,
               output: &Path,
               libs: &[Path],
-              target_triple: &str) -> ~[~str] {
+              target_triple: &str,
+              relative_only: bool) -> ~[~[u8]] {
     debug!("sysroot: {}", sysroot.display());
     debug!("output: {}", output.display());
     debug!("libs:");
@@ -86,6 +115,13 @@ fn get_rpaths(os: abi::Os,
     // crates they depend on.
     let rel_rpaths = get_rpaths_relative_to_output(os, output, libs);
 
+    // With `-C relative-rpath`, the output is meant to be shipped along with
+    // its libraries, so the paths of the build machine are left out.
+    if relative_only {
+        let rel_rpaths = rel_rpaths.map(|rpath| canonicalize_rpath(*rpath));
+        return minimize_rpaths(rel_rpaths);
+    }
+
     // Make backup absolute paths to the libraries. Binaries can
     // be moved as long as the crates they link against don't move.
     let abs_rpaths = get_absolute_rpaths(libs);
@@ -93,10 +129,10 @@ fn get_rpaths(os: abi::Os,
     // And a final backup rpath to the global library location.
     let fallback_rpaths = ~[get_install_prefix_rpath(target_triple)];
 
-    fn log_rpaths(desc: &str, rpaths: &[~str]) {
+    fn log_rpaths(desc: &str, rpaths: &[~[u8]]) {
         debug!("{} rpaths:", desc);
         for rpath in rpaths.iter() {
-            debug!("    {}", *rpath);
+            debug!("    {}", Path::new(rpath.as_slice()).display());
         }
     }
 
@@ -109,20 +145,21 @@ fn get_rpaths(os: abi::Os,
     rpaths.push_all(fallback_rpaths);
 
     // Remove duplicates
+    let rpaths = rpaths.map(|rpath| canonicalize_rpath(*rpath));
     let rpaths = minimize_rpaths(rpaths);
     return rpaths;
 }
 
 fn get_rpaths_relative_to_output(os: abi::Os,
                                  output: &Path,
-                                 libs: &[Path]) -> ~[~str] {
+                                 libs: &[Path]) -> ~[~[u8]] {
     libs.iter().map(|a| get_rpath_relative_to_output(os, output, a)).collect()
 }
 
 pub fn get_rpath_relative_to_output(os: abi::Os,
                                     output: &Path,
                                     lib: &Path)
-                                 -> ~str {
+                                 -> ~[u8] {
     use std::os;
 
     assert!(not_win32(os));
@@ -141,33 +178,65 @@ pub fn get_rpath_relative_to_output(os: abi::Os,
     output.pop();
     let relative = lib.path_relative_from(&output);
     let relative = relative.expect("could not create rpath relative to output");
-    // FIXME (#9639): This needs to handle non-utf8 paths
-    prefix+"/"+relative.as_str().expect("non-utf8 component in path")
+    let mut rpath = prefix.as_bytes().to_owned();
+    rpath.push('/' as u8);
+    rpath.push_all(relative.as_vec());
+    rpath
 }
 
-fn get_absolute_rpaths(libs: &[Path]) -> ~[~str] {
+fn get_absolute_rpaths(libs: &[Path]) -> ~[~[u8]] {
     libs.iter().map(|a| get_absolute_rpath(a)).collect()
 }
 
-pub fn get_absolute_rpath(lib: &Path) -> ~str {
+pub fn get_absolute_rpath(lib: &Path) -> ~[u8] {
     let mut p = os::make_absolute(lib);
     p.pop();
-    // FIXME (#9639): This needs to handle non-utf8 paths
-    p.as_str().expect("non-utf8 component in rpath").to_owned()
+    p.as_vec().to_owned()
 }
 
-pub fn get_install_prefix_rpath(target_triple: &str) -> ~str {
+pub fn get_install_prefix_rpath(target_triple: &str) -> ~[u8] {
     let install_prefix = env!("CFG_PREFIX");
 
     let tlib = filesearch::relative_target_lib_path(target_triple);
     let mut path = Path::new(install_prefix);
     path.push(&tlib);
     let path = os::make_absolute(&path);
-    // FIXME (#9639): This needs to handle non-utf8 paths
-    path.as_str().expect("non-utf8 component in rpath").to_owned()
+    path.as_vec().to_owned()
 }
 
-pub fn minimize_rpaths(rpaths: &[~str]) -> ~[~str] {
+// Normalizes away `.` components, `..` components which follow a directory,
+// and redundant slashes, so that equivalent rpaths compare equal. The
+// `$ORIGIN`/`@loader_path` prefix of a relative rpath is kept as it is, since
+// a `..` following it must not cancel it out.
+pub fn canonicalize_rpath(rpath: &[u8]) -> ~[u8] {
+    let origin = bytes!("$ORIGIN");
+    let loader_path = bytes!("@loader_path");
+    let (prefix, rest) = if rpath.starts_with(origin) {
+        (origin, rpath.slice_from(origin.len()))
+    } else if rpath.starts_with(loader_path) {
+        (loader_path, rpath.slice_from(loader_path.len()))
+    } else {
+        (&[], rpath)
+    };
+    if rest.is_empty() {
+        return rpath.to_owned();
+    }
+    let rest = if prefix.is_empty() { rest } else { rest.slice_from(1) };
+    let path = Path::new(rest);
+    let path = path.as_vec();
+    if prefix.is_empty() {
+        path.to_owned()
+    } else if path == bytes!(".") {
+        prefix.to_owned()
+    } else {
+        let mut rpath = prefix.to_owned();
+        rpath.push('/' as u8);
+        rpath.push_all(path);
+        rpath
+    }
+}
+
+pub fn minimize_rpaths(rpaths: &[~[u8]]) -> ~[~[u8]] {
     let mut set = HashSet::new();
     let mut minimized = ~[];
     for rpath in rpaths.iter() {
@@ -184,23 +253,41 @@ mod test {
 
     use back::rpath::{get_absolute_rpath, get_install_prefix_rpath};
     use back::rpath::{minimize_rpaths, rpaths_to_flags, get_rpath_relative_to_output};
+    use back::rpath::{get_rpaths, canonicalize_rpath};
     use syntax::abi;
 
+    fn b(strs: &[&str]) -> ~[~[u8]] {
+        strs.map(|s| s.as_bytes().to_owned())
+    }
+
     #[test]
     fn test_rpaths_to_flags() {
-        let flags = rpaths_to_flags([~"path1", ~"path2"]);
-        assert_eq!(flags, ~[~"-Wl,-rpath,path1", ~"-Wl,-rpath,path2"]);
+        let flags = rpaths_to_flags(abi::OsLinux, b(["path1", "path2"]));
+        assert_eq!(flags, b(["-Wl,-rpath,path1:path2"]));
+        assert_eq!(rpaths_to_flags(abi::OsLinux, []), ~[]);
+    }
+
+    #[test]
+    fn test_rpaths_to_flags_macos() {
+        let flags = rpaths_to_flags(abi::OsMacos, b(["path1", "path2"]));
+        assert_eq!(flags, b(["-Wl,-rpath,path1", "-Wl,-rpath,path2"]));
+    }
+
+    #[test]
+    fn test_rpaths_to_flags_non_utf8() {
+        let flags = rpaths_to_flags(abi::OsLinux, [~[0xff, '/' as u8, 'a' as u8]]);
+        assert_eq!(flags, ~[bytes!("-Wl,-rpath,") + [0xff, '/' as u8, 'a' as u8]]);
     }
 
     #[test]
     fn test_prefix_rpath() {
         let res = get_install_prefix_rpath("triple");
         let mut d = Path::new(env!("CFG_PREFIX"));
-        d.push("lib/rustc/triple/lib");
+        d.push("lib/rustlib/triple/lib");
         debug!("test_prefix_path: {} vs. {}",
-               res,
+               Path::new(res.as_slice()).display(),
                d.display());
-        assert!(res.as_bytes().ends_with(d.as_vec()));
+        assert!(res.ends_with(d.as_vec()));
     }
 
     #[test]
@@ -211,17 +298,17 @@ mod test {
 
     #[test]
     fn test_minimize1() {
-        let res = minimize_rpaths([~"rpath1", ~"rpath2", ~"rpath1"]);
-        assert_eq!(res.as_slice(), [~"rpath1", ~"rpath2"]);
+        let res = minimize_rpaths(b(["rpath1", "rpath2", "rpath1"]));
+        assert_eq!(res, b(["rpath1", "rpath2"]));
     }
 
     #[test]
     fn test_minimize2() {
-        let res = minimize_rpaths([~"1a", ~"2",  ~"2",
-                                   ~"1a", ~"4a", ~"1a",
-                                   ~"2",  ~"3",  ~"4a",
-                                   ~"3"]);
-        assert_eq!(res.as_slice(), [~"1a", ~"2", ~"4a", ~"3"]);
+        let res = minimize_rpaths(b(["1a", "2",  "2",
+                                     "1a", "4a", "1a",
+                                     "2",  "3",  "4a",
+                                     "3"]));
+        assert_eq!(res, b(["1a", "2", "4a", "3"]));
     }
 
     #[test]
@@ -231,7 +318,7 @@ mod test {
       let o = abi::OsLinux;
       let res = get_rpath_relative_to_output(o,
             &Path::new("bin/rustc"), &Path::new("lib/libstd.so"));
-      assert_eq!(res.as_slice(), "$ORIGIN/../lib");
+      assert_eq!(res.as_slice(), bytes!("$ORIGIN/../lib"));
     }
 
     #[test]
@@ -240,7 +327,7 @@ mod test {
         let o = abi::OsFreebsd;
         let res = get_rpath_relative_to_output(o,
             &Path::new("bin/rustc"), &Path::new("lib/libstd.so"));
-        assert_eq!(res.as_slice(), "$ORIGIN/../lib");
+        assert_eq!(res.as_slice(), bytes!("$ORIGIN/../lib"));
     }
 
     #[test]
@@ -250,17 +337,37 @@ mod test {
         let res = get_rpath_relative_to_output(o,
                                                &Path::new("bin/rustc"),
                                                &Path::new("lib/libstd.so"));
-        assert_eq!(res.as_slice(), "@loader_path/../lib");
+        assert_eq!(res.as_slice(), bytes!("@loader_path/../lib"));
     }
 
     #[test]
     fn test_get_absolute_rpath() {
         let res = get_absolute_rpath(&Path::new("lib/libstd.so"));
         let lib = os::make_absolute(&Path::new("lib"));
-        debug!("test_get_absolute_rpath: {} vs. {}",
-               res.to_str(), lib.display());
+        assert_eq!(res.as_slice(), lib.as_vec());
+    }
 
-        // FIXME (#9639): This needs to handle non-utf8 paths
-        assert_eq!(res.as_slice(), lib.as_str().expect("non-utf8 component in path"));
+    #[test]
+    fn test_rpaths_relative_only() {
+        let res = get_rpaths(abi::OsLinux, &Path::new("/sysroot"),
+                             &Path::new("/app/bin/foo"),
+                             [Path::new("/app/lib/libbar.so"),
+                              Path::new("/app/lib/libbaz.so")],
+                             "triple", true);
+        assert_eq!(res, b(["$ORIGIN/../lib"]));
+    }
+
+    #[test]
+    fn test_canonicalize_rpath() {
+        fn check(rpath: &str, canonical: &str) {
+            assert_eq!(canonicalize_rpath(rpath.as_bytes()).as_slice(),
+                       canonical.as_bytes());
+        }
+        check("/usr//lib/./rust/../", "/usr/lib");
+        check("$ORIGIN/../lib", "$ORIGIN/../lib");
+        check("$ORIGIN/a/../../lib", "$ORIGIN/../lib");
+        check("$ORIGIN/.", "$ORIGIN");
+        check("@loader_path/a/./b", "@loader_path/a/b");
+        check("$ORIGIN", "$ORIGIN");
     }
 }
@@ -15,4 +15,9 @@ pub struct t {
     data_layout: ~str,
     target_triple: ~str,
     cc_args: ~[~str],
+    // Whether cc leaves out its default libraries (libc and the like) when
+    // linking for the target
+    no_default_libraries: bool,
+    // The libraries the runtime needs linked into every executable and dylib
+    runtime_libraries: ~[~str],
 }
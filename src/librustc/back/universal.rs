@@ -0,0 +1,125 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Universal ("fat") Mach-O files, which hold a program (or library) built for
+//! several architectures, of which OSX loads the one for the machine it runs
+//! on. This is what `lipo -create` writes.
+
+static FAT_MAGIC: u32 = 0xcafebabe;
+static MH_MAGIC: u32 = 0xfeedface;
+static MH_MAGIC_64: u32 = 0xfeedfacf;
+
+// The capability bits of the CPU subtype, which the universal header leaves out
+static CPU_SUBTYPE_MASK: u32 = 0xff000000;
+
+// Each of the files starts at a page boundary (of 2^12 bytes)
+static ALIGN: uint = 12;
+
+/// Whether `bytes` are a Mach-O file for a little-endian architecture, which
+/// all of the OSX targets are
+pub fn is_macho(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && {
+        let magic = read_le(bytes, 0);
+        magic == MH_MAGIC || magic == MH_MAGIC_64
+    }
+}
+
+/// Combines Mach-O files into a universal one. The architecture of each file
+/// is taken from its own header, and no two of them may be the same.
+pub fn write_universal(files: &[~[u8]]) -> Result<~[u8], ~str> {
+    let mut archs = ~[];
+    for file in files.iter() {
+        if !is_macho(*file) {
+            return Err(~"not a Mach-O file");
+        }
+        let cputype = read_le(*file, 4);
+        let cpusubtype = read_le(*file, 8) & !CPU_SUBTYPE_MASK;
+        if archs.iter().any(|&(t, s)| t == cputype && s == cpusubtype) {
+            return Err(format!("more than one file for the cpu type {}",
+                               cputype));
+        }
+        archs.push((cputype, cpusubtype));
+    }
+
+    // The header is big-endian, unlike the files which follow it
+    let mut out = ~[];
+    push_be(&mut out, FAT_MAGIC);
+    push_be(&mut out, files.len() as u32);
+    let mut offset = 8 + 20 * files.len();
+    let mut offsets = ~[];
+    for (file, &(cputype, cpusubtype)) in files.iter().zip(archs.iter()) {
+        offset = align(offset);
+        push_be(&mut out, cputype);
+        push_be(&mut out, cpusubtype);
+        push_be(&mut out, offset as u32);
+        push_be(&mut out, file.len() as u32);
+        push_be(&mut out, ALIGN as u32);
+        offsets.push(offset);
+        offset += file.len();
+    }
+    for (file, &offset) in files.iter().zip(offsets.iter()) {
+        out.grow(offset - out.len(), &0u8);
+        out.push_all(*file);
+    }
+    Ok(out)
+}
+
+fn align(offset: uint) -> uint {
+    let page = 1 << ALIGN;
+    (offset + page - 1) & !(page - 1)
+}
+
+fn read_le(bytes: &[u8], at: uint) -> u32 {
+    (bytes[at] as u32) | (bytes[at + 1] as u32 << 8) |
+        (bytes[at + 2] as u32 << 16) | (bytes[at + 3] as u32 << 24)
+}
+
+fn push_be(out: &mut ~[u8], n: u32) {
+    out.push((n >> 24) as u8);
+    out.push((n >> 16) as u8);
+    out.push((n >> 8) as u8);
+    out.push(n as u8);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_macho, write_universal};
+
+    // The start of a Mach-O header with the given magic, cputype and subtype
+    fn header(magic: u32, cputype: u32, cpusubtype: u32) -> ~[u8] {
+        let mut bytes = ~[];
+        for &n in [magic, cputype, cpusubtype].iter() {
+            bytes.push_all([n as u8, (n >> 8) as u8, (n >> 16) as u8,
+                            (n >> 24) as u8]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_write_universal() {
+        let i386 = header(0xfeedface, 7, 3);
+        let x86_64 = header(0xfeedfacf, 0x01000007, 0x80000003);
+        assert!(is_macho(i386) && is_macho(x86_64));
+        let fat = write_universal([i386.clone(), x86_64.clone()]).unwrap();
+        assert_eq!(fat.slice(0, 8), &[0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2]);
+        // The capability bits are left out of the subtype
+        assert_eq!(fat.slice(28, 36), &[1, 0, 0, 7, 0, 0, 0, 3]);
+        assert_eq!(fat.slice(4096, 4096 + 12), i386.as_slice());
+        assert_eq!(fat.slice(8192, 8192 + 12), x86_64.as_slice());
+        assert!(!is_macho(fat));
+    }
+
+    #[test]
+    fn test_write_universal_same_arch() {
+        let i386 = header(0xfeedface, 7, 3);
+        assert!(write_universal([i386.clone(), i386]).is_err());
+        assert!(write_universal([~[1, 2, 3]]).is_err());
+    }
+}
@@ -47,5 +47,9 @@ pub fn get_target_strs(target_triple: ~str, target_os: abi::Os) -> target_strs::
         target_triple: target_triple,
 
         cc_args: ~[~"-m32"],
+
+        no_default_libraries: false,
+
+        runtime_libraries: ~[~"morestack"],
     };
 }
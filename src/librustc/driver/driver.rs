@@ -9,10 +9,15 @@
 // except according to those terms.
 
 
+use back::codegen;
+use back::dep_graph;
 use back::link;
+use back::metadata_json;
+use back::universal;
 use back::{arm, x86, x86_64, mips};
 use driver::session::{Aggressive, OutputExecutable};
 use driver::session::{Session, Session_, No, Less, Default};
+use driver::jobserver;
 use driver::session;
 use front;
 use lib::llvm::llvm;
@@ -22,7 +27,7 @@ use metadata::{creader, cstore, filesearch};
 use metadata;
 use middle::{trans, freevars, kind, ty, typeck, lint, astencode, reachable};
 use middle;
-use util::common::time;
+use util::common::{time, set_json_times};
 use util::ppaux;
 
 use std::hashmap::{HashMap,HashSet};
@@ -33,6 +38,9 @@ use std::os;
 use std::vec;
 use extra::getopts::groups::{optopt, optmulti, optflag, optflagopt};
 use extra::getopts;
+use extra::json;
+use extra::tempfile::TempDir;
+use extra::treemap::TreeMap;
 use syntax::ast;
 use syntax::abi;
 use syntax::attr;
@@ -166,6 +174,8 @@ pub fn phase_2_configure_and_expand(sess: Session,
 
     *sess.building_library = session::building_library(sess.opts, &crate);
     *sess.outputs = session::collect_outputs(sess.opts, &crate);
+    *sess.no_split_stack = sess.no_morestack() ||
+                           attr::contains_name(crate.attrs, "no_split_stack");
 
     time(time_passes, "gated feature checking", (), |_|
          front::feature_gate::check_crate(sess, &crate));
@@ -332,10 +342,24 @@ pub fn phase_3_run_analysis_passes(sess: Session,
 pub struct CrateTranslation {
     context: ContextRef,
     module: ModuleRef,
+    metadata_context: ContextRef,
     metadata_module: ModuleRef,
     link: LinkMeta,
     metadata: ~[u8],
     reachable: ~[~str],
+    exported_symbols: ~[~str],
+    exported_statics: ~[~str],
+    unmangled_symbols: ~[(~str, codemap::Span)],
+    // The symbols which the linker must keep, those of the items placed in
+    // sections with `#[link_section]` and of the static constructors and
+    // destructors
+    kept_symbols: ~[~str],
+    c_header: Option<~str>,
+    metadata_json: Option<~str>,
+    // The intermediate files which go into an rlib are written here rather
+    // than next to the output, where other invocations may be writing files
+    // of the same names
+    tmpdir: TempDir,
 }
 
 /// Run the translation phase to LLVM, after which the AST and analysis can
@@ -349,23 +373,24 @@ pub fn phase_4_translate_to_llvm(sess: Session,
                                   &outputs.obj_filename))
 }
 
-/// Run LLVM itself, producing a bitcode file, assembly file or object file
-/// as a side effect.
+/// Run the codegen backend (LLVM unless `-Z codegen-backend` says otherwise),
+/// producing a bitcode file, assembly file or object file as a side effect.
 pub fn phase_5_run_llvm_passes(sess: Session,
                                trans: &CrateTranslation,
                                outputs: &OutputFilenames) {
+    let backend = sess.codegen_backend;
 
     if sess.no_integrated_as() {
         let output_type = link::output_type_assembly;
         let asm_filename = outputs.obj_filename.with_extension("s");
 
         time(sess.time_passes(), "LLVM passes", (), |_|
-            link::write::run_passes(sess,
-                                    trans,
-                                    output_type,
-                                    &asm_filename));
+            backend.run_passes(sess,
+                               trans,
+                               output_type,
+                               &asm_filename));
 
-        link::write::run_assembler(sess, &asm_filename, &outputs.obj_filename);
+        backend.run_assembler(sess, &asm_filename, &outputs.obj_filename);
 
         // Remove assembly source, unless --save-temps was specified
         if !sess.opts.save_temps {
@@ -373,10 +398,16 @@ pub fn phase_5_run_llvm_passes(sess: Session,
         }
     } else {
         time(sess.time_passes(), "LLVM passes", (), |_|
-            link::write::run_passes(sess,
-                                    trans,
-                                    sess.opts.output_type,
-                                    &outputs.obj_filename));
+            backend.run_passes(sess,
+                               trans,
+                               sess.opts.output_type,
+                               &outputs.obj_filename));
+    }
+
+    if sess.split_dwarf() &&
+       (sess.opts.output_type == link::output_type_object ||
+        sess.opts.output_type == link::output_type_exe) {
+        backend.split_dwarf(sess, &outputs.obj_filename);
     }
 }
 
@@ -436,9 +467,136 @@ pub fn compile_input(sess: Session, cfg: ast::CrateConfig, input: &input,
                                               &analysis, outputs);
         (outputs, trans)
     };
+    if sess.opts.print_exported_symbols {
+        link::print_exported_symbols(&trans);
+        return;
+    }
+    match trans.c_header {
+        Some(ref header) => {
+            let name = format!("{}.h", trans.link.pkgid.name);
+            let path = outputs.out_filename.with_filename(name);
+            fs::File::create(&path).write(header.as_bytes());
+        }
+        None => {}
+    }
+    match trans.metadata_json {
+        Some(ref json) => {
+            let name = format!("{}.json", trans.link.pkgid.name);
+            let path = outputs.out_filename.with_filename(name);
+            fs::File::create(&path).write(json.as_bytes());
+        }
+        None => {}
+    }
+    if sess.opts.emit_dep_graph {
+        let name = trans.link.pkgid.name.as_slice();
+        let dot = dep_graph::to_dot(sess, &trans.link);
+        let path = outputs.out_filename.with_filename(format!("{}-deps.dot", name));
+        fs::File::create(&path).write(dot.as_bytes());
+        let json = dep_graph::to_json(sess, &trans.link);
+        let path = outputs.out_filename.with_filename(format!("{}-deps.json", name));
+        fs::File::create(&path).write(json.as_bytes());
+    }
     phase_5_run_llvm_passes(sess, &trans, outputs);
-    if stop_after_phase_5(sess) { return; }
-    phase_6_link_output(sess, &trans, outputs);
+    if !stop_after_phase_5(sess) {
+        phase_6_link_output(sess, &trans, outputs);
+    }
+    match sess.opts.debugging_values.self_profile {
+        Some(ref path) => write_self_profile(sess, trans.link.pkgid.name, path),
+        None => {}
+    }
+}
+
+/// Compiles the crate once for each of the architectures given with
+/// `-C universal-archs`, each into a directory of its own, and combines the
+/// executables and dylibs which come out into universal binaries. The
+/// upstream crates have to be found for each of the architectures.
+pub fn compile_universal(sopts: @session::options,
+                         demitter: @diagnostic::Emitter, input: &input,
+                         outdir: &Option<Path>, output: &Option<Path>) {
+    if get_os(sopts.target_triple) != Some(abi::OsMacos) {
+        early_error(demitter, "universal binaries are only available on OSX \
+                               targets");
+    }
+
+    if sopts.output_type != link::output_type_exe {
+        early_error(demitter, "only executables and dylibs can be universal");
+    }
+
+    let tmpdir = TempDir::new("rustc").expect("needs a temp dir");
+    // The linked files of each architecture, in the same order. Their names
+    // can differ, dylibs are named after a hash which covers the target.
+    let mut outputs = ~[];
+    for arch in sopts.cg.universal_archs.iter() {
+        let triple = format!("{}-apple-darwin", *arch);
+        if get_arch(triple).is_none() {
+            early_error(demitter, format!("unknown architecture: {}", *arch));
+        }
+        let mut opts = (*sopts).clone();
+        opts.target_triple = triple;
+        opts.cg.universal_archs = ~[];
+        let sess = build_session(@opts, demitter);
+        let cfg = build_configuration(sess);
+
+        let dir = tmpdir.path().join(arch.as_slice());
+        fs::mkdir(&dir, io::UserRWX);
+        let output = output.as_ref().map(|o| dir.join(o.filename().unwrap()));
+        compile_input(sess, cfg, input, &Some(dir.clone()), &output);
+        // Only the linked files are combined, not the object files and debug
+        // info which can be left next to them
+        outputs.push((*sess.output_files).clone());
+    }
+
+    // The outputs end up where they would have without -C universal-archs
+    let dest = match *output {
+        Some(ref o) => o.dir_path(),
+        None => match *outdir {
+            Some(ref d) => d.clone(),
+            None => match *input {
+                str_input(_) => os::getcwd(),
+                file_input(ref ifile) => ifile.dir_path(),
+            }
+        }
+    };
+    for (i, file) in outputs[0].iter().enumerate() {
+        // The universal file takes the name of the first architecture's
+        let name = file.filename().unwrap();
+        let thin = outputs.map(|files| fs::File::open(&files[i]).read_to_end());
+        if !thin.iter().all(|bytes| universal::is_macho(*bytes)) {
+            early_error(demitter, format!("only executables and dylibs can be \
+                                           universal, not {}",
+                                          file.filename_display()));
+        }
+        match universal::write_universal(thin) {
+            Ok(bytes) => {
+                let dst = dest.join(name);
+                fs::File::create(&dst).write(bytes);
+                fs::chmod(&dst, fs::stat(file).perm);
+            }
+            Err(e) => {
+                early_error(demitter, format!("failed to write universal {}: {}",
+                                              file.filename_display(), e));
+            }
+        }
+    }
+}
+
+/// Appends the events recorded for `-Z self-profile` to `path`, one JSON
+/// record per line, so that the profiles of all the crates of a build can be
+/// gathered in a single file
+pub fn write_self_profile(sess: Session, crate_name: &str, path: &Path) {
+    let mut records = ~"";
+    for event in sess.profile_events.iter() {
+        let mut record = ~TreeMap::new();
+        record.insert(~"crate", json::String(crate_name.to_owned()));
+        record.insert(~"kind", json::String(event.kind.to_owned()));
+        record.insert(~"name", json::String(event.name.clone()));
+        record.insert(~"start", json::Number(event.start));
+        record.insert(~"time", json::Number(event.time));
+        records.push_str(json::Object(record).to_str());
+        records.push_char('\n');
+    }
+    let mut file = fs::File::open_mode(path, io::Append, io::Write);
+    file.write(records.as_bytes());
 }
 
 struct IdentifiedAnnotation {
@@ -594,7 +752,25 @@ pub fn build_target_config(sopts: @session::options,
       abi::Arm => (ast::ty_i32, ast::ty_u32),
       abi::Mips => (ast::ty_i32, ast::ty_u32)
     };
-    let target_triple = sopts.target_triple.clone();
+    let macos_min_version = if os == abi::OsMacos {
+        macos_min_version(sopts, demitter)
+    } else {
+        None
+    };
+    // LLVM records the deployment target in the outputs (in their
+    // LC_VERSION_MIN_MACOSX load command) when the triple names it
+    let target_triple = match macos_min_version {
+        Some(ref version) => {
+            let parts = sopts.target_triple.split('-').to_owned_vec();
+            if parts.len() < 2 {
+                early_error(demitter, format!("malformed target triple `{}`, \
+                                               expected ARCH-VENDOR-OS",
+                                              sopts.target_triple));
+            }
+            format!("{}-{}-macosx{}", parts[0], parts[1], *version)
+        }
+        None => sopts.target_triple.clone(),
+    };
     let target_strs = match arch {
       abi::X86 => x86::get_target_strs(target_triple, os),
       abi::X86_64 => x86_64::get_target_strs(target_triple, os),
@@ -607,10 +783,34 @@ pub fn build_target_config(sopts: @session::options,
         target_strs: target_strs,
         int_type: int_type,
         uint_type: uint_type,
+        macos_min_version: macos_min_version,
     };
     return target_cfg;
 }
 
+// The oldest OSX release the outputs are to run on: the one given with
+// `-C macos-min-version`, or else in MACOSX_DEPLOYMENT_TARGET like for the C
+// compiler
+fn macos_min_version(sopts: @session::options,
+                     demitter: @diagnostic::Emitter) -> Option<~str> {
+    let version = match sopts.cg.macos_min_version {
+        Some(ref version) => version.clone(),
+        None => match os::getenv("MACOSX_DEPLOYMENT_TARGET") {
+            Some(version) if !version.is_empty() => version,
+            _ => return None,
+        },
+    };
+    let parts = version.split('.').to_owned_vec();
+    let valid = (parts.len() == 2 || parts.len() == 3) && parts.iter().all(|p| {
+        !p.is_empty() && p.chars().all(|c| c.is_digit())
+    });
+    if !valid {
+        early_error(demitter, format!("invalid OSX version `{}`, expected one \
+                                       like 10.7", version));
+    }
+    Some(version)
+}
+
 pub fn host_triple() -> ~str {
     // Get the host triple out of the build environment. This ensures that our
     // idea of the host triple is the same as for the set of libraries we've
@@ -623,10 +823,20 @@ pub fn host_triple() -> ~str {
     (env!("CFG_COMPILER")).to_owned()
 }
 
+/// Whether a free command line argument is an object file or archive to link
+/// in (see `-C link-objects`) rather than the crate to compile
+pub fn is_link_object(arg: &str) -> bool {
+    arg.ends_with(".o") || arg.ends_with(".a")
+}
+
 pub fn build_session_options(binary: @str,
                              matches: &getopts::Matches,
                              demitter: @diagnostic::Emitter)
                              -> @session::options {
+    let matches = &SessionMatches {
+        cmdline: matches,
+        env: env_matches(demitter),
+    };
     let mut outputs = ~[];
     if matches.opt_present("rlib") {
         outputs.push(session::OutputRlib)
@@ -638,6 +848,9 @@ pub fn build_session_options(binary: @str,
     if matches.opt_present("dylib") || matches.opt_present("lib") {
         outputs.push(session::OutputDylib)
     }
+    if matches.opt_present("cdylib") {
+        outputs.push(session::OutputCdylib)
+    }
     if matches.opt_present("bin") {
         outputs.push(session::OutputExecutable)
     }
@@ -645,6 +858,32 @@ pub fn build_session_options(binary: @str,
     let parse_only = matches.opt_present("parse-only");
     let no_trans = matches.opt_present("no-trans");
 
+    let mut print_exported_symbols = false;
+    let mut dry_run_link = false;
+    for info in matches.opt_strs("print").iter() {
+        match info.as_slice() {
+            "exported-symbols" => print_exported_symbols = true,
+            "link-args" => dry_run_link = true,
+            // These are printed without compiling anything
+            "passes" | "llvm-args" => {}
+            _ => early_error(demitter, format!("unknown --print argument: {}",
+                                               *info)),
+        }
+    }
+
+    let mut emit_c_header = false;
+    let mut emit_metadata_json = false;
+    let mut emit_dep_graph = false;
+    for kind in matches.opt_strs("emit").iter() {
+        match kind.as_slice() {
+            "c-header" => emit_c_header = true,
+            "metadata-json" => emit_metadata_json = true,
+            "dep-graph" => emit_dep_graph = true,
+            _ => early_error(demitter, format!("unknown --emit argument: {}",
+                                               *kind)),
+        }
+    }
+
     let lint_levels = [lint::allow, lint::warn,
                        lint::deny, lint::forbid];
     let mut lint_opts = ~[];
@@ -670,25 +909,116 @@ pub fn build_session_options(binary: @str,
         }
     }
 
-    let mut debugging_opts = 0u;
+    let mut debugging_opts = 0u64;
+    let mut debugging_values = session::basic_debugging_values();
     let debug_flags = matches.opt_strs("Z");
     let debug_map = session::debugging_opts_map();
     for debug_flag in debug_flags.iter() {
-        let mut this_bit = 0u;
-        for tuple in debug_map.iter() {
-            let (name, bit) = match *tuple { (ref a, _, b) => (a, b) };
-            if *name == *debug_flag { this_bit = bit; break; }
-        }
-        if this_bit == 0u {
-            early_error(demitter, format!("unknown debug flag: {}", *debug_flag))
+        let mut iter = debug_flag.splitn('=', 1);
+        let key = iter.next().unwrap();
+        match iter.next() {
+            // The options which take a value are looked up like the codegen
+            // options, and the others are bits
+            Some(value) => {
+                let option_to_lookup = key.replace("-", "_");
+                let setter = session::DV_OPTIONS.iter().find(|&&(name, _, _, _)| {
+                    name == option_to_lookup.as_slice()
+                });
+                match setter {
+                    Some(&(_, setter, _, _)) => {
+                        if !setter(&mut debugging_values, Some(value)) {
+                            early_error(demitter, format!("debugging option \
+                                                           `{}` does not take \
+                                                           the value `{}`",
+                                                          key, value))
+                        }
+                    }
+                    None if debug_map.iter().any(|&(name, _, _)| name == key) => {
+                        early_error(demitter, format!("debugging option `{}` \
+                                                       does not take a value",
+                                                      key))
+                    }
+                    None => {
+                        early_error(demitter, format!("unknown debug flag: {}",
+                                                      *debug_flag))
+                    }
+                }
+            }
+            None => {
+                let mut this_bit = 0;
+                for tuple in debug_map.iter() {
+                    let (name, bit) = match *tuple { (ref a, _, b) => (a, b) };
+                    if *name == *debug_flag { this_bit = bit; break; }
+                }
+                if this_bit == 0 {
+                    early_error(demitter, format!("unknown debug flag: {}",
+                                                  *debug_flag))
+                }
+                debugging_opts |= this_bit;
+            }
         }
-        debugging_opts |= this_bit;
+    }
+    // `-Z time-passes=json` measures the passes like `-Z time-passes`
+    if debugging_values.time_passes {
+        debugging_opts |= session::time_passes;
+    }
+    // Nothing unwinds through the code of the crate
+    if debugging_opts & session::abort_on_failure != 0 {
+        debugging_opts |= session::no_landing_pads;
+    }
+    if codegen::find_backend(debugging_values.codegen_backend).is_none() {
+        let names = codegen::backends().map(|b| b.name());
+        early_error(demitter, format!("unknown codegen backend `{}`, expected \
+                                       one of {}",
+                                      debugging_values.codegen_backend,
+                                      names.connect(", ")))
     }
 
     if debugging_opts & session::debug_llvm != 0 {
         unsafe { llvm::LLVMSetDebug(1); }
     }
 
+    let mut cg = session::basic_codegen_options();
+    for option in matches.opt_strs("C").iter() {
+        let mut iter = option.splitn('=', 1);
+        let key = iter.next().unwrap();
+        let value = iter.next();
+        let option_to_lookup = key.replace("-", "_");
+        let mut found = false;
+        for &(candidate, setter, _, _) in session::CG_OPTIONS.iter() {
+            if option_to_lookup.as_slice() != candidate { continue }
+            if !setter(&mut cg, value) {
+                match value {
+                    Some(value) => {
+                        early_error(demitter, format!("codegen option `{}` does \
+                                                       not take the value `{}`",
+                                                      key, value))
+                    }
+                    None => {
+                        early_error(demitter, format!("codegen option `{0}` requires \
+                                                       a value (-C {0}=<value>)",
+                                                      key))
+                    }
+                }
+            }
+            found = true;
+            break;
+        }
+        if !found {
+            early_error(demitter, format!("unknown codegen option: `{}`", key));
+        }
+    }
+    for arg in matches.cmdline.free.iter() {
+        if is_link_object(*arg) {
+            cg.link_objects.push(arg.clone());
+        }
+    }
+    // `-Z soft-float` is the old name of `-C soft-float`, and the only one
+    // the snapshot compiler knows
+    if debugging_opts & session::use_softfp != 0 {
+        cg.soft_float = true;
+    }
+
     let output_type =
         if parse_only || no_trans {
             link::output_type_none
@@ -702,7 +1032,16 @@ pub fn build_session_options(binary: @str,
         } else if matches.opt_present("emit-llvm") {
             link::output_type_bitcode
         } else { link::output_type_exe };
-    let sysroot_opt = matches.opt_str("sysroot").map(|m| @Path::new(m));
+    // The sysroot ends up in rpaths and in the debug info, so it's made
+    // absolute rather than left relative to the working directory
+    let sysroot_opt = matches.opt_str("sysroot").map(|m| {
+        let sysroot = os::make_absolute(&Path::new(m));
+        if !sysroot.is_dir() {
+            early_error(demitter, format!("sysroot `{}` is not a directory",
+                                          sysroot.display()));
+        }
+        @sysroot
+    });
     let target = matches.opt_str("target").unwrap_or(host_triple());
     let target_cpu = matches.opt_str("target-cpu").unwrap_or(~"generic");
     let target_feature = matches.opt_str("target-feature").unwrap_or(~"");
@@ -728,15 +1067,111 @@ pub fn build_session_options(binary: @str,
         } else { No }
     };
     let gc = debugging_opts & session::gc != 0;
-    let extra_debuginfo = debugging_opts & session::extra_debug_info != 0;
-    let debuginfo = debugging_opts & session::debug_info != 0 ||
-        extra_debuginfo;
+    // Level 1 is line tables only: what `-Z debug-info` produces without its
+    // lexical scopes (see `Session::line_tables_only`). Level 2 adds the
+    // scopes, variables and types of `-Z extra-debug-info`.
+    let (debuginfo, extra_debuginfo) = match cg.debuginfo {
+        Some(level) => (level >= 1, level >= 2),
+        None => {
+            let extra = debugging_opts & session::extra_debug_info != 0;
+            (debugging_opts & session::debug_info != 0 || extra, extra)
+        }
+    };
+    // The source lines interleaved into the assembly by `-Z asm-comments` are
+    // found through the line tables
+    let debuginfo = debuginfo ||
+        (debugging_opts & session::asm_comments != 0 &&
+         output_type == link::output_type_assembly);
+
+    let mut addl_lib_search_paths = HashSet::new();
+    let mut search_paths = ~[];
+    for s in matches.opt_strs("L").iter() {
+        // Paths are only split on '=' if it follows one of the kinds
+        let (kind, path) = match s.find('=') {
+            Some(i) => match s.slice_to(i) {
+                "all" => (session::SearchAll, s.slice_from(i + 1)),
+                "crate" | "dependency" => {
+                    (session::SearchCrate, s.slice_from(i + 1))
+                }
+                "native" => (session::SearchNative, s.slice_from(i + 1)),
+                "framework" => (session::SearchFramework, s.slice_from(i + 1)),
+                _ => (session::SearchAll, s.as_slice()),
+            },
+            None => (session::SearchAll, s.as_slice()),
+        };
+        let path = Path::new(path);
+        match kind {
+            session::SearchAll | session::SearchCrate => {
+                addl_lib_search_paths.insert(path.clone());
+            }
+            session::SearchNative | session::SearchFramework => {}
+        }
+        search_paths.push((kind, path));
+    }
+    for s in matches.opt_strs("framework-path").iter() {
+        search_paths.push((session::SearchFramework, Path::new(s.as_slice())));
+    }
 
-    let addl_lib_search_paths = matches.opt_strs("L").map(|s| {
-        Path::new(s.as_slice())
-    }).move_iter().collect();
+    let cmdline_libs = matches.opt_strs("l").map(|s| {
+        let (kind, name) = match s.find('=') {
+            Some(i) => (s.slice_to(i), s.slice_from(i + 1)),
+            None => ("dylib", s.as_slice()),
+        };
+        // Modifiers follow the kind, as in `static:+whole-archive=foo`
+        let (kind, modifiers) = match kind.find(':') {
+            Some(i) => (kind.slice_to(i), Some(kind.slice_from(i + 1))),
+            None => (kind, None),
+        };
+        let kind = match kind {
+            "static" => cstore::NativeStatic,
+            "static-nobundle" => cstore::NativeStaticNobundle,
+            "dylib" => cstore::NativeUnknown,
+            "framework" => cstore::NativeFramework,
+            "framework,weak" => cstore::NativeFrameworkWeak,
+            _ => {
+                early_error(demitter, format!("unknown library kind `{}`, \
+                                               expected one of static, \
+                                               static-nobundle, dylib, \
+                                               framework or framework,weak",
+                                              kind))
+            }
+        };
+        let kind = match modifiers {
+            Some(modifiers) => {
+                match creader::apply_modifiers(kind, modifiers) {
+                    Ok(kind) => kind,
+                    Err(msg) => early_error(demitter, msg),
+                }
+            }
+            None => kind,
+        };
+        if name.is_empty() {
+            early_error(demitter, "empty library name given via `-l`");
+        }
+        (name.to_owned(), kind)
+    });
     let ar = matches.opt_str("ar");
-    let linker = matches.opt_str("linker");
+    let linker = matches.opt_str("linker").map(|linker| {
+        // The value is split into words as a shell would split it, unless it
+        // has no quotes and is the absolute path of a file: that's how a
+        // program with spaces in its path used to be given. Relative paths
+        // are always split, so that they mean the same in any directory.
+        let path = Path::new(linker.as_slice());
+        let quoted = linker.contains_char('\'') || linker.contains_char('"');
+        if !quoted && path.is_absolute() && path.is_file() {
+            ~[linker]
+        } else {
+            match link::split_command_line(linker) {
+                Ok(words) => {
+                    if words.is_empty() {
+                        early_error(demitter, "empty command given via --linker");
+                    }
+                    words
+                }
+                Err(e) => early_error(demitter, format!("invalid --linker: {}", e)),
+            }
+        }
+    });
     let linker_args = matches.opt_strs("link-args").flat_map( |a| {
         a.split(' ').filter_map(|arg| {
             if arg.is_empty() {
@@ -756,7 +1191,7 @@ pub fn build_session_options(binary: @str,
         Some(s) => {
             s.split(|c: char| c == ' ' || c == ',').map(|s| {
                 s.trim().to_owned()
-            }).collect()
+            }).filter(|s| !s.is_empty()).collect()
         }
     };
     let llvm_args = match matches.opt_str("llvm-args") {
@@ -764,9 +1199,23 @@ pub fn build_session_options(binary: @str,
         Some(s) => {
             s.split(|c: char| c == ' ' || c == ',').map(|s| {
                 s.trim().to_owned()
-            }).collect()
+            }).filter(|s| !s.is_empty()).collect()
         }
     };
+    // LLVM aborts on options it doesn't know, and passes it doesn't know
+    // would be left out, so both are checked before getting that far
+    for pass in custom_passes.iter() {
+        if !codegen::is_known_pass(pass.as_slice()) {
+            early_error(demitter, format!("unknown LLVM pass `{}`, see \
+                                           --print passes", *pass));
+        }
+    }
+    for arg in llvm_args.iter() {
+        if !codegen::is_known_llvm_arg(arg.as_slice()) {
+            early_error(demitter, format!("unknown LLVM option `{}`, see \
+                                           --print llvm-args", *arg));
+        }
+    }
 
     let sopts = @session::options {
         outputs: outputs,
@@ -780,6 +1229,8 @@ pub fn build_session_options(binary: @str,
         save_temps: save_temps,
         output_type: output_type,
         addl_lib_search_paths: @mut addl_lib_search_paths,
+        search_paths: search_paths,
+        cmdline_libs: cmdline_libs,
         ar: ar,
         linker: linker,
         linker_args: linker_args,
@@ -792,7 +1243,14 @@ pub fn build_session_options(binary: @str,
         test: test,
         parse_only: parse_only,
         no_trans: no_trans,
+        print_exported_symbols: print_exported_symbols,
+        dry_run_link: dry_run_link,
+        emit_c_header: emit_c_header,
+        emit_metadata_json: emit_metadata_json,
+        emit_dep_graph: emit_dep_graph,
         debugging_opts: debugging_opts,
+        debugging_values: debugging_values,
+        cg: cg,
         android_cross_path: android_cross_path
     };
     return sopts;
@@ -814,6 +1272,7 @@ pub fn build_session_(sopts: @session::options,
                       span_diagnostic_handler: @mut diagnostic::span_handler)
                       -> Session {
     let target_cfg = build_target_config(sopts, demitter);
+    set_json_times(sopts.debugging_values.time_passes);
     let p_s = parse::new_parse_sess_special_handler(span_diagnostic_handler,
                                                     cm);
     let cstore = @mut cstore::mk_cstore(token::get_ident_interner());
@@ -837,6 +1296,14 @@ pub fn build_session_(sopts: @session::options,
         lints: @mut HashMap::new(),
         node_id: @mut 1,
         outputs: @mut ~[],
+        output_files: @mut ~[],
+        no_split_stack: @mut false,
+        profile_events: @mut ~[],
+        jobserver: jobserver::Client::from_env(),
+        codegen_backend: codegen::find_backend(sopts.debugging_values
+                                                    .codegen_backend)
+                             .expect("the backend is checked with the options"),
+        cc_probes: @mut HashMap::new(),
     }
 }
 
@@ -861,28 +1328,72 @@ pub fn optgroups() -> ~[getopts::groups::OptGroup] {
   optflag("c", "",    "Compile and assemble, but do not link"),
   optmulti("", "cfg", "Configure the compilation
                           environment", "SPEC"),
+  optmulti("", "emit", "Additionally emit the given kind of output. TYPE is
+                          `c-header`, which writes a C header declaring the
+                          crate's extern \"C\" items, `metadata-json`, which
+                          describes the crate's public API and dependencies
+                          as JSON (given an rlib or dylib rather than source,
+                          it describes that library instead of compiling
+                          anything), or `dep-graph`, which writes the graph of
+                          the upstream crates and where they're linked from
+                          as DOT and JSON",
+                          "TYPE"),
   optflag("",  "emit-llvm",
                         "Produce an LLVM assembly file if used with -S option;
                          produce an LLVM bitcode file otherwise"),
   optflag("h", "help","Display this message"),
-  optmulti("L", "",   "Add a directory to the library search path",
-                              "PATH"),
+  optmulti("L", "",   "Add a directory to the library search path. The
+                        optional KIND can be one of native (only search for
+                        native libraries), crate or dependency (only search
+                        for crates), framework (only search for frameworks,
+                        like --framework-path) or all (the default). Native
+                        libraries and frameworks are looked for in these
+                        directories in the order they're given, then in the
+                        lib directories of the RUST_PATH workspaces and then
+                        in the system ones", "[KIND=]PATH"),
+  optmulti("l", "",   "Link the generated crate(s) to the native library
+                        NAME. The optional KIND can be one of static,
+                        static-nobundle, dylib, framework or framework,weak
+                        (a framework which may be missing at runtime). If
+                        omitted, dylib is assumed. Static libraries accept
+                        the `+whole-archive` modifier, as in
+                        `static:+whole-archive=NAME`.",
+                        "[KIND[:MODIFIERS]=]NAME"),
+  optmulti("", "framework-path", "Add a directory to the framework search path
+                        (OSX)", "PATH"),
   optflag("",  "bin", "Compile an executable crate (default)"),
   optflag("",  "lib", "Compile a rust library crate using the compiler's default"),
   optflag("",  "rlib", "Compile a rust library crate as an rlib file"),
   optflag("",  "staticlib", "Compile a static library crate"),
   optflag("",  "dylib", "Compile a dynamic library crate"),
-  optopt("", "linker", "Program to use for linking instead of the default.", "LINKER"),
+  optflag("",  "cdylib", "Compile a dynamic library crate for use from C,
+                          with all rust dependencies linked statically"),
+  optflag("",  "demangle", "Demangle the rust symbols read from stdin"),
+  optopt("", "linker", "Program to use for linking instead of the default,
+                          which may be followed by arguments to always pass
+                          it. LINKER is split into words as by a shell, so a
+                          path with spaces in it has to be quoted, unless it's
+                          the absolute path of the program as a whole",
+                          "LINKER"),
   optopt("", "ar", "Program to use for managing archives instead of the default.", "AR"),
   optmulti("",  "link-args", "FLAGS is a space-separated list of flags
                             passed to the linker", "FLAGS"),
-  optflag("",  "ls",  "List the symbols defined by a library crate"),
+  optflag("",  "ls",  "List the name, version, hash, dependencies, native
+                       libraries and exported symbols of a library crate"),
   optflag("", "no-trans",
                         "Run all passes except translation; no output"),
   optflag("O", "",    "Equivalent to --opt-level=2"),
   optopt("o", "",     "Write output to <filename>", "FILENAME"),
   optopt("", "opt-level",
                         "Optimize with possible levels 0-3", "LEVEL"),
+  optmulti("", "print", "Print the given information after translation
+                          instead of producing any output. The supported INFO
+                          are `exported-symbols`, `link-args` which prints the
+                          commands that would link the output, in the order
+                          they'd be run, and leaves the files they need in
+                          place, and `passes` and `llvm-args` which list the
+                          LLVM passes and options without compiling anything",
+                          "INFO"),
   optopt("", "passes", "Comma or space separated list of pass names to use. \
                         Appends to the default list of passes to run for the \
                         specified current optimization level. A value of \
@@ -906,7 +1417,11 @@ pub fn optgroups() -> ~[getopts::groups::OptGroup] {
                         "Write intermediate files (.bc, .opt.bc, .o)
                           in addition to normal output"),
   optopt("", "sysroot",
-                        "Override the system root", "PATH"),
+                        "Override the system root, which is otherwise the
+                          installation rustc runs from. The crates of the
+                          target are looked for in its
+                          lib/rustlib/TRIPLE/lib directory, which is also
+                          passed to the linker", "PATH"),
   optflag("", "test", "Build a test harness"),
   optopt("", "target",
                         "Target triple cpu-manufacturer-kernel[-os]
@@ -929,6 +1444,7 @@ pub fn optgroups() -> ~[getopts::groups::OptGroup] {
   optmulti("F", "forbid",
                         "Set lint forbidden", "OPT"),
   optmulti("Z", "",   "Set internal debugging options", "FLAG"),
+  optmulti("C", "codegen", "Set a codegen-related flag", "FLAG"),
   optflag( "v", "version",
                         "Print version info and exit"),
  ]
@@ -1024,6 +1540,55 @@ pub fn build_output_filenames(input: &input,
     }
 }
 
+/// The options in the RUSTFLAGS environment variable, which are separated by
+/// whitespace, if it's set
+fn env_matches(demitter: @diagnostic::Emitter) -> Option<getopts::Matches> {
+    let flags = match os::getenv("RUSTFLAGS") {
+        Some(flags) => flags.words().map(|s| s.to_owned()).collect::<~[~str]>(),
+        None => return None,
+    };
+    let matches = match getopts::groups::getopts(flags, optgroups()) {
+        Ok(m) => m,
+        Err(f) => {
+            early_error(demitter, format!("invalid RUSTFLAGS: {}", f.to_err_msg()))
+        }
+    };
+    if !matches.free.is_empty() {
+        early_error(demitter, format!("RUSTFLAGS can only hold options, not `{}`",
+                                      matches.free[0]));
+    }
+    Some(matches)
+}
+
+// The options of the command line together with those of RUSTFLAGS. Options
+// which can be given more than once take the values of both, the command
+// line's first, and the others take the command line's value if it gives one.
+struct SessionMatches<'a> {
+    cmdline: &'a getopts::Matches,
+    env: Option<getopts::Matches>,
+}
+
+impl<'a> SessionMatches<'a> {
+    fn opt_present(&self, nm: &str) -> bool {
+        self.cmdline.opt_present(nm) ||
+            self.env.as_ref().map_default(false, |env| env.opt_present(nm))
+    }
+
+    fn opt_str(&self, nm: &str) -> Option<~str> {
+        self.cmdline.opt_str(nm).or_else(|| {
+            self.env.as_ref().and_then(|env| env.opt_str(nm))
+        })
+    }
+
+    fn opt_strs(&self, nm: &str) -> ~[~str] {
+        let mut strs = self.cmdline.opt_strs(nm);
+        for env in self.env.iter() {
+            strs.push_all_move(env.opt_strs(nm));
+        }
+        strs
+    }
+}
+
 pub fn early_error(emitter: @diagnostic::Emitter, msg: &str) -> ! {
     emitter.emit(None, msg, diagnostic::fatal);
     fail!();
@@ -1036,6 +1601,42 @@ pub fn list_metadata(sess: Session, path: &Path, out: @mut io::Writer) {
         session::sess_os_to_meta_os(sess.targ_cfg.os), path, out);
 }
 
+/// Whether `--emit metadata-json` should describe the input library rather
+/// than compile it
+pub fn is_library_file(sess: Session, path: &Path) -> bool {
+    let os = session::sess_os_to_meta_os(sess.targ_cfg.os);
+    metadata::loader::is_library_file(os, path)
+}
+
+/// Writes the JSON description of an existing library, named after the crate
+/// like the one written while compiling it
+pub fn write_library_metadata_json(sess: Session, path: &Path,
+                                   odir: &Option<Path>, ofile: &Option<Path>) {
+    let os = session::sess_os_to_meta_os(sess.targ_cfg.os);
+    let data = match metadata::loader::read_file_metadata(sess, os, path) {
+        Ok(data) => data,
+        Err(msg) => sess.fatal(msg),
+    };
+    let json = metadata_json::generate_from_metadata(token::get_ident_interner(),
+                                                     data);
+    let out = match *ofile {
+        Some(ref file) => file.clone(),
+        None => {
+            let attrs = metadata::decoder::get_crate_attributes(data);
+            let name = match attr::find_pkgid(attrs) {
+                Some(pkgid) => pkgid.name,
+                None => path.filestem_str().unwrap_or("metadata").to_owned(),
+            };
+            let dir = match *odir {
+                Some(ref dir) => dir.clone(),
+                None => path.dir_path(),
+            };
+            dir.join(format!("{}.json", name))
+        }
+    };
+    fs::File::create(&out).write(json.as_bytes());
+}
+
 #[cfg(test)]
 mod test {
 
@@ -0,0 +1,206 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A client of GNU make's jobserver, which keeps `make -jN` to N jobs in
+//! total when the jobs themselves run work in parallel.
+//!
+//! Make hands its recursive commands the two ends of a pipe in `MAKEFLAGS`
+//! (`--jobserver-fds=R,W`, or `--jobserver-auth=R,W` in newer versions). The
+//! pipe holds one byte per free job slot, beyond the slot that every job gets
+//! implicitly. The compiler runs on its implicit slot, so any task it spawns
+//! to run LLVM in parallel has to `acquire` a token first and hold it while
+//! it works.
+
+use std::libc::{c_int, c_void};
+use std::libc;
+use std::os;
+#[cfg(unix)]
+use std::unstable::intrinsics;
+
+#[deriving(Clone)]
+pub struct Client {
+    priv read: c_int,
+    priv write: c_int,
+}
+
+/// A job slot taken from the jobserver, given back when dropped
+pub struct Token {
+    priv client: Client,
+    priv byte: u8,
+}
+
+impl Client {
+    /// A client for the pipe with the given ends
+    pub fn new(read: c_int, write: c_int) -> Client {
+        Client { read: read, write: write }
+    }
+
+    /// The client for the jobserver that make passes down in `MAKEFLAGS`, if
+    /// any
+    pub fn from_env() -> Option<Client> {
+        match os::getenv("MAKEFLAGS") {
+            Some(flags) => Client::from_makeflags(flags),
+            None => None,
+        }
+    }
+
+    /// Finds the jobserver in the given value of `MAKEFLAGS`. Make leaves the
+    /// descriptors out of the flags only for commands it runs without `+`, so
+    /// both of them are checked to still be open pipes before they're used.
+    pub fn from_makeflags(flags: &str) -> Option<Client> {
+        if cfg!(windows) {
+            // Make uses a named semaphore instead of a pipe on windows
+            return None;
+        }
+        for arg in flags.words() {
+            let fds = if arg.starts_with("--jobserver-fds=") {
+                arg.slice_from("--jobserver-fds=".len())
+            } else if arg.starts_with("--jobserver-auth=") {
+                arg.slice_from("--jobserver-auth=".len())
+            } else {
+                continue
+            };
+            let fds: ~[Option<c_int>] = fds.split(',').map(|fd| from_str(fd))
+                                           .collect();
+            match fds.as_slice() {
+                [Some(read), Some(write)] => {
+                    if is_pipe(read) && is_pipe(write) {
+                        return Some(Client::new(read, write));
+                    }
+                    return None;
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Blocks until a job slot is free and takes it. This fails when make
+    /// didn't let this process use the jobserver after all (i.e. the command
+    /// wasn't marked with `+`), in which case the work shouldn't be run in
+    /// parallel.
+    pub fn acquire(&self) -> Option<Token> {
+        if !is_pipe(self.read) || !is_pipe(self.write) {
+            return None;
+        }
+        let mut byte = 0u8;
+        loop {
+            let n = unsafe {
+                libc::read(self.read, &mut byte as *mut u8 as *mut c_void, 1)
+            };
+            if n == 1 {
+                return Some(Token { client: self.clone(), byte: byte });
+            }
+            if n < 0 && os::errno() as c_int == libc::EINTR {
+                continue
+            }
+            return None;
+        }
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        if !is_pipe(self.client.write) {
+            return;
+        }
+        unsafe {
+            libc::write(self.client.write, &self.byte as *u8 as *c_void, 1);
+        }
+    }
+}
+
+// The same on every unix we support; libc doesn't export it.
+#[cfg(unix)]
+static F_GETFD: c_int = 1;
+
+/// Whether `fd` is an open descriptor for a pipe. The descriptors named in
+/// `MAKEFLAGS` may have been closed, or reused for something else entirely.
+#[cfg(unix)]
+fn is_pipe(fd: c_int) -> bool {
+    unsafe {
+        if libc::fcntl(fd, F_GETFD) == -1 {
+            return false;
+        }
+        let mut stat: libc::stat = intrinsics::uninit();
+        if libc::fstat(fd, &mut stat) == -1 {
+            return false;
+        }
+        (stat.st_mode as c_int & libc::S_IFMT) == libc::S_IFIFO
+    }
+}
+
+#[cfg(windows)]
+fn is_pipe(_fd: c_int) -> bool { false }
+
+#[cfg(test, unix)]
+mod test {
+    use super::Client;
+
+    use std::c_str::ToCStr;
+    use std::libc;
+    use std::os;
+
+    #[test]
+    fn test_from_makeflags() {
+        let pipe = os::pipe();
+        let (r, w) = (pipe.input, pipe.out);
+        assert!(Client::from_makeflags("").is_none());
+        assert!(Client::from_makeflags(format!(" -j --jobserver-fds={}", r)).is_none());
+        let flags = format!(" -j --jobserver-fds={},{}", r, w);
+        let client = Client::from_makeflags(flags).unwrap();
+        assert_eq!((client.read, client.write), (r, w));
+        let flags = format!("-j4 --jobserver-auth={},{}", r, w);
+        let client = Client::from_makeflags(flags).unwrap();
+        assert_eq!((client.read, client.write), (r, w));
+        unsafe {
+            libc::close(r);
+            libc::close(w);
+        }
+    }
+
+    #[test]
+    fn test_from_makeflags_not_a_pipe() {
+        // Descriptors that are open but not a pipe mean there's no jobserver.
+        // The descriptor is ours for the whole test, so no other test can
+        // reuse its number in the meantime.
+        let fd = "/dev/null".with_c_str(|path| unsafe {
+            libc::open(path, libc::O_RDONLY, 0)
+        });
+        assert!(fd >= 0);
+        let flags = format!(" -j --jobserver-fds={},{}", fd, fd);
+        assert!(Client::from_makeflags(flags).is_none());
+        unsafe {
+            libc::close(fd);
+        }
+    }
+
+    #[test]
+    fn test_acquire() {
+        let pipe = os::pipe();
+        let client = Client::new(pipe.input, pipe.out);
+        unsafe {
+            libc::write(pipe.out, &('+' as u8) as *u8 as *libc::c_void, 1);
+        }
+        {
+            let token = client.acquire().unwrap();
+            assert_eq!(token.byte, '+' as u8);
+        }
+        {
+            // Dropping the token put it back
+            let token = client.acquire().unwrap();
+            assert_eq!(token.byte, '+' as u8);
+        }
+        unsafe {
+            libc::close(pipe.input);
+            libc::close(pipe.out);
+        }
+    }
+}
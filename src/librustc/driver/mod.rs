@@ -11,4 +11,5 @@
 pub use syntax::diagnostic;
 
 pub mod driver;
+pub mod jobserver;
 pub mod session;
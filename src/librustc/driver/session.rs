@@ -9,10 +9,12 @@
 // except according to those terms.
 
 
+use back::codegen;
 use back::link;
 use back::target_strs;
 use back;
 use driver::driver::host_triple;
+use driver::jobserver;
 use metadata::filesearch;
 use metadata;
 use middle::lint;
@@ -29,6 +31,7 @@ use syntax::parse::token;
 use syntax;
 
 use std::hashmap::{HashMap,HashSet};
+use extra;
 
 pub struct config {
     os: abi::Os,
@@ -36,41 +39,49 @@ pub struct config {
     target_strs: target_strs::t,
     int_type: int_ty,
     uint_type: uint_ty,
+    // The oldest OSX release the outputs run on, for OSX targets
+    macos_min_version: Option<~str>,
 }
 
-pub static verbose:                 uint = 1 <<  0;
-pub static time_passes:             uint = 1 <<  1;
-pub static count_llvm_insns:        uint = 1 <<  2;
-pub static time_llvm_passes:        uint = 1 <<  3;
-pub static trans_stats:             uint = 1 <<  4;
-pub static asm_comments:            uint = 1 <<  5;
-pub static no_verify:               uint = 1 <<  6;
-pub static coherence:               uint = 1 <<  7;
-pub static borrowck_stats:          uint = 1 <<  8;
-pub static borrowck_note_pure:      uint = 1 <<  9;
-pub static borrowck_note_loan:      uint = 1 << 10;
-pub static no_landing_pads:         uint = 1 << 11;
-pub static debug_llvm:              uint = 1 << 12;
-pub static count_type_sizes:        uint = 1 << 13;
-pub static meta_stats:              uint = 1 << 14;
-pub static no_opt:                  uint = 1 << 15;
-pub static gc:                      uint = 1 << 16;
-pub static debug_info:              uint = 1 << 17;
-pub static extra_debug_info:        uint = 1 << 18;
-pub static print_link_args:         uint = 1 << 19;
-pub static no_debug_borrows:        uint = 1 << 20;
-pub static lint_llvm:               uint = 1 << 21;
-pub static print_llvm_passes:       uint = 1 << 22;
-pub static no_vectorize_loops:      uint = 1 << 23;
-pub static no_vectorize_slp:        uint = 1 << 24;
-pub static no_prepopulate_passes:   uint = 1 << 25;
-pub static use_softfp:              uint = 1 << 26;
-pub static gen_crate_map:           uint = 1 << 27;
-pub static prefer_dynamic:          uint = 1 << 28;
-pub static no_integrated_as:        uint = 1 << 29;
-pub static lto:                     uint = 1 << 30;
-
-pub fn debugging_opts_map() -> ~[(&'static str, &'static str, uint)] {
+pub static verbose:                 u64 = 1 <<  0;
+pub static time_passes:             u64 = 1 <<  1;
+pub static count_llvm_insns:        u64 = 1 <<  2;
+pub static time_llvm_passes:        u64 = 1 <<  3;
+pub static trans_stats:             u64 = 1 <<  4;
+pub static asm_comments:            u64 = 1 <<  5;
+pub static no_verify:               u64 = 1 <<  6;
+pub static coherence:               u64 = 1 <<  7;
+pub static borrowck_stats:          u64 = 1 <<  8;
+pub static borrowck_note_pure:      u64 = 1 <<  9;
+pub static borrowck_note_loan:      u64 = 1 << 10;
+pub static no_landing_pads:         u64 = 1 << 11;
+pub static debug_llvm:              u64 = 1 << 12;
+pub static count_type_sizes:        u64 = 1 << 13;
+pub static meta_stats:              u64 = 1 << 14;
+pub static no_opt:                  u64 = 1 << 15;
+pub static gc:                      u64 = 1 << 16;
+pub static debug_info:              u64 = 1 << 17;
+pub static extra_debug_info:        u64 = 1 << 18;
+pub static print_link_args:         u64 = 1 << 19;
+pub static no_debug_borrows:        u64 = 1 << 20;
+pub static lint_llvm:               u64 = 1 << 21;
+pub static print_llvm_passes:       u64 = 1 << 22;
+pub static no_vectorize_loops:      u64 = 1 << 23;
+pub static no_vectorize_slp:        u64 = 1 << 24;
+pub static no_prepopulate_passes:   u64 = 1 << 25;
+pub static use_softfp:              u64 = 1 << 26;
+pub static gen_crate_map:           u64 = 1 << 27;
+pub static prefer_dynamic:          u64 = 1 << 28;
+pub static no_integrated_as:        u64 = 1 << 29;
+pub static lto:                     u64 = 1 << 30;
+pub static merge_functions:         u64 = 1 << 31;
+pub static lto_partial:             u64 = 1 << 32;
+pub static no_morestack:            u64 = 1 << 33;
+pub static abort_on_failure:        u64 = 1 << 34;
+pub static quick_codegen:           u64 = 1 << 35;
+pub static verify_llvm_each_pass:   u64 = 1 << 36;
+
+pub fn debugging_opts_map() -> ~[(&'static str, &'static str, u64)] {
     ~[("verbose", "in general, enable more debug printouts", verbose),
      ("time-passes", "measure time of each rustc pass", time_passes),
      ("count-llvm-insns", "count where LLVM \
@@ -78,7 +89,8 @@ pub fn debugging_opts_map() -> ~[(&'static str, &'static str, uint)] {
      ("time-llvm-passes", "measure time of each LLVM pass",
       time_llvm_passes),
      ("trans-stats", "gather trans statistics", trans_stats),
-     ("asm-comments", "generate comments into the assembly (may change behavior)", asm_comments),
+     ("asm-comments", "generate comments into the assembly, including the source lines \
+                       (may change behavior)", asm_comments),
      ("no-verify", "skip LLVM verification", no_verify),
      ("coherence", "perform coherence checking", coherence),
      ("borrowck-stats", "gather borrowck statistics",  borrowck_stats),
@@ -117,15 +129,452 @@ pub fn debugging_opts_map() -> ~[(&'static str, &'static str, uint)] {
      ("no-vectorize-slp",
       "Don't run LLVM's SLP vectorization passes",
       no_vectorize_slp),
-     ("soft-float", "Generate software floating point library calls", use_softfp),
+     ("soft-float", "Deprecated, use -C soft-float", use_softfp),
      ("gen-crate-map", "Force generation of a toplevel crate map", gen_crate_map),
      ("prefer-dynamic", "Prefer dynamic linking to static linking", prefer_dynamic),
      ("no-integrated-as",
       "Use external assembler rather than LLVM's integrated one", no_integrated_as),
      ("lto", "Perform LLVM link-time optimizations", lto),
+     ("merge-functions",
+      "Merge functions with identical code with LLVM's mergefunc pass",
+      merge_functions),
+     ("lto-partial",
+      "Let -Z lto optimize the crates available as rlibs and link the others \
+       dynamically", lto_partial),
+     ("no-morestack",
+      "Leave out the split-stack prologues and libmorestack, like \
+       #[no_split_stack] on the crate", no_morestack),
+     ("abort-on-failure",
+      "Abort the process where code of the crate fails instead of unwinding, \
+       and omit landing pads. Failures inside upstream crates, std included, \
+       still unwind, so the unwinder is only left out of the link when \
+       they're all dylibs", abort_on_failure),
+     ("quick-codegen",
+      "Generate code as fast as possible: no function passes, no \
+       verification and FastISel, at the cost of its quality", quick_codegen),
+     ("verify-llvm-each-pass",
+      "Verify the LLVM IR after each optimization pass, stopping at the \
+       first pass which breaks it", verify_llvm_each_pass),
     ]
 }
 
+// How the value of a codegen or debugging option is written in `-C help`
+// and `-Z help`, according to the function which parses it
+macro_rules! cg_value_form(
+    (parse_bool) => ("");
+    (parse_opt_bool) => ("[=yes|no]");
+    ($parse:ident) => ("=val");
+)
+
+/// Declares the codegen options (`-C name=value`), which are stored in a
+/// `CodegenOptions` structure. Each option is given its type, its default
+/// value, the function of `parsers` which parses it and a description for
+/// `-C help`.
+macro_rules! cgoptions(
+    ($($opt:ident : $t:ty = ($init:expr, $parse:ident, $desc:expr)),* ,) =>
+(
+    #[deriving(Clone)]
+    pub struct CodegenOptions { $($opt: $t),* }
+
+    pub fn basic_codegen_options() -> CodegenOptions {
+        CodegenOptions { $($opt: $init),* }
+    }
+
+    pub type CodegenSetter = fn(&mut CodegenOptions, v: Option<&str>) -> bool;
+    pub static CG_OPTIONS: &'static [(&'static str, CodegenSetter,
+                                      &'static str, &'static str)] =
+        &'static [ $( (stringify!($opt), cgsetters::$opt,
+                       cg_value_form!($parse), $desc) ),* ];
+
+    mod cgsetters {
+        use super::CodegenOptions;
+        use super::parsers;
+
+        $(
+            pub fn $opt(cg: &mut CodegenOptions, v: Option<&str>) -> bool {
+                parsers::$parse(&mut cg.$opt, v)
+            }
+        )*
+    }
+) )
+
+/// Declares the debugging options which take a value (`-Z name=value`),
+/// which are stored in a `DebuggingValues` structure the same way as the
+/// codegen options are. The debugging options which are only turned on are
+/// bits of `debugging_opts` instead (see `debugging_opts_map`).
+macro_rules! dvoptions(
+    ($($opt:ident : $t:ty = ($init:expr, $parse:ident, $desc:expr)),* ,) =>
+(
+    #[deriving(Clone)]
+    pub struct DebuggingValues { $($opt: $t),* }
+
+    pub fn basic_debugging_values() -> DebuggingValues {
+        DebuggingValues { $($opt: $init),* }
+    }
+
+    pub type DebuggingSetter = fn(&mut DebuggingValues, v: Option<&str>) -> bool;
+    pub static DV_OPTIONS: &'static [(&'static str, DebuggingSetter,
+                                      &'static str, &'static str)] =
+        &'static [ $( (stringify!($opt), dvsetters::$opt,
+                       cg_value_form!($parse), $desc) ),* ];
+
+    mod dvsetters {
+        use super::DebuggingValues;
+        use super::parsers;
+
+        $(
+            pub fn $opt(dv: &mut DebuggingValues, v: Option<&str>) -> bool {
+                parsers::$parse(&mut dv.$opt, v)
+            }
+        )*
+    }
+) )
+
+// The functions which parse the values of the codegen and debugging options
+// into their slots, returning whether the value was valid
+mod parsers {
+    use super::{RelroLevel, RelroOff, RelroPartial, RelroFull};
+    use super::{StackProtector, SspNone, SspBasic, SspStrong, SspAll};
+    use super::{Strip, StripNone, StripDebuginfo, StripSymbols};
+    use super::{SplitDebuginfo, SplitDebuginfoOff, SplitDebuginfoPacked};
+    use super::SplitDebuginfoUnpacked;
+    use super::{DebuginfoFormat, DebuginfoDwarf, DebuginfoCodeView};
+    use super::{AsmSyntax, AsmSyntaxAtt, AsmSyntaxIntel};
+    use super::{Subsystem, SubsystemConsole, SubsystemWindows};
+    use super::{TlsModel, TlsGlobalDynamic, TlsLocalDynamic};
+    use super::{TlsInitialExec, TlsLocalExec};
+
+    pub fn parse_bool(slot: &mut bool, v: Option<&str>) -> bool {
+        match v {
+            Some(..) => false,
+            None => { *slot = true; true }
+        }
+    }
+
+    pub fn parse_opt_string(slot: &mut Option<~str>, v: Option<&str>) -> bool {
+        match v {
+            Some(s) => { *slot = Some(s.to_owned()); true },
+            None => false,
+        }
+    }
+
+    pub fn parse_string(slot: &mut ~str, v: Option<&str>) -> bool {
+        match v {
+            Some(s) => { *slot = s.to_owned(); true },
+            None => false,
+        }
+    }
+
+    pub fn parse_opt_bool(slot: &mut Option<bool>, v: Option<&str>) -> bool {
+        match v {
+            Some("y") | Some("yes") | Some("on") => { *slot = Some(true); true }
+            Some("n") | Some("no") | Some("off") => { *slot = Some(false); true }
+            Some(..) => false,
+            None => { *slot = Some(true); true }
+        }
+    }
+
+    pub fn parse_relro_level(slot: &mut Option<RelroLevel>,
+                             v: Option<&str>) -> bool {
+        match v {
+            Some("off") => { *slot = Some(RelroOff); true }
+            Some("partial") => { *slot = Some(RelroPartial); true }
+            Some("full") => { *slot = Some(RelroFull); true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_stack_protector(slot: &mut StackProtector,
+                                 v: Option<&str>) -> bool {
+        match v {
+            Some("none") => { *slot = SspNone; true }
+            Some("basic") => { *slot = SspBasic; true }
+            Some("strong") => { *slot = SspStrong; true }
+            Some("all") => { *slot = SspAll; true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_strip(slot: &mut Strip, v: Option<&str>) -> bool {
+        match v {
+            Some("none") => { *slot = StripNone; true }
+            Some("debuginfo") => { *slot = StripDebuginfo; true }
+            Some("symbols") => { *slot = StripSymbols; true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_subsystem(slot: &mut Subsystem, v: Option<&str>) -> bool {
+        match v {
+            Some("console") => { *slot = SubsystemConsole; true }
+            Some("windows") => { *slot = SubsystemWindows; true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_split_debuginfo(slot: &mut Option<SplitDebuginfo>,
+                                 v: Option<&str>) -> bool {
+        match v {
+            Some("off") => { *slot = Some(SplitDebuginfoOff); true }
+            Some("packed") => { *slot = Some(SplitDebuginfoPacked); true }
+            Some("unpacked") => {
+                *slot = Some(SplitDebuginfoUnpacked);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn parse_debuginfo_level(slot: &mut Option<uint>,
+                                 v: Option<&str>) -> bool {
+        match v {
+            Some("0") => { *slot = Some(0); true }
+            Some("1") => { *slot = Some(1); true }
+            Some("2") => { *slot = Some(2); true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_debuginfo_format(slot: &mut DebuginfoFormat,
+                                  v: Option<&str>) -> bool {
+        match v {
+            Some("dwarf") => { *slot = DebuginfoDwarf; true }
+            Some("codeview") => { *slot = DebuginfoCodeView; true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_asm_syntax(slot: &mut AsmSyntax, v: Option<&str>) -> bool {
+        match v {
+            Some("att") => { *slot = AsmSyntaxAtt; true }
+            Some("intel") => { *slot = AsmSyntaxIntel; true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_opt_list(slot: &mut Option<~[~str]>, v: Option<&str>) -> bool {
+        match v {
+            Some("") => { *slot = Some(~[]); true }
+            Some(s) => {
+                *slot = Some(s.split(',').map(|s| s.to_owned()).collect());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn parse_list(slot: &mut ~[~str], v: Option<&str>) -> bool {
+        match v {
+            Some(s) => {
+                for s in s.split(',') {
+                    slot.push(s.to_owned());
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn parse_opt_path(slot: &mut Option<Path>, v: Option<&str>) -> bool {
+        match v {
+            Some(s) => { *slot = Some(Path::new(s)); true },
+            None => false,
+        }
+    }
+
+    pub fn parse_json(slot: &mut bool, v: Option<&str>) -> bool {
+        match v {
+            Some("json") => { *slot = true; true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_tls_model(slot: &mut TlsModel, v: Option<&str>) -> bool {
+        match v {
+            Some("global-dynamic") => { *slot = TlsGlobalDynamic; true }
+            Some("local-dynamic") => { *slot = TlsLocalDynamic; true }
+            Some("initial-exec") => { *slot = TlsInitialExec; true }
+            Some("local-exec") => { *slot = TlsLocalExec; true }
+            _ => false,
+        }
+    }
+
+    pub fn parse_dwarf_version(slot: &mut Option<uint>,
+                               v: Option<&str>) -> bool {
+        match v {
+            Some("2") => { *slot = Some(2); true }
+            Some("3") => { *slot = Some(3); true }
+            Some("4") => { *slot = Some(4); true }
+            _ => false,
+        }
+    }
+}
+
+cgoptions!(
+    version_script: Option<~str> = (None, parse_opt_string,
+        "linker version script to use for dylibs instead of the generated one"),
+    soname: Option<~str> = (None, parse_opt_string,
+        "SONAME to record in dylibs instead of their file name"),
+    no_hash_in_filename: bool = (false, parse_bool,
+        "leave the crate hash out of the file names of libraries"),
+    pkg_config: bool = (false, parse_bool,
+        "write a pkg-config file describing staticlib and dylib outputs"),
+    pkg_config_libdir: Option<~str> = (None, parse_opt_string,
+        "library directory to record in pkg-config files"),
+    link_objects: ~[~str] = (~[], parse_list,
+        "object files and archives to link in, separated by commas"),
+    check_native_libs: bool = (false, parse_bool,
+        "check that native libraries exist before invoking the linker"),
+    prefer_dynamic: ~[~str] = (~[], parse_list,
+        "upstream crates to link dynamically when possible, separated by commas"),
+    prefer_static: ~[~str] = (~[], parse_list,
+        "upstream crates to link statically when possible, separated by commas"),
+    static_executable: bool = (false, parse_bool,
+        "link executables fully statically, without any shared libraries"),
+    default_linker_libraries: Option<bool> = (None, parse_opt_bool,
+        "whether to link the C compiler's default libraries (yes or no)"),
+    runtime_libraries: Option<~[~str]> = (None, parse_opt_list,
+        "the runtime libraries to link instead of the target's, separated by commas"),
+    rpath: Option<bool> = (None, parse_opt_bool,
+        "whether to emit rpaths to upstream dylibs (yes or no)"),
+    relative_rpath: bool = (false, parse_bool,
+        "only emit rpaths relative to the output, for relocatable bundles"),
+    relro_level: Option<RelroLevel> = (None, parse_relro_level,
+        "how much of the relocated data to make read-only (off, partial or full)"),
+    pie: bool = (false, parse_bool,
+        "link executables as position independent executables"),
+    entry: Option<~str> = (None, parse_opt_string,
+        "the symbol executables start at instead of the C runtime's entry point"),
+    no_startfiles: bool = (false, parse_bool,
+        "link executables without the C runtime's startup objects (crt1.o etc.)"),
+    link_wrapper: Option<~str> = (None, parse_opt_string,
+        "a program to run the final link through, given the linker and its \
+         arguments (the linker is still probed locally)"),
+    stack_protector: StackProtector = (SspNone, parse_stack_protector,
+        "which functions get stack canaries (none, basic, strong or all)"),
+    force_frame_pointers: Option<bool> = (None, parse_opt_bool,
+        "whether to keep frame pointers, which profilers rely on (yes or no)"),
+    soft_float: bool = (false, parse_bool,
+        "generate software floating point library calls (changes the float ABI)"),
+    function_sections: bool = (false, parse_bool,
+        "place each function in its own section, so that executables drop unused ones"),
+    data_sections: bool = (false, parse_bool,
+        "place each static in its own section, so that executables drop unused ones"),
+    icf: bool = (false, parse_bool,
+        "fold identical functions at link time (needs the gold linker)"),
+    strip: Strip = (StripNone, parse_strip,
+        "what to strip from the output (none, debuginfo or symbols)"),
+    split_debuginfo: Option<SplitDebuginfo> = (None, parse_split_debuginfo,
+        "where debug info goes (off, packed or unpacked)"),
+    debuginfo: Option<uint> = (None, parse_debuginfo_level,
+        "how much debug info to emit (0: none, 1: line tables only, 2: full)"),
+    debuginfo_format: DebuginfoFormat = (DebuginfoDwarf, parse_debuginfo_format,
+        "the format of Windows debug info (dwarf, or codeview for a .pdb)"),
+    build_id: Option<bool> = (None, parse_opt_bool,
+        "whether ELF outputs carry a GNU build id (yes or no)"),
+    no_gdb_scripts: bool = (false, parse_bool,
+        "don't have the debug info point gdb at the Rust pretty-printers"),
+    asm_syntax: AsmSyntax = (AsmSyntaxAtt, parse_asm_syntax,
+        "the syntax of emitted x86 assembly (att or intel)"),
+    universal_archs: ~[~str] = (~[], parse_list,
+        "architectures to build a universal OSX binary for, separated by commas"),
+    link_resource: ~[~str] = (~[], parse_list,
+        "Windows resource files (.res or .rc) to link in, separated by commas"),
+    manifest: Option<~str> = (None, parse_opt_string,
+        "application manifest to embed in Windows executables and dylibs"),
+    rc: Option<~str> = (None, parse_opt_string,
+        "the resource compiler for -C link-resource and -C manifest, windres \
+         or MSVC's rc (default: the windres which goes with the C compiler)"),
+    subsystem: Subsystem = (SubsystemConsole, parse_subsystem,
+        "the Windows subsystem executables are linked for (console or windows)"),
+    dynamic_base: Option<bool> = (None, parse_opt_bool,
+        "whether Windows outputs may be loaded at a random address, like \
+         /DYNAMICBASE (yes or no)"),
+    nx_compat: Option<bool> = (None, parse_opt_bool,
+        "whether Windows outputs run with data execution prevention, like \
+         /NXCOMPAT (yes or no)"),
+    high_entropy_va: Option<bool> = (None, parse_opt_bool,
+        "whether 64-bit Windows outputs use the whole address space for ASLR, \
+         like /HIGHENTROPYVA (yes or no)"),
+    macos_min_version: Option<~str> = (None, parse_opt_string,
+        "the oldest OSX release to run on, like 10.7 (default: $MACOSX_DEPLOYMENT_TARGET)"),
+    embed_bitcode: Option<bool> = (None, parse_opt_bool,
+        "whether objects carry the bitcode of their module in a section (yes or no)"),
+    bitcode_only_rlib: bool = (false, parse_bool,
+        "leave the machine code out of rlibs, to be generated from their bitcode downstream"),
+)
+
+dvoptions!(
+    tls_model: TlsModel = (TlsGlobalDynamic, parse_tls_model,
+        "the model of #[thread_local] statics (global-dynamic, local-dynamic, \
+         initial-exec or local-exec)"),
+    dwarf_version: Option<uint> = (None, parse_dwarf_version,
+        "the version of the emitted DWARF (2, 3 or 4)"),
+    print_fn: Option<~str> = (None, parse_opt_string,
+        "only emit the LLVM IR or assembly of the functions whose demangled \
+         names contain this"),
+    time_passes: bool = (false, parse_json,
+        "measure time of each rustc pass, printing one record per pass in the \
+         given format (json)"),
+    self_profile: Option<Path> = (None, parse_opt_path,
+        "append the time spent on each function, output file, archive and \
+         linker run to this file"),
+    llvm_stats: Option<Path> = (None, parse_opt_path,
+        "append the LLVM pass timings and statistics to this file"),
+    lto_keep_symbols: Option<Path> = (None, parse_opt_path,
+        "keep the symbols listed in this file, one per line, from being \
+         internalized by LTO"),
+    codegen_backend: ~str = (codegen::DEFAULT_BACKEND.to_owned(), parse_string,
+        "the backend generating the output files (default: llvm)"),
+)
+
+#[deriving(Clone, Eq)]
+pub enum AsmSyntax {
+    AsmSyntaxAtt,
+    AsmSyntaxIntel,
+}
+
+#[deriving(Clone, Eq)]
+pub enum DebuginfoFormat {
+    DebuginfoDwarf,
+    DebuginfoCodeView, // read by the Windows debuggers, from a .pdb file
+}
+
+#[deriving(Clone, Eq)]
+pub enum SplitDebuginfo {
+    SplitDebuginfoOff, // in the output itself
+    SplitDebuginfoPacked, // in a .dSYM bundle or a .dwp file
+    SplitDebuginfoUnpacked, // in the object files or in .dwo files
+}
+
+#[deriving(Clone, Eq)]
+pub enum Strip {
+    StripNone,
+    StripDebuginfo, // the debugging sections
+    StripSymbols, // the debugging sections and the symbol table
+}
+
+#[deriving(Clone, Eq)]
+pub enum Subsystem {
+    SubsystemConsole,
+    SubsystemWindows, // GUI applications, which don't get a console window
+}
+
+#[deriving(Clone, Eq)]
+pub enum StackProtector {
+    SspNone,
+    SspBasic, // ssp, functions with character arrays
+    SspStrong, // sspstrong, functions with any arrays or address taken locals
+    SspAll, // sspreq, every function
+}
+
+#[deriving(Clone, Eq)]
+pub enum RelroLevel {
+    RelroOff,
+    RelroPartial, // -z relro
+    RelroFull, // -z relro -z now
+}
+
 #[deriving(Clone, Eq)]
 pub enum OptLevel {
     No, // -O0
@@ -152,8 +601,16 @@ pub struct options {
     addl_lib_search_paths: @mut HashSet<Path>, // This is mutable for rustpkg, which
                                                // updates search paths based on the
                                                // parsed code
+    // Paths given with `-L [KIND=]PATH` and `--framework-path PATH`, in the
+    // order of the command line. Those searched for crates are also among the
+    // addl_lib_search_paths.
+    search_paths: ~[(SearchPathKind, Path)],
+    // Native libraries given with `-l [KIND=]NAME`
+    cmdline_libs: ~[(~str, metadata::cstore::NativeLibaryKind)],
     ar: Option<~str>,
-    linker: Option<~str>,
+    // The program given with `--linker`, followed by the arguments it's always
+    // run with
+    linker: Option<~[~str]>,
     linker_args: ~[~str],
     maybe_sysroot: Option<@Path>,
     target_triple: ~str,
@@ -168,10 +625,29 @@ pub struct options {
     test: bool,
     parse_only: bool,
     no_trans: bool,
-    debugging_opts: uint,
+    print_exported_symbols: bool,
+    // Prints the commands which would link the output instead of running them,
+    // given with `--print link-args` (unlike `-Z print-link-args`, which
+    // prints the arguments of the linker it runs)
+    dry_run_link: bool,
+    emit_c_header: bool,
+    emit_metadata_json: bool,
+    emit_dep_graph: bool,
+    debugging_opts: u64,
+    // The debugging options given with a value (`-Z name=value`)
+    debugging_values: DebuggingValues,
+    cg: CodegenOptions,
     android_cross_path: Option<~str>,
 }
 
+#[deriving(Clone, Eq)]
+pub enum TlsModel {
+    TlsGlobalDynamic, // the default, works everywhere
+    TlsLocalDynamic, // only for statics of the module being compiled
+    TlsInitialExec, // not for dylibs which are loaded with dlopen
+    TlsLocalExec, // only in executables, for their own statics
+}
+
 pub struct crate_metadata {
     name: ~str,
     data: ~[u8]
@@ -188,12 +664,22 @@ pub enum EntryFnType {
     EntryNone,
 }
 
+/// What a directory given with `-L KIND=PATH` is searched for
+#[deriving(Eq, Clone)]
+pub enum SearchPathKind {
+    SearchAll,       // crates and native libraries, the default
+    SearchCrate,     // crates only (`crate=` or `dependency=`)
+    SearchNative,    // native libraries only
+    SearchFramework, // OSX frameworks only, like `--framework-path`
+}
+
 #[deriving(Eq, Clone)]
 pub enum OutputStyle {
     OutputExecutable,
     OutputDylib,
     OutputRlib,
     OutputStaticlib,
+    OutputCdylib,
 }
 
 pub struct Session_ {
@@ -212,10 +698,40 @@ pub struct Session_ {
     lints: @mut HashMap<ast::NodeId, ~[(lint::lint, codemap::Span, ~str)]>,
     node_id: @mut ast::NodeId,
     outputs: @mut ~[OutputStyle],
+    // The files written by linking, which are those `-C universal-archs`
+    // combines
+    output_files: @mut ~[Path],
+    // Whether functions go without the split-stack prologue, which calls
+    // __morestack to grow the stack, because of `-Z no-morestack` or the
+    // crate's `#[no_split_stack]`
+    no_split_stack: @mut bool,
+    profile_events: @mut ~[ProfileEvent],
+    // The jobserver of the make running us, which any task doing work in
+    // parallel with the compiler has to take a token from first
+    jobserver: Option<jobserver::Client>,
+    // The backend selected with `-Z codegen-backend`, which owns whatever it
+    // needs to generate code, such as LLVM's target machine
+    codegen_backend: @codegen::CodegenBackend,
+    // What's been found out about the C compiler drivers run, by their command
+    // line
+    cc_probes: @mut HashMap<~str, @mut link::CcProbe>,
 }
 
 pub type Session = @Session_;
 
+/// Something that took time during codegen or linking, as recorded for
+/// `-Z self-profile`
+pub struct ProfileEvent {
+    // What sort of work this was, e.g. "archive" or "linker"
+    kind: &'static str,
+    // What the work was done on: a function, an output file or a command
+    name: ~str,
+    // When the work started, in seconds from an arbitrary point
+    start: f64,
+    // The wall time of the work in seconds
+    time: f64,
+}
+
 impl Session_ {
     pub fn span_fatal(&self, sp: Span, msg: &str) -> ! {
         self.span_diagnostic.span_fatal(sp, msg)
@@ -289,8 +805,8 @@ impl Session_ {
     pub fn diagnostic(&self) -> @mut diagnostic::span_handler {
         self.span_diagnostic
     }
-    pub fn debugging_opt(&self, opt: uint) -> bool {
-        (self.opts.debugging_opts & opt) != 0u
+    pub fn debugging_opt(&self, opt: u64) -> bool {
+        (self.opts.debugging_opts & opt) != 0
     }
     // This exists to help with refactoring to eliminate impossible
     // cases later on
@@ -339,15 +855,129 @@ impl Session_ {
     pub fn gen_crate_map(&self) -> bool {
         self.debugging_opt(gen_crate_map)
     }
+    pub fn no_landing_pads(&self) -> bool {
+        self.debugging_opt(no_landing_pads)
+    }
     pub fn prefer_dynamic(&self) -> bool {
         self.debugging_opt(prefer_dynamic)
     }
     pub fn no_integrated_as(&self) -> bool {
         self.debugging_opt(no_integrated_as)
     }
+    pub fn split_debuginfo(&self) -> SplitDebuginfo {
+        match self.opts.cg.split_debuginfo {
+            Some(split) => split,
+            // The OSX linker never copies debug info into its output, which
+            // is why dsymutil is run by default
+            None if self.targ_cfg.os == abi::OsMacos => SplitDebuginfoPacked,
+            None => SplitDebuginfoOff,
+        }
+    }
+    // Whether the DWARF of ELF objects is split off into a .dwo file
+    pub fn split_dwarf(&self) -> bool {
+        self.opts.debuginfo && self.split_debuginfo() != SplitDebuginfoOff &&
+            match self.targ_cfg.os {
+                abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => true,
+                abi::OsMacos | abi::OsWin32 => false,
+            }
+    }
+    // Whether only the functions and the lines of their code are described,
+    // for `-C debuginfo=1`
+    pub fn line_tables_only(&self) -> bool {
+        self.opts.debuginfo && self.opts.cg.debuginfo == Some(1)
+    }
+    // Whether the debug info is CodeView rather than DWARF, which is only
+    // emitted for Windows targets
+    pub fn codeview(&self) -> bool {
+        self.opts.debuginfo && self.targ_cfg.os == abi::OsWin32 &&
+            self.opts.cg.debuginfo_format == DebuginfoCodeView
+    }
     pub fn lto(&self) -> bool {
         self.debugging_opt(lto)
     }
+    // Whether the rlib being built leaves out its machine code, which
+    // `-C bitcode-only-rlib` has no effect on for the other outputs
+    pub fn bitcode_only_rlib(&self) -> bool {
+        self.opts.cg.bitcode_only_rlib &&
+            self.outputs.iter().any(|&o| o == OutputRlib)
+    }
+    pub fn merge_functions(&self) -> bool {
+        self.debugging_opt(merge_functions)
+    }
+    pub fn lto_partial(&self) -> bool { self.debugging_opt(lto_partial) }
+    pub fn no_morestack(&self) -> bool { self.debugging_opt(no_morestack) }
+    pub fn abort_on_failure(&self) -> bool {
+        self.debugging_opt(abort_on_failure)
+    }
+    pub fn quick_codegen(&self) -> bool { self.debugging_opt(quick_codegen) }
+    pub fn verify_llvm_each_pass(&self) -> bool {
+        self.debugging_opt(verify_llvm_each_pass)
+    }
+    pub fn self_profile(&self) -> bool {
+        self.opts.debugging_values.self_profile.is_some()
+    }
+    // Whether the metadata module has to be written to an object file. Only
+    // dylibs link it in, but the object is only skipped for the other outputs
+    // under `-Z quick-codegen`.
+    pub fn needs_metadata_object(&self) -> bool {
+        !self.quick_codegen() ||
+            self.outputs.iter().any(|&o| o == OutputDylib)
+    }
+
+    /// Runs `f`, recording its wall time as an event for `-Z self-profile`
+    pub fn profile<T>(&self, kind: &'static str, name: &str, f: || -> T) -> T {
+        if !self.self_profile() { return f(); }
+
+        let start = extra::time::precise_time_s();
+        let rv = f();
+        let end = extra::time::precise_time_s();
+        self.record_profile_event(kind, name, start, end - start);
+        rv
+    }
+
+    /// Records an event for `-Z self-profile` which was timed elsewhere, e.g.
+    /// by another task
+    pub fn record_profile_event(&self, kind: &'static str, name: &str,
+                                start: f64, time: f64) {
+        if !self.self_profile() { return; }
+        self.profile_events.push(ProfileEvent {
+            kind: kind,
+            name: name.to_owned(),
+            start: start,
+            time: time,
+        });
+    }
+
+    /// The directories given on the command line to search for native
+    /// libraries, in the order they were given. They come before the
+    /// workspaces of RUST_PATH and the system directories.
+    pub fn native_search_paths(&self) -> ~[Path] {
+        let mut paths = self.search_paths(|kind| {
+            kind == SearchAll || kind == SearchNative
+        });
+        // Those added by tools like rustpkg after the command line was parsed
+        let mut added = ~[];
+        for path in self.opts.addl_lib_search_paths.iter() {
+            if !self.opts.search_paths.iter().any(|&(_, ref p)| p == path) {
+                added.push(path.clone());
+            }
+        }
+        extra::sort::quick_sort(added, |a, b| a.as_vec() <= b.as_vec());
+        paths.push_all_move(added);
+        paths
+    }
+
+    /// The directories given with `--framework-path` or `-L framework=` to
+    /// search for frameworks, in order, before the system ones
+    pub fn framework_search_paths(&self) -> ~[Path] {
+        self.search_paths(|kind| kind == SearchFramework)
+    }
+
+    fn search_paths(&self, pred: |SearchPathKind| -> bool) -> ~[Path] {
+        self.opts.search_paths.iter().filter_map(|&(kind, ref path)| {
+            if pred(kind) { Some(path.clone()) } else { None }
+        }).collect()
+    }
 
     // pointless function, now...
     pub fn str_of(&self, id: ast::Ident) -> @str {
@@ -379,6 +1009,8 @@ pub fn basic_options() -> @options {
         save_temps: false,
         output_type: link::output_type_exe,
         addl_lib_search_paths: @mut HashSet::new(),
+        search_paths: ~[],
+        cmdline_libs: ~[],
         ar: None,
         linker: None,
         linker_args: ~[],
@@ -391,7 +1023,14 @@ pub fn basic_options() -> @options {
         test: false,
         parse_only: false,
         no_trans: false,
-        debugging_opts: 0u,
+        print_exported_symbols: false,
+        dry_run_link: false,
+        emit_c_header: false,
+        emit_metadata_json: false,
+        emit_dep_graph: false,
+        debugging_opts: 0,
+        debugging_values: basic_debugging_values(),
+        cg: basic_codegen_options(),
         android_cross_path: None,
     }
 }
@@ -405,16 +1044,29 @@ pub fn building_library(options: &options, crate: &ast::Crate) -> bool {
     for output in options.outputs.iter() {
         match *output {
             OutputExecutable => {}
-            OutputStaticlib | OutputDylib | OutputRlib => return true
+            OutputStaticlib | OutputDylib | OutputRlib |
+            OutputCdylib => return true
         }
     }
     if options.test { return false }
     match syntax::attr::first_attr_value_str_by_name(crate.attrs, "crate_type") {
-        Some(s) => "lib" == s || "rlib" == s || "dylib" == s || "staticlib" == s,
+        Some(s) => "lib" == s || "rlib" == s || "dylib" == s ||
+                   "staticlib" == s || "cdylib" == s,
         _ => false
     }
 }
 
+/// The name of an output type, as given with `#[crate_type]`
+pub fn output_style_name(output: OutputStyle) -> &'static str {
+    match output {
+        OutputExecutable => "bin",
+        OutputDylib => "dylib",
+        OutputRlib => "rlib",
+        OutputStaticlib => "staticlib",
+        OutputCdylib => "cdylib",
+    }
+}
+
 pub fn collect_outputs(options: &options, crate: &ast::Crate) -> ~[OutputStyle] {
     let mut base = options.outputs.clone();
     let mut iter = crate.attrs.iter().filter_map(|a| {
@@ -424,6 +1076,7 @@ pub fn collect_outputs(options: &options, crate: &ast::Crate) -> ~[OutputStyle]
                 Some(n) if "dylib" == n => Some(OutputDylib),
                 Some(n) if "lib" == n => Some(OutputDylib),
                 Some(n) if "staticlib" == n => Some(OutputStaticlib),
+                Some(n) if "cdylib" == n => Some(OutputCdylib),
                 Some(n) if "bin" == n => Some(OutputExecutable),
                 _ => None
             }
@@ -30,7 +30,8 @@ use driver::driver::{host_triple, optgroups, early_error};
 use driver::driver::{str_input, file_input, build_session_options};
 use driver::driver::{build_session, build_configuration, parse_pretty};
 use driver::driver::{PpMode, pretty_print_input, list_metadata};
-use driver::driver::{compile_input};
+use driver::driver::{compile_input, compile_universal, is_link_object};
+use driver::driver::{is_library_file, write_library_metadata_json};
 use driver::session;
 use middle::lint;
 
@@ -92,7 +93,11 @@ pub mod front {
 
 pub mod back {
     pub mod archive;
+    pub mod c_header;
+    pub mod codegen;
+    pub mod dep_graph;
     pub mod link;
+    pub mod metadata_json;
     pub mod abi;
     pub mod upcall;
     pub mod arm;
@@ -102,6 +107,7 @@ pub mod back {
     pub mod rpath;
     pub mod target_strs;
     pub mod lto;
+    pub mod universal;
 }
 
 pub mod metadata;
@@ -144,11 +150,15 @@ pub fn version(argv0: &str) {
 }
 
 pub fn usage(argv0: &str) {
-    let message = format!("Usage: {} [OPTIONS] INPUT", argv0);
+    let message = format!("Usage: {} [OPTIONS] INPUT [OBJECTS]", argv0);
     println!("{}\n\
 Additional help:
     -W help             Print 'lint' options and default settings
-    -Z help             Print internal options for debugging rustc\n",
+    -Z help             Print internal options for debugging rustc
+
+Options separated by whitespace in the RUSTFLAGS environment variable are
+added to those of the command line, whose values take precedence for options
+which can only be given once.\n",
               groups::usage(message, optgroups()));
 }
 
@@ -200,6 +210,16 @@ pub fn describe_debug_flags() {
             }
         }
     }
+    for &(name, _, value, desc) in session::DV_OPTIONS.iter() {
+        println!("    -Z {:>20s} -- {}", name.replace("_", "-") + value, desc);
+    }
+}
+
+pub fn describe_codegen_flags() {
+    println("\nAvailable codegen options:\n");
+    for &(name, _, value, desc) in session::CG_OPTIONS.iter() {
+        println!("    -C {:>25s} -- {}", name.replace("_", "-") + value, desc);
+    }
 }
 
 pub fn run_compiler(args: &[~str], demitter: @diagnostic::Emitter) {
@@ -239,19 +259,40 @@ pub fn run_compiler(args: &[~str], demitter: @diagnostic::Emitter) {
         return;
     }
 
-    if matches.opt_str("passes") == Some(~"list") {
+    let cg_flags = matches.opt_strs("C");
+    if cg_flags.iter().any(|x| x == &~"help") {
+        describe_codegen_flags();
+        return;
+    }
+
+    let print_infos = matches.opt_strs("print");
+    if matches.opt_str("passes") == Some(~"list") ||
+       print_infos.iter().any(|x| x == &~"passes") {
         unsafe { lib::llvm::llvm::LLVMRustPrintPasses(); }
         return;
     }
+    if print_infos.iter().any(|x| x == &~"llvm-args") {
+        unsafe { lib::llvm::llvm::LLVMRustPrintLLVMOptions(); }
+        return;
+    }
 
     if matches.opt_present("v") || matches.opt_present("version") {
         version(binary);
         return;
     }
-    let input = match matches.free.len() {
+
+    if matches.opt_present("demangle") {
+        let input = str::from_utf8_owned(io::stdin().read_to_end());
+        print(back::link::demangle_symbols(input));
+        return;
+    }
+
+    let free = matches.free.iter().filter(|arg| !is_link_object(arg.as_slice()))
+                       .to_owned_vec();
+    let input = match free.len() {
       0u => early_error(demitter, "no input filename given"),
       1u => {
-        let ifile = matches.free[0].as_slice();
+        let ifile = free[0].as_slice();
         if "-" == ifile {
             let src = str::from_utf8_owned(io::stdin().read_to_end());
             str_input(src.to_managed())
@@ -289,7 +330,19 @@ pub fn run_compiler(args: &[~str], demitter: @diagnostic::Emitter) {
         }
         return;
     }
+    match input {
+      file_input(ref ifile) if sopts.emit_metadata_json &&
+                               is_library_file(sess, ifile) => {
+        write_library_metadata_json(sess, ifile, &odir, &ofile);
+        return;
+      }
+      _ => {}
+    }
 
+    if !sopts.cg.universal_archs.is_empty() {
+        compile_universal(sopts, demitter, &input, &odir, &ofile);
+        return;
+    }
     compile_input(sess, cfg, &input, &odir, &ofile);
 }
 
@@ -214,6 +214,15 @@ pub enum RelocMode {
     RelocDynamicNoPic = 3,
 }
 
+#[repr(C)]
+pub enum ThreadLocalMode {
+    NotThreadLocal = 0,
+    GeneralDynamicTLSModel = 1,
+    LocalDynamicTLSModel = 2,
+    InitialExecTLSModel = 3,
+    LocalExecTLSModel = 4,
+}
+
 #[repr(C)]
 pub enum CodeGenModel {
     CodeModelDefault = 0,
@@ -257,6 +266,8 @@ pub enum Pass_opaque {}
 pub type PassRef = *Pass_opaque;
 pub enum TargetMachine_opaque {}
 pub type TargetMachineRef = *TargetMachine_opaque;
+pub enum TargetLibraryInfo_opaque {}
+pub type TargetLibraryInfoRef = *TargetLibraryInfo_opaque;
 
 pub mod debuginfo {
     use super::{ValueRef};
@@ -300,7 +311,7 @@ pub mod llvm {
     use super::{Bool, BuilderRef, ContextRef, MemoryBufferRef, ModuleRef};
     use super::{ObjectFileRef, Opcode, PassManagerRef, PassManagerBuilderRef};
     use super::{SectionIteratorRef, TargetDataRef, TypeKind, TypeRef, UseRef};
-    use super::{ValueRef, TargetMachineRef, FileType};
+    use super::{ValueRef, TargetMachineRef, TargetLibraryInfoRef, FileType};
     use super::{CodeGenModel, RelocMode, CodeGenOptLevel};
     use super::debuginfo::*;
     use std::libc::{c_char, c_int, c_longlong, c_ushort, c_uint, c_ulonglong,
@@ -674,6 +685,8 @@ pub mod llvm {
                                          ConstantVal: ValueRef);
         pub fn LLVMIsThreadLocal(GlobalVar: ValueRef) -> Bool;
         pub fn LLVMSetThreadLocal(GlobalVar: ValueRef, IsThreadLocal: Bool);
+        pub fn LLVMSetThreadLocalMode(GlobalVar: ValueRef,
+                                      Mode: ThreadLocalMode);
         pub fn LLVMIsGlobalConstant(GlobalVar: ValueRef) -> Bool;
         pub fn LLVMSetGlobalConstant(GlobalVar: ValueRef, IsConstant: Bool);
 
@@ -712,6 +725,8 @@ pub mod llvm {
         pub fn LLVMRemoveReturnAttribute(Fn: ValueRef, PA: c_uint);
 
         pub fn LLVMAddColdAttribute(Fn: ValueRef);
+        pub fn LLVMAddMinSizeAttribute(Fn: ValueRef);
+        pub fn LLVMAddStackProtectStrongAttribute(Fn: ValueRef);
 
         pub fn LLVMRemoveFunctionAttr(Fn: ValueRef,
                                       PA: c_ulonglong,
@@ -1460,6 +1475,9 @@ pub mod llvm {
         /// Print the pass timings since static dtors aren't picking them up.
         pub fn LLVMRustPrintPassTimings();
 
+        /// Appends the pass timings and the statistics counters to a file
+        pub fn LLVMRustWritePassTimingsAndStats(Path: *c_char) -> bool;
+
         pub fn LLVMRustStartMultithreading() -> bool;
 
         pub fn LLVMStructCreateNamed(C: ContextRef, Name: *c_char) -> TypeRef;
@@ -1712,6 +1730,11 @@ pub mod llvm {
         pub fn LLVMInitializeMipsAsmParser();
 
         pub fn LLVMRustAddPass(PM: PassManagerRef, Pass: *c_char) -> bool;
+        /// Creates pass managers which verify the IR after each of their
+        /// passes, and abort naming the first one which breaks it.
+        pub fn LLVMRustCreateVerifyingPassManager() -> PassManagerRef;
+        pub fn LLVMRustCreateVerifyingFunctionPassManager(M: ModuleRef)
+                                                          -> PassManagerRef;
         pub fn LLVMRustCreateTargetMachine(Triple: *c_char,
                                            CPU: *c_char,
                                            Features: *c_char,
@@ -1719,25 +1742,38 @@ pub mod llvm {
                                            Reloc: RelocMode,
                                            Level: CodeGenOptLevel,
                                            EnableSegstk: bool,
-                                           UseSoftFP: bool) -> TargetMachineRef;
+                                           UseSoftFP: bool,
+                                           NoFramePointerElim: bool,
+                                           FunctionSections: bool,
+                                           DataSections: bool)
+                                           -> TargetMachineRef;
         pub fn LLVMRustDisposeTargetMachine(T: TargetMachineRef);
         pub fn LLVMRustAddAnalysisPasses(T: TargetMachineRef,
                                          PM: PassManagerRef,
                                          M: ModuleRef);
+        pub fn LLVMRustCreateTargetLibraryInfo(Triple: *c_char)
+                                               -> TargetLibraryInfoRef;
+        pub fn LLVMRustDisposeTargetLibraryInfo(TLI: TargetLibraryInfoRef);
         pub fn LLVMRustAddBuilderLibraryInfo(PMB: PassManagerBuilderRef,
-                                             M: ModuleRef);
-        pub fn LLVMRustAddLibraryInfo(PM: PassManagerRef, M: ModuleRef);
+                                             TLI: TargetLibraryInfoRef);
+        pub fn LLVMRustAddLibraryInfo(PM: PassManagerRef,
+                                      TLI: TargetLibraryInfoRef);
         pub fn LLVMRustRunFunctionPassManager(PM: PassManagerRef, M: ModuleRef);
         pub fn LLVMRustWriteOutputFile(T: TargetMachineRef,
                                        PM: PassManagerRef,
                                        M: ModuleRef,
                                        Output: *c_char,
-                                       FileType: FileType) -> bool;
+                                       FileType: FileType,
+                                       DisableVerify: bool) -> bool;
         pub fn LLVMRustPrintModule(PM: PassManagerRef,
                                    M: ModuleRef,
                                    Output: *c_char);
-        pub fn LLVMRustSetLLVMOptions(Argc: c_int, Argv: **c_char);
+        pub fn LLVMRustSetLLVMOptions(Argc: c_int, Argv: **c_char) -> bool;
+        pub fn LLVMRustAddLLVMOption(Arg: *c_char) -> bool;
         pub fn LLVMRustPrintPasses();
+        pub fn LLVMRustHasPass(PassName: *c_char) -> bool;
+        pub fn LLVMRustHasLLVMOption(Name: *c_char) -> bool;
+        pub fn LLVMRustPrintLLVMOptions();
         pub fn LLVMRustSetNormalizedTarget(M: ModuleRef, triple: *c_char);
         pub fn LLVMRustAddAlwaysInlinePass(P: PassManagerBuilderRef,
                                            AddLifetimes: bool);
@@ -1747,6 +1783,9 @@ pub mod llvm {
         pub fn LLVMRustRunRestrictionPass(M: ModuleRef,
                                           syms: **c_char,
                                           len: size_t);
+        pub fn LLVMRustAddModuleFlag(M: ModuleRef,
+                                     name: *c_char,
+                                     value: u32);
     }
 }
 
@@ -1778,6 +1817,12 @@ pub fn set_thread_local(global: ValueRef, is_thread_local: bool) {
     }
 }
 
+pub fn set_thread_local_mode(global: ValueRef, mode: ThreadLocalMode) {
+    unsafe {
+        llvm::LLVMSetThreadLocalMode(global, mode);
+    }
+}
+
 pub fn ConstICmp(Pred: IntPredicate, V1: ValueRef, V2: ValueRef) -> ValueRef {
     unsafe {
         llvm::LLVMConstICmp(Pred as c_ushort, V1, V2)
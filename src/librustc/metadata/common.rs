@@ -204,8 +204,26 @@ pub static tag_native_libraries_lib: uint = 0x104;
 pub static tag_native_libraries_name: uint = 0x105;
 pub static tag_native_libraries_kind: uint = 0x106;
 
+pub static tag_exported_symbols: uint = 0x107;
+pub static tag_exported_symbols_symbol: uint = 0x108;
+
+pub static tag_soft_float: uint = 0x109;
+
+pub static tag_bitcode_only: uint = 0x10b;
+
+pub static tag_crate_interface_hash: uint = 0x10c;
+pub static tag_crate_dep_interface_hash: uint = 0x10d;
+
+pub static tag_split_stack: uint = 0x10e;
+
+pub static tag_kept_symbols: uint = 0x10f;
+pub static tag_kept_symbols_symbol: uint = 0x110;
+
 #[deriving(Clone)]
 pub struct LinkMeta {
     pkgid: PkgId,
     crate_hash: @str,
+    // The hash of the crate's interface, which only changes when the crates
+    // built against it have to be rebuilt (see `back::link`)
+    interface_hash: @str,
 }
@@ -43,6 +43,7 @@ pub fn read_crates(sess: Session,
     let mut v = ReadCrateVisitor{ e:e };
     visit_crate(e, crate);
     visit::walk_crate(&mut v, crate, ());
+    add_cmdline_libraries(e);
     dump_crates(*e.crate_cache);
     warn_if_multiple_versions(e, sess.diagnostic(), *e.crate_cache);
 }
@@ -65,6 +66,7 @@ struct cache_entry {
     span: Span,
     hash: @str,
     pkgid: PkgId,
+    path: Option<Path>,
 }
 
 fn dump_crates(crate_cache: &[cache_entry]) {
@@ -90,9 +92,19 @@ fn warn_if_multiple_versions(e: @mut Env,
         if matches.len() != 1u {
             diag.handler().warn(
                 format!("using multiple versions of crate `{}`", name));
+            // The types and traits of the versions are distinct, which leads
+            // to baffling mismatches, so point out where each one came from
             for match_ in matches.iter() {
                 diag.span_note(match_.span, "used here");
                 loader::note_pkgid_attr(diag, &match_.pkgid);
+                match match_.path {
+                    Some(ref path) => {
+                        diag.handler().note(format!("hash {} loaded from {}",
+                                                    match_.hash,
+                                                    path.display()));
+                    }
+                    None => {}
+                }
             }
         }
 
@@ -121,6 +133,20 @@ fn visit_crate(e: &Env, c: &ast::Crate) {
     }
 }
 
+// Libraries given on the command line with `-l` are treated as though they
+// were declared with #[link] attributes in the crate
+fn add_cmdline_libraries(e: &Env) {
+    for &(ref name, kind) in e.sess.opts.cmdline_libs.iter() {
+        let framework = kind == cstore::NativeFramework ||
+                        kind == cstore::NativeFrameworkWeak;
+        if framework && e.sess.targ_cfg.os != abi::OsMacos {
+            e.sess.err("native frameworks are only available on OSX targets");
+            continue;
+        }
+        cstore::add_used_library(e.sess.cstore, name.clone(), kind);
+    }
+}
+
 fn visit_view_item(e: @mut Env, i: &ast::view_item) {
     match i.node {
       ast::view_item_extern_mod(ident, path_opt, _, id) => {
@@ -148,6 +174,7 @@ fn visit_view_item(e: @mut Env, i: &ast::view_item) {
                                    name,
                                    version,
                                    @"",
+                                   None,
                                    i.span);
           cstore::add_extern_mod_stmt_cnum(e.sess.cstore, id, cnum);
       }
@@ -191,10 +218,18 @@ fn visit_item(e: &Env, i: @ast::item) {
                             Some(k) => {
                                 if "static" == k {
                                     cstore::NativeStatic
+                                } else if "static-nobundle" == k {
+                                    cstore::NativeStaticNobundle
+                                } else if "dylib" == k {
+                                    cstore::NativeUnknown
                                 } else if e.sess.targ_cfg.os == abi::OsMacos &&
                                           "framework" == k {
                                     cstore::NativeFramework
-                                } else if "framework" == k {
+                                } else if e.sess.targ_cfg.os == abi::OsMacos &&
+                                          "framework,weak" == k {
+                                    cstore::NativeFrameworkWeak
+                                } else if "framework" == k ||
+                                          "framework,weak" == k {
                                     e.sess.span_err(m.span,
                                         "native frameworks are only available \
                                          on OSX targets");
@@ -207,6 +242,21 @@ fn visit_item(e: &Env, i: @ast::item) {
                             }
                             None => cstore::NativeUnknown
                         };
+                        let modifiers = items.iter().find(|k| {
+                            "modifiers" == k.name()
+                        }).and_then(|a| a.value_str());
+                        let kind = match modifiers {
+                            Some(modifiers) => {
+                                match apply_modifiers(kind, modifiers) {
+                                    Ok(kind) => kind,
+                                    Err(msg) => {
+                                        e.sess.span_err(m.span, msg);
+                                        kind
+                                    }
+                                }
+                            }
+                            None => kind
+                        };
                         let n = items.iter().find(|n| {
                             "name" == n.name()
                         }).and_then(|a| a.value_str());
@@ -229,6 +279,27 @@ fn visit_item(e: &Env, i: @ast::item) {
     }
 }
 
+/// Applies a comma separated list of linking modifiers, such as
+/// `+whole-archive`, to the kind of a native library
+pub fn apply_modifiers(kind: cstore::NativeLibaryKind,
+                       modifiers: &str) -> Result<cstore::NativeLibaryKind, ~str> {
+    let mut kind = kind;
+    for modifier in modifiers.split(',') {
+        match modifier.trim() {
+            "+whole-archive" => {
+                if kind != cstore::NativeStatic {
+                    return Err(~"the `+whole-archive` modifier is only valid \
+                                 for static libraries");
+                }
+                kind = cstore::NativeStaticWholeArchive;
+            }
+            "-whole-archive" => {}
+            m => return Err(format!("unknown linking modifier: `{}`", m)),
+        }
+    }
+    Ok(kind)
+}
+
 fn existing_match(e: &Env, name: @str, version: @str, hash: &str) -> Option<ast::CrateNum> {
     for c in e.crate_cache.iter() {
         let pkgid_version = match c.pkgid.version {
@@ -249,6 +320,7 @@ fn resolve_crate(e: @mut Env,
                  name: @str,
                  version: @str,
                  hash: @str,
+                 root_ident: Option<@str>,
                  span: Span)
               -> ast::CrateNum {
     match existing_match(e, name, version, hash) {
@@ -260,6 +332,7 @@ fn resolve_crate(e: @mut Env,
             name: name,
             version: version,
             hash: hash,
+            root_ident: root_ident,
             os: e.os,
             intr: e.intr
         };
@@ -278,11 +351,12 @@ fn resolve_crate(e: @mut Env,
             span: span,
             hash: hash,
             pkgid: pkgid,
+            path: rlib.clone().or(dylib.clone()),
         });
         e.next_crate_num += 1;
 
         // Now resolve the crates referenced by this crate
-        let cnum_map = resolve_crate_deps(e, metadata);
+        let cnum_map = resolve_crate_deps(e, name, metadata);
 
         let cmeta = @cstore::crate_metadata {
             name: name,
@@ -307,7 +381,8 @@ fn resolve_crate(e: @mut Env,
 }
 
 // Go through the crate metadata and load any crates that it references
-fn resolve_crate_deps(e: @mut Env, cdata: @~[u8]) -> cstore::cnum_map {
+fn resolve_crate_deps(e: @mut Env, root_ident: @str,
+                      cdata: @~[u8]) -> cstore::cnum_map {
     debug!("resolving deps of external crate");
     // The map from crate numbers in the crate we're resolving to local crate
     // numbers
@@ -331,7 +406,7 @@ fn resolve_crate_deps(e: @mut Env, cdata: @~[u8]) -> cstore::cnum_map {
             // span.
             let fake_span = dummy_sp();
             let local_cnum = resolve_crate(e, cname_str, cname_str, dep.vers,
-                                           dep.hash, fake_span);
+                                           dep.hash, Some(root_ident), fake_span);
             cnum_map.insert(extrn_cnum, local_cnum);
           }
         }
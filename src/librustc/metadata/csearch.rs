@@ -269,6 +269,36 @@ pub fn get_native_libraries(cstore: @mut cstore::CStore,
     decoder::get_native_libraries(cdata)
 }
 
+pub fn get_exported_symbols(cstore: @mut cstore::CStore,
+                            crate_num: ast::CrateNum) -> ~[~str] {
+    let cdata = cstore::get_crate_data(cstore, crate_num);
+    decoder::get_exported_symbols(cdata)
+}
+
+pub fn get_kept_symbols(cstore: @mut cstore::CStore,
+                        crate_num: ast::CrateNum) -> ~[~str] {
+    let cdata = cstore::get_crate_data(cstore, crate_num);
+    decoder::get_kept_symbols(cdata)
+}
+
+pub fn get_soft_float(cstore: @mut cstore::CStore,
+                      crate_num: ast::CrateNum) -> bool {
+    let cdata = cstore::get_crate_data(cstore, crate_num);
+    decoder::get_soft_float(cdata)
+}
+
+pub fn get_bitcode_only(cstore: @mut cstore::CStore,
+                        crate_num: ast::CrateNum) -> bool {
+    let cdata = cstore::get_crate_data(cstore, crate_num);
+    decoder::get_bitcode_only(cdata)
+}
+
+pub fn get_split_stack(cstore: @mut cstore::CStore,
+                       crate_num: ast::CrateNum) -> bool {
+    let cdata = cstore::get_crate_data(cstore, crate_num);
+    decoder::get_split_stack(cdata)
+}
+
 pub fn each_impl(cstore: @mut cstore::CStore,
                  crate_num: ast::CrateNum,
                  callback: |ast::DefId|) {
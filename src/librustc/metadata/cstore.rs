@@ -40,11 +40,28 @@ pub enum LinkagePreference {
     RequireStatic,
 }
 
-#[deriving(Eq, FromPrimitive)]
+#[deriving(Eq, Clone, FromPrimitive)]
 pub enum NativeLibaryKind {
     NativeStatic,    // native static library (.a archive)
     NativeFramework, // OSX-specific
-    NativeUnknown,   // default way to specify a dynamic library
+    NativeUnknown,   // dynamic library, the default (or kind = "dylib")
+    NativeStaticWholeArchive, // static library which is linked in its entirety
+    NativeStaticNobundle, // static library which is only linked at final link
+    NativeFrameworkWeak, // OSX framework which may be missing at runtime
+}
+
+impl NativeLibaryKind {
+    /// The name of the kind in the listings of a crate's native libraries
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NativeStatic => "static",
+            NativeStaticWholeArchive => "static:+whole-archive",
+            NativeStaticNobundle => "static-nobundle",
+            NativeUnknown => "dylib",
+            NativeFramework => "framework",
+            NativeFrameworkWeak => "framework,weak",
+        }
+    }
 }
 
 // Where a crate came from on the local filesystem. One of these two options
@@ -168,6 +185,16 @@ pub fn find_extern_mod_stmt_cnum(cstore: &CStore,
     cstore.extern_mod_crate_map.find(&emod_id).map(|x| *x)
 }
 
+// The crates named by the `extern mod` statements of this crate, in the order
+// of their numbers
+pub fn get_extern_mod_crates(cstore: &CStore) -> ~[ast::CrateNum] {
+    let mut crates = cstore.extern_mod_crate_map.values().map(|&c| c)
+                           .to_owned_vec();
+    extra::sort::quick_sort(crates, |a, b| *a <= *b);
+    crates.dedup();
+    crates
+}
+
 #[deriving(Clone)]
 struct crate_hash {
     name: @str,
@@ -1128,7 +1128,10 @@ pub struct CrateDep {
     cnum: ast::CrateNum,
     name: ast::Ident,
     vers: @str,
-    hash: @str
+    hash: @str,
+    // The interface hash of the dependency when the crate was built, which
+    // older crates don't record
+    interface_hash: Option<@str>,
 }
 
 pub fn get_crate_deps(data: @~[u8]) -> ~[CrateDep] {
@@ -1140,11 +1143,15 @@ pub fn get_crate_deps(data: @~[u8]) -> ~[CrateDep] {
         let d = reader::get_doc(doc, tag_);
         d.as_str_slice().to_managed()
     }
+    fn maybe_docstr(doc: ebml::Doc, tag_: uint) -> Option<@str> {
+        reader::maybe_get_doc(doc, tag_).map(|d| d.as_str_slice().to_managed())
+    }
     reader::tagged_docs(depsdoc, tag_crate_dep, |depdoc| {
         deps.push(CrateDep {cnum: crate_num,
                   name: token::str_to_ident(docstr(depdoc, tag_crate_dep_name)),
                   vers: docstr(depdoc, tag_crate_dep_vers),
-                  hash: docstr(depdoc, tag_crate_dep_hash)});
+                  hash: docstr(depdoc, tag_crate_dep_hash),
+                  interface_hash: maybe_docstr(depdoc, tag_crate_dep_interface_hash)});
         crate_num += 1;
         true
     });
@@ -1158,6 +1165,10 @@ fn list_crate_deps(data: @~[u8], out: @mut io::Writer) {
     for dep in r.iter() {
         write!(out, "{} {}-{}-{}\n",
                  dep.cnum, token::ident_to_str(&dep.name), dep.hash, dep.vers);
+        match dep.interface_hash {
+            Some(hash) => write!(out, "  built against interface {}\n", hash),
+            None => {}
+        }
     }
 
     write!(out, "\n");
@@ -1169,6 +1180,15 @@ pub fn get_crate_hash(data: @~[u8]) -> @str {
     hashdoc.as_str_slice().to_managed()
 }
 
+/// The hash of the crate's interface (see `back::link`), if the crate is
+/// recent enough to record it
+pub fn get_crate_interface_hash(data: @~[u8]) -> Option<@str> {
+    let cratedoc = reader::Doc(data);
+    reader::maybe_get_doc(cratedoc, tag_crate_interface_hash).map(|doc| {
+        doc.as_str_slice().to_managed()
+    })
+}
+
 pub fn get_crate_vers(data: @~[u8]) -> @str {
     let attrs = decoder::get_crate_attributes(data);
     match attr::find_pkgid(attrs) {
@@ -1177,12 +1197,148 @@ pub fn get_crate_vers(data: @~[u8]) -> @str {
     }
 }
 
+fn list_crate_info(data: @~[u8], hash: &str, out: @mut io::Writer) {
+    write!(out, "=Crate=\n");
+    match attr::find_pkgid(get_crate_attributes(data)) {
+        Some(pkgid) => {
+            write!(out, "name: {}\nversion: {}\n",
+                   pkgid.name, pkgid.version_or_default());
+        }
+        None => {}
+    }
+    write!(out, "hash: {}\n", hash);
+    match get_crate_interface_hash(data) {
+        Some(hash) => write!(out, "interface hash: {}\n", hash),
+        None => {}
+    }
+    write!(out, "\n");
+}
+
+fn list_native_libraries(data: @~[u8], out: @mut io::Writer) {
+    write!(out, "=Native Libraries=\n");
+    for &(kind, ref name) in native_libraries(data).iter() {
+        write!(out, "{} ({})\n", *name, kind.name());
+    }
+    write!(out, "\n");
+}
+
+fn list_exported_symbols(data: @~[u8], out: @mut io::Writer) {
+    write!(out, "=Exported Symbols=\n");
+    for symbol in exported_symbols(data).iter() {
+        write!(out, "{}\n", *symbol);
+    }
+    write!(out, "\n");
+}
+
+fn list_kept_symbols(data: @~[u8], out: @mut io::Writer) {
+    write!(out, "=Kept Symbols=\n");
+    for symbol in kept_symbols(data).iter() {
+        write!(out, "{}\n", *symbol);
+    }
+    write!(out, "\n");
+}
+
 pub fn list_crate_metadata(intr: @ident_interner, bytes: @~[u8],
                            out: @mut io::Writer) {
     let hash = get_crate_hash(bytes);
     let md = reader::Doc(bytes);
+    list_crate_info(bytes, hash, out);
     list_crate_attributes(intr, md, hash, out);
     list_crate_deps(bytes, out);
+    list_native_libraries(bytes, out);
+    list_exported_symbols(bytes, out);
+    list_kept_symbols(bytes, out);
+}
+
+// An item of a crate's public API
+pub struct PublicItem {
+    path: ~str,
+    kind: &'static str,
+    symbol: Option<~str>,
+}
+
+// The items of a crate which can be used from other crates. Without the
+// privacy pass this goes by the declared visibility of the items, of the
+// modules containing them and, for methods, of the traits and impls they're
+// part of. Impls are listed when any of their methods are.
+pub fn get_public_items(intr: @ident_interner, data: @~[u8]) -> ~[PublicItem] {
+    let items = reader::get_doc(reader::Doc(data), tag_items);
+    let items_data = reader::get_doc(items, tag_items_data);
+
+    let mut private_mods = ~[];
+    reader::tagged_docs(items_data, tag_items_data_item, |item| {
+        if item_family(item) == Mod && item_visibility(item) != ast::public {
+            private_mods.push(ast_map::path_to_str(item_path(item), intr) + "::");
+        }
+        true
+    });
+
+    let mut result = ~[];
+    let mut impls = ~[];
+    let mut public_impls = ~[];
+    reader::tagged_docs(items_data, tag_items_data_item, |item| {
+        let path = ast_map::path_to_str(item_path(item), intr);
+        if path.is_empty() ||
+           private_mods.iter().any(|m| path.starts_with(m.as_slice())) {
+            return true;
+        }
+        let family = item_family(item);
+        let public = item_visibility(item) == ast::public;
+        let kind = match family {
+            Fn | UnsafeFn | StaticMethod | UnsafeStaticMethod => {
+                match item_parent_item(item) {
+                    Some(parent) => {
+                        let parent_doc = find_item(parent.node, items);
+                        match item_family(parent_doc) {
+                            Trait if item_visibility(parent_doc) != ast::public => {
+                                return true;
+                            }
+                            Trait => {}
+                            _ => {
+                                // Trait impls have all of their methods
+                                // exported
+                                let in_trait_impl = reader::maybe_get_doc(
+                                    parent_doc, tag_item_trait_ref).is_some();
+                                if !public && !in_trait_impl { return true; }
+                                public_impls.push(parent.node);
+                            }
+                        }
+                        "method"
+                    }
+                    None if public => "fn",
+                    None => return true,
+                }
+            }
+            Impl => {
+                let id = reader::get_doc(item, tag_def_id);
+                impls.push((reader::with_doc_data(id, parse_def_id).node, path));
+                return true;
+            }
+            _ if !public => return true,
+            ForeignFn => "fn",
+            ImmStatic | MutStatic => "static",
+            Mod => "mod",
+            Type | ForeignType => "type",
+            Enum => "enum",
+            Struct => "struct",
+            Trait => "trait",
+            _ => return true,
+        };
+        let symbol = reader::maybe_get_doc(item, tag_items_data_item_symbol);
+        result.push(PublicItem {
+            path: path,
+            kind: kind,
+            symbol: symbol.map(|doc| doc.as_str()),
+        });
+        true
+    });
+
+    for (id, path) in impls.move_iter() {
+        if public_impls.contains(&id) {
+            result.push(PublicItem { path: path, kind: "impl", symbol: None });
+        }
+    }
+    return result;
 }
 
 // Translates a def_id from an external crate to a def_id for the current
@@ -1257,7 +1413,16 @@ pub fn get_trait_of_method(cdata: Cmd, id: ast::NodeId, tcx: ty::ctxt)
 
 
 pub fn get_native_libraries(cdata: Cmd) -> ~[(cstore::NativeLibaryKind, ~str)] {
-    let libraries = reader::get_doc(reader::Doc(cdata.data), tag_native_libraries);
+    native_libraries(cdata.data)
+}
+
+pub fn get_crate_native_libraries(data: @~[u8])
+                                  -> ~[(cstore::NativeLibaryKind, ~str)] {
+    native_libraries(data)
+}
+
+fn native_libraries(data: @~[u8]) -> ~[(cstore::NativeLibaryKind, ~str)] {
+    let libraries = reader::get_doc(reader::Doc(data), tag_native_libraries);
     let mut result = ~[];
     reader::tagged_docs(libraries, tag_native_libraries_lib, |lib_doc| {
         let kind_doc = reader::get_doc(lib_doc, tag_native_libraries_kind);
@@ -1270,3 +1435,66 @@ pub fn get_native_libraries(cdata: Cmd) -> ~[(cstore::NativeLibaryKind, ~str)] {
     });
     return result;
 }
+
+// Crates from before the flag was recorded were built for the hard float ABI,
+// unless `-Z soft-float` was given, which there's no telling
+pub fn get_soft_float(cdata: Cmd) -> bool {
+    match reader::maybe_get_doc(reader::Doc(cdata.data), tag_soft_float) {
+        Some(doc) => reader::doc_as_u8(doc) != 0,
+        None => false,
+    }
+}
+
+// Crates from before the flag was recorded all had an object file
+pub fn get_bitcode_only(cdata: Cmd) -> bool {
+    match reader::maybe_get_doc(reader::Doc(cdata.data), tag_bitcode_only) {
+        Some(doc) => reader::doc_as_u8(doc) != 0,
+        None => false,
+    }
+}
+
+// Crates from before the flag was recorded all used split stacks
+pub fn get_split_stack(cdata: Cmd) -> bool {
+    match reader::maybe_get_doc(reader::Doc(cdata.data), tag_split_stack) {
+        Some(doc) => reader::doc_as_u8(doc) != 0,
+        None => true,
+    }
+}
+
+pub fn get_kept_symbols(cdata: Cmd) -> ~[~str] {
+    kept_symbols(cdata.data)
+}
+
+// Crates from before the kept symbols were recorded have none
+fn kept_symbols(data: @~[u8]) -> ~[~str] {
+    let mut result = ~[];
+    match reader::maybe_get_doc(reader::Doc(data), tag_kept_symbols) {
+        Some(symbols) => {
+            reader::tagged_docs(symbols, tag_kept_symbols_symbol, |sym_doc| {
+                result.push(sym_doc.as_str());
+                true
+            });
+        }
+        None => {}
+    }
+    return result;
+}
+
+pub fn get_exported_symbols(cdata: Cmd) -> ~[~str] {
+    exported_symbols(cdata.data)
+}
+
+// Crates built before their exported symbols were recorded have none listed
+fn exported_symbols(data: @~[u8]) -> ~[~str] {
+    let mut result = ~[];
+    match reader::maybe_get_doc(reader::Doc(data), tag_exported_symbols) {
+        Some(symbols) => {
+            reader::tagged_docs(symbols, tag_exported_symbols_symbol, |sym_doc| {
+                result.push(sym_doc.as_str());
+                true
+            });
+        }
+        None => {}
+    }
+    return result;
+}
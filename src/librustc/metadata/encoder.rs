@@ -11,6 +11,7 @@
 // Metadata encoding
 
 
+use back::link;
 use metadata::common::*;
 use metadata::cstore;
 use metadata::decoder;
@@ -67,6 +68,7 @@ pub struct EncodeParams<'a> {
     cstore: @mut cstore::CStore,
     encode_inlined_item: encode_inlined_item<'a>,
     reachable: @mut HashSet<ast::NodeId>,
+    kept_items: &'a HashSet<ast::NodeId>,
 }
 
 struct Stats {
@@ -98,6 +100,7 @@ pub struct EncodeContext<'a> {
     encode_inlined_item: encode_inlined_item<'a>,
     type_abbrevs: abbrev_map,
     reachable: @mut HashSet<ast::NodeId>,
+    kept_items: &'a HashSet<ast::NodeId>,
 }
 
 pub fn reachable(ecx: &EncodeContext, id: NodeId) -> bool {
@@ -1154,6 +1157,7 @@ fn encode_info_for_item(ecx: &EncodeContext,
             ebml_w.end_tag();
         }
         encode_path(ecx, ebml_w, path, ast_map::path_name(item.ident));
+        encode_visibility(ebml_w, vis);
         // FIXME(#8559): This should use the tcx's supertrait cache instead of
         // reading the AST's list, because the former has already filtered out
         // the builtin-kinds-as-supertraits. See corresponding fixme in decoder.
@@ -1521,7 +1525,8 @@ fn encode_crate_deps(ecx: &EncodeContext,
             let dep = decoder::CrateDep {cnum: key,
                        name: ecx.tcx.sess.ident_of(val.name),
                        vers: decoder::get_crate_vers(val.data),
-                       hash: decoder::get_crate_hash(val.data)};
+                       hash: decoder::get_crate_hash(val.data),
+                       interface_hash: decoder::get_crate_interface_hash(val.data)};
             deps.push(dep);
         });
 
@@ -1583,28 +1588,87 @@ fn encode_lang_items(ecx: &EncodeContext, ebml_w: &mut writer::Encoder) {
 fn encode_native_libraries(ecx: &EncodeContext, ebml_w: &mut writer::Encoder) {
     ebml_w.start_tag(tag_native_libraries);
 
+    // Static libraries are bundled into the rlib itself, so downstream crates
+    // never link to them again. They are still recorded here so that
+    // downstream crates can tell when the same archive has been bundled more
+    // than once.
     for &(ref lib, kind) in cstore::get_used_libraries(ecx.cstore).iter() {
-        match kind {
-            cstore::NativeStatic => {} // these libraries are not propagated
-            cstore::NativeFramework | cstore::NativeUnknown => {
-                ebml_w.start_tag(tag_native_libraries_lib);
+        ebml_w.start_tag(tag_native_libraries_lib);
 
-                ebml_w.start_tag(tag_native_libraries_kind);
-                ebml_w.writer.write_be_u32(kind as u32);
-                ebml_w.end_tag();
+        ebml_w.start_tag(tag_native_libraries_kind);
+        ebml_w.writer.write_be_u32(kind as u32);
+        ebml_w.end_tag();
 
-                ebml_w.start_tag(tag_native_libraries_name);
-                ebml_w.writer.write(lib.as_bytes());
-                ebml_w.end_tag();
+        ebml_w.start_tag(tag_native_libraries_name);
+        ebml_w.writer.write(lib.as_bytes());
+        ebml_w.end_tag();
 
-                ebml_w.end_tag();
-            }
-        }
+        ebml_w.end_tag();
     }
 
     ebml_w.end_tag();
 }
 
+// Records the symbols of this crate which aren't mangled (and hence aren't
+// unique to this crate) so that downstream crates can detect collisions
+// before invoking the linker.
+fn encode_exported_symbols(ecx: &EncodeContext, ebml_w: &mut writer::Encoder) {
+    ebml_w.start_tag(tag_exported_symbols);
+
+    let symbols = link::unmangled_symbols(ecx.tcx, ecx.reachable,
+                                          ecx.item_symbols);
+    for &(ref sym, _) in symbols.iter() {
+        ebml_w.start_tag(tag_exported_symbols_symbol);
+        ebml_w.writer.write(sym.as_bytes());
+        ebml_w.end_tag();
+    }
+
+    ebml_w.end_tag();
+}
+
+// Records the symbols of the items this crate places in sections of their own
+// and of its static constructors and destructors, which the linker must keep
+// in the outputs the crate is linked into even when nothing refers to them.
+fn encode_kept_symbols(ecx: &EncodeContext, ebml_w: &mut writer::Encoder) {
+    ebml_w.start_tag(tag_kept_symbols);
+
+    let mut symbols = ecx.kept_items.iter().filter_map(|id| {
+        ecx.item_symbols.find(id)
+    }).to_owned_vec();
+    extra::sort::quick_sort(symbols, |a, b| *a <= *b);
+    for sym in symbols.iter() {
+        ebml_w.start_tag(tag_kept_symbols_symbol);
+        ebml_w.writer.write(sym.as_bytes());
+        ebml_w.end_tag();
+    }
+
+    ebml_w.end_tag();
+}
+
+// Records whether this crate was built with `-C soft-float`, since crates of
+// different float ABIs can't be linked together.
+fn encode_soft_float(ecx: &EncodeContext, ebml_w: &mut writer::Encoder) {
+    ebml_w.start_tag(tag_soft_float);
+    ebml_w.writer.write([ecx.tcx.sess.opts.cg.soft_float as u8]);
+    ebml_w.end_tag();
+}
+
+// Records whether the rlib of this crate was built with `-C bitcode-only-rlib`,
+// so that it's known without reading the archive whenever the rlib is linked.
+fn encode_bitcode_only(ecx: &EncodeContext, ebml_w: &mut writer::Encoder) {
+    ebml_w.start_tag(tag_bitcode_only);
+    ebml_w.writer.write([ecx.tcx.sess.bitcode_only_rlib() as u8]);
+    ebml_w.end_tag();
+}
+
+// Records whether the functions of this crate have split-stack prologues, so
+// that whatever links the crate in statically knows it needs libmorestack.
+fn encode_split_stack(ecx: &EncodeContext, ebml_w: &mut writer::Encoder) {
+    ebml_w.start_tag(tag_split_stack);
+    ebml_w.writer.write([!*ecx.tcx.sess.no_split_stack as u8]);
+    ebml_w.end_tag();
+}
+
 struct ImplVisitor<'a> {
     ecx: &'a EncodeContext<'a>,
     ebml_w: &'a mut writer::Encoder,
@@ -1698,6 +1762,14 @@ fn encode_crate_dep(ecx: &EncodeContext,
     ebml_w.start_tag(tag_crate_dep_hash);
     ebml_w.writer.write(dep.hash.as_bytes());
     ebml_w.end_tag();
+    match dep.interface_hash {
+        Some(hash) => {
+            ebml_w.start_tag(tag_crate_dep_interface_hash);
+            ebml_w.writer.write(hash.as_bytes());
+            ebml_w.end_tag();
+        }
+        None => {}
+    }
     ebml_w.end_tag();
 }
 
@@ -1707,13 +1779,38 @@ fn encode_hash(ebml_w: &mut writer::Encoder, hash: &str) {
     ebml_w.end_tag();
 }
 
+fn encode_interface_hash(ebml_w: &mut writer::Encoder, hash: &str) {
+    ebml_w.start_tag(tag_crate_interface_hash);
+    ebml_w.writer.write(hash.as_bytes());
+    ebml_w.end_tag();
+}
+
 // NB: Increment this as you change the metadata encoding version.
 pub static metadata_encoding_version : &'static [u8] =
     &[0x72, //'r' as u8,
       0x75, //'u' as u8,
       0x73, //'s' as u8,
       0x74, //'t' as u8,
-      0, 0, 0, 1 ];
+      0, 0, 0, 2 ];
+
+/// The version of the compiler, as given by `rustc --version`
+pub fn rustc_version() -> &'static str {
+    match option_env!("CFG_VERSION") {
+        Some(vers) => vers,
+        None => "unknown version"
+    }
+}
+
+/// The header which starts the metadata of every crate: the encoding version
+/// followed by the version of the compiler which wrote it, terminated by a
+/// zero byte. The metadata of one compiler can't be read by another, and the
+/// header lets the loader say so rather than failing to decode it.
+pub fn metadata_header() -> ~[u8] {
+    let mut header = metadata_encoding_version.to_owned();
+    header.push_all(rustc_version().as_bytes());
+    header.push(0);
+    header
+}
 
 pub fn encode_metadata(parms: EncodeParams, crate: &Crate) -> ~[u8] {
     let wr = @mut MemWriter::new();
@@ -1742,6 +1839,7 @@ pub fn encode_metadata(parms: EncodeParams, crate: &Crate) -> ~[u8] {
         link_meta,
         reachable,
         non_inlineable_statics,
+        kept_items,
         ..
     } = parms;
     let type_abbrevs = @mut HashMap::new();
@@ -1759,11 +1857,13 @@ pub fn encode_metadata(parms: EncodeParams, crate: &Crate) -> ~[u8] {
         encode_inlined_item: encode_inlined_item,
         type_abbrevs: type_abbrevs,
         reachable: reachable,
+        kept_items: kept_items,
      };
 
     let mut ebml_w = writer::Encoder(wr);
 
     encode_hash(&mut ebml_w, ecx.link_meta.crate_hash);
+    encode_interface_hash(&mut ebml_w, ecx.link_meta.interface_hash);
 
     let mut i = wr.tell();
     let crate_attrs = synthesize_crate_attrs(&ecx, crate);
@@ -1784,6 +1884,17 @@ pub fn encode_metadata(parms: EncodeParams, crate: &Crate) -> ~[u8] {
     encode_native_libraries(&ecx, &mut ebml_w);
     ecx.stats.native_lib_bytes = wr.tell() - i;
 
+    // Encode the unmangled symbols exported from this crate
+    encode_exported_symbols(&ecx, &mut ebml_w);
+
+    encode_kept_symbols(&ecx, &mut ebml_w);
+
+    encode_soft_float(&ecx, &mut ebml_w);
+
+    encode_bitcode_only(&ecx, &mut ebml_w);
+
+    encode_split_stack(&ecx, &mut ebml_w);
+
     // Encode the def IDs of impls, for coherence checking.
     i = wr.tell();
     encode_impls(&ecx, crate, &mut ebml_w);
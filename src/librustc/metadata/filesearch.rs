@@ -143,11 +143,14 @@ pub fn search(filesearch: @FileSearch, pick: pick) {
     });
 }
 
+/// The directory of the sysroot holding the libraries for a target,
+/// `lib/rustlib/<triple>/lib`. Each target has a directory of its own, so that
+/// one installation can hold the libraries of many targets.
 pub fn relative_target_lib_path(target_triple: &str) -> Path {
     let dir = libdir();
     let mut p = Path::new(dir.as_slice());
     assert!(p.is_relative());
-    p.push("rustc");
+    p.push("rustlib");
     p.push(target_triple);
     p.push(dir);
     p
@@ -165,6 +168,13 @@ fn make_rustpkg_target_lib_path(dir: &Path,
     p
 }
 
+/// The directories of the RUST_PATH workspaces where libraries for the target
+/// are installed, which are searched for native libraries after those given
+/// with -L
+pub fn rust_path_lib_paths(target_triple: &str) -> ~[Path] {
+    rust_path().map(|p| make_rustpkg_target_lib_path(p, target_triple))
+}
+
 pub fn get_or_default_sysroot() -> Path {
     match os::self_exe_path() {
       option::Some(p) => { let mut p = p; p.pop(); p }
@@ -27,8 +27,6 @@ use syntax::attr::AttrMetaMethods;
 use std::c_str::ToCStr;
 use std::cast;
 use std::io;
-use std::num;
-use std::option;
 use std::os::consts::{macos, freebsd, linux, android, win32};
 use std::ptr;
 use std::str;
@@ -50,10 +48,21 @@ pub struct Context {
     name: @str,
     version: @str,
     hash: @str,
+    // The crate whose dependency is being loaded, if it's one
+    root_ident: Option<@str>,
     os: Os,
     intr: @ident_interner
 }
 
+// Why the metadata of a file couldn't be loaded
+enum MetadataError {
+    // The file has no metadata at all, or it's corrupt
+    NoMetadata,
+    // The metadata was written by another version of rustc, whose version
+    // string is known if the metadata was recent enough to record it
+    CompilerMismatch(Option<~str>),
+}
+
 pub struct Library {
     dylib: Option<Path>,
     rlib: Option<Path>,
@@ -82,6 +91,8 @@ impl Context {
         let rlib_prefix = format!("lib{}-", crate_name);
 
         let mut matches = ~[];
+        let mut mismatches = ~[];
+        let mut rejected_via_hash = ~[];
         filesearch::search(filesearch, |path| {
             match path.filename_str() {
                 None => FileDoesntMatch,
@@ -102,8 +113,18 @@ impl Context {
                         FileMatches
                     } else if candidate {
                         match get_metadata_section(self.sess, self.os, path) {
-                            Some(cvec) =>
-                                if crate_matches(cvec, self.name, self.version, self.hash) {
+                            Ok(cvec) =>
+                                if !crate_matches(cvec, self.name, self.version, self.hash) {
+                                    debug!("skipping {}, pkgid doesn't match",
+                                           path.display());
+                                    // A dependency is looked for by its hash,
+                                    // and any other build of it is stale
+                                    if !self.hash.is_empty() &&
+                                       crate_matches(cvec, self.name, @"", @"") {
+                                        rejected_via_hash.push((path.clone(), cvec));
+                                    }
+                                    FileDoesntMatch
+                                } else {
                                     debug!("found {} with matching pkgid", path.display());
                                     let (rlib, dylib) = if file.ends_with(".rlib") {
                                         (Some(path.clone()), None)
@@ -116,12 +137,14 @@ impl Context {
                                         metadata: cvec,
                                     });
                                     FileMatches
-                                } else {
-                                    debug!("skipping {}, pkgid doesn't match",
+                                },
+                                Err(CompilerMismatch(vers)) => {
+                                    debug!("{} was compiled by another rustc",
                                            path.display());
+                                    mismatches.push((path.clone(), vers));
                                     FileDoesntMatch
-                                },
-                                _ => {
+                                }
+                                Err(NoMetadata) => {
                                     debug!("could not load metadata for {}",
                                            path.display());
                                     FileDoesntMatch
@@ -134,6 +157,13 @@ impl Context {
             }
         });
 
+        if matches.is_empty() && !mismatches.is_empty() {
+            self.report_compiler_mismatches(mismatches);
+        }
+        if matches.is_empty() && !rejected_via_hash.is_empty() {
+            self.report_hash_mismatches(rejected_via_hash);
+        }
+
         match matches.len() {
             0 => None,
             1 => Some(matches[0]),
@@ -168,6 +198,47 @@ impl Context {
         }
     }
 
+    // None of the candidates for the crate could be loaded, because they were
+    // built by another version of the compiler
+    fn report_compiler_mismatches(&self, mismatches: &[(Path, Option<~str>)]) {
+        for &(ref path, ref vers) in mismatches.iter() {
+            let vers = match *vers {
+                Some(ref vers) => format!("rustc {}", *vers),
+                None => ~"an older rustc",
+            };
+            self.sess.span_err(self.span,
+                               format!("crate `{}` was compiled by {}, this is \
+                                        rustc {}; recompile it",
+                                       self.ident, vers,
+                                       encoder::rustc_version()));
+            self.sess.note(format!("path: {}", path.display()));
+        }
+        self.sess.abort_if_errors();
+    }
+
+    // Only other builds of a dependency than the one it was depended on with
+    // were found, so the crate depending on it is stale and has to be rebuilt
+    fn report_hash_mismatches(&self, rejected: &[(Path, @~[u8])]) {
+        let root = self.root_ident.unwrap_or(@"<unknown>");
+        self.sess.span_err(self.span,
+                           format!("crate `{}` was built against a version of \
+                                    crate `{}` which can't be found; recompile \
+                                    it", root, self.ident));
+        self.sess.note(format!("expected `{}` version {} with hash {}",
+                               self.ident, self.version, self.hash));
+        for &(ref path, cvec) in rejected.iter() {
+            let attrs = decoder::get_crate_attributes(cvec);
+            let vers = match attr::find_pkgid(attrs) {
+                Some(pkgid) => pkgid.version_or_default().to_owned(),
+                None => ~"?",
+            };
+            self.sess.note(format!("found version {} with hash {} at {}",
+                                   vers, decoder::get_crate_hash(cvec),
+                                   path.display()));
+        }
+        self.sess.abort_if_errors();
+    }
+
     fn add_existing_rlib(&self, libs: &mut [Library],
                          path: &Path, file: &str) -> bool {
         let (prefix, suffix) = self.dylibname();
@@ -211,13 +282,26 @@ impl Context {
     // Returns the corresponding (prefix, suffix) that files need to have for
     // dynamic libraries
     fn dylibname(&self) -> (&'static str, &'static str) {
-        match self.os {
-            OsWin32 => (win32::DLL_PREFIX, win32::DLL_SUFFIX),
-            OsMacos => (macos::DLL_PREFIX, macos::DLL_SUFFIX),
-            OsLinux => (linux::DLL_PREFIX, linux::DLL_SUFFIX),
-            OsAndroid => (android::DLL_PREFIX, android::DLL_SUFFIX),
-            OsFreebsd => (freebsd::DLL_PREFIX, freebsd::DLL_SUFFIX),
-        }
+        dylibname(self.os)
+    }
+}
+
+fn dylibname(os: Os) -> (&'static str, &'static str) {
+    match os {
+        OsWin32 => (win32::DLL_PREFIX, win32::DLL_SUFFIX),
+        OsMacos => (macos::DLL_PREFIX, macos::DLL_SUFFIX),
+        OsLinux => (linux::DLL_PREFIX, linux::DLL_SUFFIX),
+        OsAndroid => (android::DLL_PREFIX, android::DLL_SUFFIX),
+        OsFreebsd => (freebsd::DLL_PREFIX, freebsd::DLL_SUFFIX),
+    }
+}
+
+// Whether the file is named like a library crate rather than source
+pub fn is_library_file(os: Os, path: &Path) -> bool {
+    let (_, suffix) = dylibname(os);
+    match path.filename_str() {
+        Some(name) => name.ends_with(".rlib") || name.ends_with(suffix),
+        None => false,
     }
 }
 
@@ -244,19 +328,54 @@ fn crate_matches(crate_data: @~[u8],
     }
 }
 
-fn get_metadata_section(sess: Session, os: Os, filename: &Path) -> Option<@~[u8]> {
+// Checks the header which starts the metadata (see
+// `encoder::metadata_header`), returning its length
+fn check_metadata_header(data: &[u8]) -> Result<uint, MetadataError> {
+    let magic = encoder::metadata_encoding_version;
+    if data.len() < magic.len() || data.slice_to(4) != magic.slice_to(4) {
+        return Err(NoMetadata);
+    }
+    // Metadata before the second version of the encoding doesn't say which
+    // compiler wrote it
+    let format = data.slice(4, magic.len()).iter().fold(0u, |n, &b| {
+        (n << 8) | b as uint
+    });
+    if format < 2 {
+        return Err(CompilerMismatch(None));
+    }
+    let rest = data.slice_from(magic.len());
+    let vers = match rest.iter().position(|&b| b == 0) {
+        Some(end) => match str::from_utf8_opt(rest.slice_to(end)) {
+            Some(vers) => vers,
+            None => return Err(NoMetadata),
+        },
+        None => return Err(NoMetadata),
+    };
+    if data.slice_to(magic.len()) != magic || vers != encoder::rustc_version() {
+        return Err(CompilerMismatch(Some(vers.to_owned())));
+    }
+    Ok(magic.len() + vers.len() + 1)
+}
+
+fn get_metadata_section(sess: Session, os: Os,
+                        filename: &Path) -> Result<@~[u8], MetadataError> {
     if filename.filename_str().unwrap().ends_with(".rlib") {
         let archive = Archive::open(sess, filename.clone());
-        return Some(@archive.read(METADATA_FILENAME));
+        let data = archive.read(METADATA_FILENAME);
+        let len = match check_metadata_header(data) {
+            Ok(len) => len,
+            Err(e) => return Err(e),
+        };
+        return Ok(@data.slice_from(len).to_owned());
     }
     unsafe {
         let mb = filename.with_c_str(|buf| {
             llvm::LLVMRustCreateMemoryBufferWithContentsOfFile(buf)
         });
-        if mb as int == 0 { return None }
+        if mb as int == 0 { return Err(NoMetadata) }
         let of = match ObjectFile::new(mb) {
             Some(of) => of,
-            _ => return None
+            _ => return Err(NoMetadata)
         };
         let si = mk_section_iter(of.llof);
         while llvm::LLVMIsSectionIteratorAtEnd(of.llof, si.llsi) == False {
@@ -266,33 +385,33 @@ fn get_metadata_section(sess: Session, os: Os, filename: &Path) -> Option<@~[u8]
             if read_meta_section_name(os) == name {
                 let cbuf = llvm::LLVMGetSectionContents(si.llsi);
                 let csz = llvm::LLVMGetSectionSize(si.llsi) as uint;
-                let mut found = None;
                 let cvbuf: *u8 = cast::transmute(cbuf);
-                let vlen = encoder::metadata_encoding_version.len();
-                debug!("checking {} bytes of metadata-version stamp",
-                       vlen);
-                let minsz = num::min(vlen, csz);
-                let mut version_ok = false;
-                vec::raw::buf_as_slice(cvbuf, minsz, |buf0| {
-                    version_ok = (buf0 ==
-                                  encoder::metadata_encoding_version);
+                debug!("checking the metadata header");
+                let mut header = Err(NoMetadata);
+                vec::raw::buf_as_slice(cvbuf, csz, |buf0| {
+                    header = check_metadata_header(buf0);
                 });
-                if !version_ok { return None; }
+                let vlen = match header {
+                    Ok(vlen) => vlen,
+                    Err(e) => return Err(e),
+                };
 
                 let cvbuf1 = ptr::offset(cvbuf, vlen as int);
                 debug!("inflating {} bytes of compressed metadata",
                        csz - vlen);
+                let mut found = None;
                 vec::raw::buf_as_slice(cvbuf1, csz-vlen, |bytes| {
                     let inflated = flate::inflate_bytes(bytes);
                     found = Some(@(inflated));
                 });
-                if found != None {
-                    return found;
+                match found {
+                    Some(data) => return Ok(data),
+                    None => {}
                 }
             }
             llvm::LLVMMoveToNextSection(si.llsi);
         }
-        return option::None::<@~[u8]>;
+        return Err(NoMetadata);
     }
 }
 
@@ -316,6 +435,24 @@ pub fn read_meta_section_name(os: Os) -> &'static str {
     }
 }
 
+// Reads the metadata of a library crate, or describes why it can't be read
+pub fn read_file_metadata(sess: Session, os: Os,
+                          path: &Path) -> Result<@~[u8], ~str> {
+    match get_metadata_section(sess, os, path) {
+      Ok(bytes) => Ok(bytes),
+      Err(CompilerMismatch(Some(vers))) => {
+        Err(format!("{} was compiled by rustc {}, this is rustc {}",
+                    path.display(), vers, encoder::rustc_version()))
+      }
+      Err(CompilerMismatch(None)) => {
+        Err(format!("{} was compiled by an older rustc", path.display()))
+      }
+      Err(NoMetadata) => {
+        Err(format!("could not find metadata in {}", path.display()))
+      }
+    }
+}
+
 // A diagnostic function for dumping crate metadata to an output stream
 pub fn list_file_metadata(sess: Session,
                           intr: @ident_interner,
@@ -323,8 +460,15 @@ pub fn list_file_metadata(sess: Session,
                           path: &Path,
                           out: @mut io::Writer) {
     match get_metadata_section(sess, os, path) {
-      option::Some(bytes) => decoder::list_crate_metadata(intr, bytes, out),
-      option::None => {
+      Ok(bytes) => decoder::list_crate_metadata(intr, bytes, out),
+      Err(CompilerMismatch(Some(vers))) => {
+        write!(out, "{} was compiled by rustc {}, this is rustc {}.\n",
+               path.display(), vers, encoder::rustc_version())
+      }
+      Err(CompilerMismatch(None)) => {
+        write!(out, "{} was compiled by an older rustc.\n", path.display())
+      }
+      Err(NoMetadata) => {
         write!(out, "could not find metadata in {}.\n", path.display())
       }
     }
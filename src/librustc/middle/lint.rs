@@ -837,7 +837,7 @@ static other_attrs: &'static [&'static str] = &[
     "allow", "deny", "forbid", "warn", // lint options
     "deprecated", "experimental", "unstable", "stable", "locked", "frozen", //item stability
     "crate_map", "cfg", "doc", "export_name", "link_section", "no_freeze",
-    "no_mangle", "no_send", "static_assert", "unsafe_no_drop_flag",
+    "no_mangle", "no_send", "static_assert", "unsafe_no_drop_flag", "linkage",
     "packed", "simd", "repr", "deriving", "unsafe_destructor", "link",
 
     //mod-level
@@ -845,7 +845,8 @@ static other_attrs: &'static [&'static str] = &[
 
     // fn-level
     "test", "bench", "should_fail", "ignore", "inline", "lang", "main", "start",
-    "no_split_stack", "cold",
+    "no_split_stack", // can be crate-level too
+    "cold", "optimize", "static_constructor", "static_destructor",
 
     // internal attribute: bypass privacy inside items
     "!resolve_unexported",
@@ -890,6 +891,13 @@ fn check_attrs_usage(cx: &Context, attrs: &[ast::Attribute]) {
         if !other_attrs.iter().any(|other_attr| { name.equiv(other_attr) }) {
             cx.span_lint(attribute_usage, attr.span, "unknown attribute");
         }
+
+        if name.equiv(&"optimize") &&
+           attr::find_optimize_attr([attr.clone()]) == attr::OptimizeNone {
+            cx.span_lint(attribute_usage, attr.span,
+                         "unknown optimization goal, expected #[optimize(size)] or \
+                          #[optimize(min_size)]");
+        }
     }
 }
 
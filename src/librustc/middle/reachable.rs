@@ -28,10 +28,14 @@ use syntax::parse::token;
 use syntax::visit::Visitor;
 use syntax::visit;
 
-// Returns true if the given set of attributes contains the `#[inline]`
-// attribute.
+// Returns true if the given set of attributes contains an `#[inline]`
+// attribute which allows inlining. Functions marked `#[inline(never)]` aren't
+// inlined into other crates, so they're translated like any other.
 fn attributes_specify_inlining(attrs: &[ast::Attribute]) -> bool {
-    attr::contains_name(attrs, "inline")
+    match attr::find_inline_attr(attrs) {
+        attr::InlineNone | attr::InlineNever => false,
+        attr::InlineHint | attr::InlineAlways => true,
+    }
 }
 
 // Returns true if the given set of generics implies that the item it's
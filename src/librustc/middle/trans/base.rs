@@ -25,7 +25,7 @@
 
 
 use back::link::{mangle_exported_name};
-use back::{link, abi};
+use back::{link, abi, c_header, metadata_json};
 use driver::session;
 use driver::session::Session;
 use driver::driver::{CrateAnalysis, CrateTranslation};
@@ -74,6 +74,7 @@ use std::vec;
 use std::local_data;
 use extra::time;
 use extra::sort;
+use extra::tempfile::TempDir;
 use syntax::ast::Name;
 use syntax::ast_map::{path, path_elt_to_str, path_name, path_pretty_name};
 use syntax::ast_util::{local_def, is_local};
@@ -85,6 +86,7 @@ use syntax::print::pprust::stmt_to_str;
 use syntax::{ast, ast_util, codemap, ast_map};
 use syntax::attr::AttrMetaMethods;
 use syntax::abi::{X86, X86_64, Arm, Mips, Rust, RustIntrinsic, OsWin32, OsAndroid};
+use syntax::abi::{OsMacos, OsLinux, OsFreebsd};
 use syntax::visit;
 use syntax::visit::Visitor;
 
@@ -466,6 +468,38 @@ pub fn set_uwtable(f: ValueRef) {
     lib::llvm::SetFunctionAttribute(f, lib::llvm::UWTableAttribute)
 }
 
+// Have LLVM guard the stack frame of f with a canary, as requested by
+// `-C stack-protector`. Every function rustc generates code for gets it:
+// those of Rust items through `new_fn_ctxt_w_id`, which the glue, the
+// intrinsics and the visitors of reflection go through as well, along with the
+// wrappers of Rust functions with foreign ABIs and the entry function.
+pub fn set_stack_protector(ccx: &CrateContext, f: ValueRef) {
+    match ccx.sess.opts.cg.stack_protector {
+        session::SspNone => {}
+        session::SspBasic => {
+            lib::llvm::SetFunctionAttribute(f, lib::llvm::StackProtectAttribute)
+        }
+        session::SspStrong => unsafe {
+            llvm::LLVMAddStackProtectStrongAttribute(f)
+        },
+        session::SspAll => {
+            lib::llvm::SetFunctionAttribute(f, lib::llvm::StackProtectReqAttribute)
+        }
+    }
+}
+
+// Selects the code sequence used to access the thread local static g, as
+// requested by `-Z tls-model`
+pub fn set_tls_model(ccx: &CrateContext, g: ValueRef) {
+    let mode = match ccx.sess.opts.debugging_values.tls_model {
+        session::TlsGlobalDynamic => lib::llvm::GeneralDynamicTLSModel,
+        session::TlsLocalDynamic => lib::llvm::LocalDynamicTLSModel,
+        session::TlsInitialExec => lib::llvm::InitialExecTLSModel,
+        session::TlsLocalExec => lib::llvm::LocalExecTLSModel,
+    };
+    lib::llvm::set_thread_local_mode(g, mode);
+}
+
 pub fn set_inline_hint(f: ValueRef) {
     lib::llvm::SetFunctionAttribute(f, lib::llvm::InlineHintAttribute)
 }
@@ -488,6 +522,17 @@ pub fn set_llvm_fn_attrs(attrs: &[ast::Attribute], llfn: ValueRef) {
     if contains_name(attrs, "cold") {
         unsafe { llvm::LLVMAddColdAttribute(llfn) }
     }
+
+    // Functions can be optimized for size whatever the optimization level of
+    // the crate, and `min_size` trades even more speed for size
+    match find_optimize_attr(attrs) {
+        OptimizeSize => set_optimize_for_size(llfn),
+        OptimizeMinSize => {
+            set_optimize_for_size(llfn);
+            unsafe { llvm::LLVMAddMinSizeAttribute(llfn) }
+        }
+        OptimizeNone => {}
+    }
 }
 
 pub fn set_always_inline(f: ValueRef) {
@@ -866,11 +911,23 @@ pub fn trans_external_path(ccx: &mut CrateContext, did: ast::DefId, t: ty::t) ->
         }
         _ => {
             let llty = type_of(ccx, t);
-            get_extern_const(&mut ccx.externs, ccx.llmod, name, llty)
+            let c = get_extern_const(&mut ccx.externs, ccx.llmod, name, llty);
+            set_dllimport(ccx, did, c);
+            c
         }
     }
 }
 
+// A static of an upstream crate which is linked dynamically on Windows lives
+// in that crate's DLL, and is only reachable through its import table entry.
+// Unlike with functions, the linker can't patch this up for us with a thunk,
+// so the global has to be declared as being imported.
+pub fn set_dllimport(ccx: &CrateContext, did: ast::DefId, global: ValueRef) {
+    if ccx.dylib_crates.contains(&did.crate) {
+        lib::llvm::SetLinkage(global, lib::llvm::DLLImportLinkage);
+    }
+}
+
 pub fn invoke(bcx: @mut Block, llfn: ValueRef, llargs: ~[ValueRef],
               attributes: &[(uint, lib::llvm::Attribute)])
            -> (ValueRef, @mut Block) {
@@ -915,7 +972,7 @@ pub fn invoke(bcx: @mut Block, llfn: ValueRef, llargs: ~[ValueRef],
 }
 
 pub fn need_invoke(bcx: @mut Block) -> bool {
-    if (bcx.ccx().sess.opts.debugging_opts & session::no_landing_pads != 0) {
+    if bcx.ccx().sess.no_landing_pads() {
         return false;
     }
 
@@ -1254,8 +1311,7 @@ pub fn trans_block_cleanups_(bcx: @mut Block,
     let _icx = push_ctxt("trans_block_cleanups");
     // NB: Don't short-circuit even if this block is unreachable because
     // GC-based cleanup needs to the see that the roots are live.
-    let no_lpads =
-        bcx.ccx().sess.opts.debugging_opts & session::no_landing_pads != 0;
+    let no_lpads = bcx.ccx().sess.no_landing_pads();
     if bcx.unreachable && !no_lpads { return bcx; }
     let mut bcx = bcx;
     for cu in cleanups.rev_iter() {
@@ -1635,6 +1691,7 @@ pub fn new_fn_ctxt_w_id(ccx: @mut CrateContext,
     };
     let uses_outptr = type_of::return_uses_outptr(ccx, substd_output_type);
     let debug_context = debuginfo::create_function_debug_context(ccx, id, param_substs, llfndecl);
+    set_stack_protector(ccx, llfndecl);
 
     let fcx = @mut FunctionContext {
           llfn: llfndecl,
@@ -2346,6 +2403,7 @@ pub fn create_entry_wrapper(ccx: @mut CrateContext,
             "main"
         };
         let llfn = decl_cdecl_fn(ccx.llmod, main_name, llfty);
+        set_stack_protector(ccx, llfn);
         let llbb = "top".with_c_str(|buf| {
             unsafe {
                 llvm::LLVMAppendBasicBlockInContext(ccx.llcx, llfn, buf)
@@ -2509,6 +2567,7 @@ pub fn get_item_val(ccx: @mut CrateContext, id: ast::NodeId) -> ValueRef {
 
                                 if attr::contains_name(i.attrs, "thread_local") {
                                     lib::llvm::set_thread_local(g, true);
+                                    set_tls_model(ccx, g);
                                 }
 
                                 if !inlineable {
@@ -2530,19 +2589,21 @@ pub fn get_item_val(ccx: @mut CrateContext, id: ast::NodeId) -> ValueRef {
                                                                            i.id)
                             };
                             set_llvm_fn_attrs(i.attrs, llfn);
+                            register_static_ctor(ccx, i, llfn);
                             llfn
                         }
 
                         _ => fail!("get_item_val: weird result in table")
                     };
 
-                    match (attr::first_attr_value_str_by_name(i.attrs, "link_section")) {
-                        Some(sect) => unsafe {
+                    match attr::first_attr_value_str_by_name(i.attrs, "link_section") {
+                        Some(sect) if check_link_section(ccx, sect.as_slice(), i.span) => {
                             sect.with_c_str(|buf| {
-                                llvm::LLVMSetSection(v, buf);
-                            })
-                        },
-                        None => ()
+                                unsafe { llvm::LLVMSetSection(v, buf); }
+                            });
+                            ccx.kept_items.insert(i.id);
+                        }
+                        _ => ()
                     }
 
                     v
@@ -2666,17 +2727,191 @@ pub fn get_item_val(ccx: @mut CrateContext, id: ast::NodeId) -> ValueRef {
 
             // foreign items (extern fns and extern statics) don't have internal
             // linkage b/c that doesn't quite make sense. Otherwise items can
-            // have internal linkage if they're not reachable.
-            if !foreign && !ccx.reachable.contains(&id) {
+            // have internal linkage if they're not reachable, save for those
+            // in sections of their own and static constructors, which
+            // nothing may refer to at all.
+            if !foreign && !ccx.reachable.contains(&id) &&
+               !ccx.kept_items.contains(&id) {
                 lib::llvm::SetLinkage(val, lib::llvm::InternalLinkage);
             }
 
+            // A linkage given with `#[linkage]` overrides either
+            let linkage = match ccx.tcx.items.find(&id) {
+                Some(&ast_map::node_item(i, _)) => {
+                    linkage_attr(ccx, i.attrs, i.span, false)
+                }
+                Some(&ast_map::node_foreign_item(ni, _, _, _)) => {
+                    linkage_attr(ccx, ni.attrs, ni.span, true)
+                }
+                _ => None
+            };
+            match linkage {
+                Some(linkage) => {
+                    lib::llvm::SetLinkage(val, linkage);
+                    if !foreign {
+                        ccx.linkage_items.insert(id);
+                    }
+                }
+                None => {}
+            }
+
             ccx.item_vals.insert(id, val);
             val
         }
     }
 }
 
+// Registers a function marked `#[static_constructor]` or `#[static_destructor]`
+// to be run before `main` starts or after it returns, or as a dylib is loaded
+// or unloaded. The value of the attribute is an optional priority from 101 to
+// 65535, the default: constructors of lower priority run first and
+// destructors of lower priority last. The lower ones are the C runtime's.
+fn register_static_ctor(ccx: @mut CrateContext, item: &ast::item, llfn: ValueRef) {
+    for &(name, dtor) in [("static_constructor", false),
+                          ("static_destructor", true)].iter() {
+        let attr = match item.attrs.iter().find(|a| a.name().equiv(&name)) {
+            Some(attr) => attr,
+            None => continue
+        };
+        let signature_ok = match item.node {
+            ast::item_fn(_, ast::extern_fn, _, ref generics, _) => {
+                !generics.is_type_parameterized() &&
+                match ty::get(ty::node_id_to_type(ccx.tcx, item.id)).sty {
+                    ty::ty_bare_fn(ref f) => {
+                        f.sig.inputs.is_empty() && ty::type_is_nil(f.sig.output)
+                    }
+                    _ => false
+                }
+            }
+            _ => false
+        };
+        if !signature_ok {
+            ccx.sess.span_err(item.span, "static constructors and destructors \
+                                          must be non-generic `extern fn()`s");
+            continue
+        }
+        let priority = match attr.value_str() {
+            None => 65535,
+            Some(s) => match from_str::<uint>(s.as_slice()) {
+                Some(p) if p > 100 && p <= 65535 => p,
+                _ => {
+                    ccx.sess.span_err(attr.span, format!("invalid priority `{}` \
+                                                          of a static constructor \
+                                                          or destructor, expected \
+                                                          101 to 65535", s));
+                    continue
+                }
+            }
+        };
+        if dtor {
+            ccx.static_dtors.push((priority, llfn));
+        } else {
+            ccx.static_ctors.push((priority, llfn));
+        }
+        ccx.kept_items.insert(item.id);
+    }
+}
+
+// Emits the static constructors or destructors of the crate as the
+// `llvm.global_ctors` or `llvm.global_dtors` array, which LLVM places in the
+// section the target runs them from (`.init_array` or `.ctors` and the like).
+// Those of the same priority run in the order they're defined in.
+fn declare_static_ctors(ccx: &CrateContext, name: &str, fns: &[(uint, ValueRef)]) {
+    if fns.is_empty() { return }
+    let fn_ty = Type::func([], &Type::void()).ptr_to();
+    let entry_ty = Type::struct_([Type::i32(), fn_ty], false);
+    // Sorted by priority, keeping the order of definition among equal ones
+    let mut sorted: ~[(uint, ValueRef)] = ~[];
+    for &(p, llfn) in fns.iter() {
+        let pos = sorted.iter().position(|&(q, _)| q > p).unwrap_or(sorted.len());
+        sorted.insert(pos, (p, llfn));
+    }
+    let entries = sorted.map(|&(p, llfn)| {
+        let llfn = unsafe { llvm::LLVMConstBitCast(llfn, fn_ty.to_ref()) };
+        C_struct([C_i32(p as i32), llfn], false)
+    });
+    let array = C_array(entry_ty, entries);
+    unsafe {
+        let g = name.with_c_str(|buf| {
+            llvm::LLVMAddGlobal(ccx.llmod, val_ty(array).to_ref(), buf)
+        });
+        llvm::LLVMSetInitializer(g, array);
+        lib::llvm::SetLinkage(g, lib::llvm::AppendingLinkage);
+    }
+}
+
+// Checks that a section given with `#[link_section]` can be had in the object
+// format of the target. A Mach-O section is named by its segment and section,
+// of at most 16 characters each, and the name of a COFF section is cut to 8
+// characters in an image. The metadata section is off limits everywhere.
+fn check_link_section(ccx: &CrateContext, sect: &str, sp: Span) -> bool {
+    if sect.is_empty() || sect.contains_char('\0') {
+        ccx.sess.span_err(sp, format!("invalid section name `{}`", sect.escape_default()));
+        return false
+    }
+    if sect == ccx.sess.targ_cfg.target_strs.meta_sect_name.as_slice() {
+        ccx.sess.span_err(sp, format!("section `{}` is reserved for the crate's \
+                                       metadata", sect));
+        return false
+    }
+    match ccx.sess.targ_cfg.os {
+        OsMacos => {
+            let parts = sect.split(',').to_owned_vec();
+            if parts.len() < 2 || parts.slice_to(2).iter().any(|p| {
+                p.is_empty() || p.len() > 16
+            }) {
+                ccx.sess.span_err(sp, format!("section `{}` isn't of the form \
+                                               `segment,section` with names of \
+                                               at most 16 characters, as Mach-O \
+                                               requires", sect));
+                return false
+            }
+        }
+        OsWin32 => {
+            if sect.len() > 8 {
+                ccx.sess.span_err(sp, format!("section `{}` is longer than the 8 \
+                                               characters COFF allows", sect));
+                return false
+            }
+        }
+        OsLinux | OsAndroid | OsFreebsd => {}
+    }
+    true
+}
+
+// The linkage of an item given with `#[linkage = "..."]`. Functions and
+// statics may be defined with `weak` linkage, which lets another definition of
+// the symbol take precedence, or `linkonce_odr`, and foreign items declared
+// with `external_weak` linkage, which leaves them null when nothing defines
+// them.
+fn linkage_attr(ccx: &CrateContext, attrs: &[ast::Attribute], sp: Span,
+                foreign: bool) -> Option<lib::llvm::Linkage> {
+    let name = match attr::first_attr_value_str_by_name(attrs, "linkage") {
+        Some(name) => name,
+        None => return None
+    };
+    match (name.as_slice(), foreign) {
+        ("weak", false) => Some(lib::llvm::WeakAnyLinkage),
+        ("linkonce_odr", false) => Some(lib::llvm::LinkOnceODRLinkage),
+        ("external_weak", true) => Some(lib::llvm::ExternalWeakLinkage),
+        ("weak", true) | ("linkonce_odr", true) => {
+            ccx.sess.span_err(sp, format!("foreign items can't have `{}` \
+                                           linkage, only definitions can", name));
+            None
+        }
+        ("external_weak", false) => {
+            ccx.sess.span_err(sp, "only foreign items can have `external_weak` \
+                                   linkage");
+            None
+        }
+        (name, _) => {
+            ccx.sess.span_err(sp, format!("unknown linkage `{}`, expected weak, \
+                                           external_weak or linkonce_odr", name));
+            None
+        }
+    }
+}
+
 pub fn register_method(ccx: @mut CrateContext,
                        id: ast::NodeId,
                        path: @ast_map::path,
@@ -2934,7 +3169,9 @@ pub fn decl_crate_map(sess: session::Session, mapmeta: LinkMeta,
     let mut n_subcrates = 1;
     let cstore = sess.cstore;
     while cstore::have_crate_data(cstore, n_subcrates) { n_subcrates += 1; }
-    let is_top = !*sess.building_library || sess.gen_crate_map();
+    // A cdylib contains the runtime, so it needs a toplevel crate map
+    let is_top = !*sess.building_library || sess.gen_crate_map() ||
+                 sess.outputs.iter().any(|&o| o == session::OutputCdylib);
     let sym_name = if is_top {
         ~"_rust_crate_map_toplevel"
     } else {
@@ -3040,9 +3277,15 @@ pub fn crate_ctxt_to_encode_parms<'r>(cx: &'r CrateContext, ie: encoder::encode_
             cstore: cx.sess.cstore,
             encode_inlined_item: ie,
             reachable: cx.reachable,
+            kept_items: &cx.kept_items,
         }
 }
 
+pub fn metadata_symbol_name(link_meta: &LinkMeta) -> ~str {
+    format!("rust_metadata_{}_{}_{}", link_meta.pkgid.name,
+            link_meta.pkgid.version_or_default(), link_meta.crate_hash)
+}
+
 pub fn write_metadata(cx: &CrateContext, crate: &ast::Crate) -> ~[u8] {
     use extra::flate;
 
@@ -3054,12 +3297,11 @@ pub fn write_metadata(cx: &CrateContext, crate: &ast::Crate) -> ~[u8] {
 
     let encode_parms = crate_ctxt_to_encode_parms(cx, encode_inlined_item);
     let metadata = encoder::encode_metadata(encode_parms, crate);
-    let compressed = encoder::metadata_encoding_version +
+    let compressed = encoder::metadata_header() +
                         flate::deflate_bytes(metadata);
-    let llmeta = C_bytes(compressed);
-    let llconst = C_struct([llmeta], false);
-    let name = format!("rust_metadata_{}_{}_{}", cx.link_meta.pkgid.name,
-                       cx.link_meta.pkgid.version_or_default(), cx.link_meta.crate_hash);
+    let llmeta = C_bytes_in_context(cx.metadata_llcx, compressed);
+    let llconst = C_struct_in_context(cx.metadata_llcx, [llmeta], false);
+    let name = metadata_symbol_name(&cx.link_meta);
     let llglobal = name.with_c_str(|buf| {
         unsafe {
             llvm::LLVMAddGlobal(cx.metadata_llmod, val_ty(llconst).to_ref(), buf)
@@ -3084,7 +3326,7 @@ pub fn trans_crate(sess: session::Session,
     }
 
     let mut symbol_hasher = Sha256::new();
-    let link_meta = link::build_link_meta(sess, &crate, output,
+    let link_meta = link::build_link_meta(sess, &crate, output, analysis,
                                           &mut symbol_hasher);
 
     // Append ".rc" to crate name as LLVM module identifier.
@@ -3112,6 +3354,8 @@ pub fn trans_crate(sess: session::Session,
 
     decl_gc_metadata(ccx, llmod_id);
     fill_crate_map(ccx, ccx.crate_map);
+    declare_static_ctors(ccx, "llvm.global_ctors", ccx.static_ctors);
+    declare_static_ctors(ccx, "llvm.global_dtors", ccx.static_dtors);
 
     // win32: wart with exporting crate_map symbol
     // We set the crate map (_rust_crate_map_toplevel) to use dll_export
@@ -3134,11 +3378,13 @@ pub fn trans_crate(sess: session::Session,
 
     glue::emit_tydescs(ccx);
     if ccx.sess.opts.debuginfo {
-        debuginfo::finalize(ccx);
+        debuginfo::finalize(ccx, output);
     }
 
     // Translate the metadata.
     let metadata = write_metadata(ccx, &crate);
+    let unmangled_symbols = link::unmangled_symbols(ccx.tcx, ccx.reachable,
+                                                    &ccx.item_symbols);
     if ccx.sess.trans_stats() {
         println("--- trans stats ---");
         println!("n_static_tydescs: {}", ccx.stats.n_static_tydescs);
@@ -3169,13 +3415,83 @@ pub fn trans_crate(sess: session::Session,
         }
     }
 
+    let c_header = if sess.opts.emit_c_header {
+        Some(c_header::generate(ccx))
+    } else {
+        None
+    };
+    let metadata_json = if sess.opts.emit_metadata_json {
+        Some(metadata_json::generate(ccx, &analysis.exported_items))
+    } else {
+        None
+    };
+
     let llcx = ccx.llcx;
     let link_meta = ccx.link_meta.clone();
     let llmod = ccx.llmod;
-    let mut reachable = ccx.reachable.iter().filter_map(|id| {
+    let mut reachable = ccx.reachable.iter().chain(ccx.linkage_items.iter()).filter_map(|id| {
         ccx.item_symbols.find(id).map(|s| s.to_owned())
     }).to_owned_vec();
 
+    // The symbols of the items in sections of their own and of the static
+    // constructors are kept by LTO as well as by the linker
+    let mut kept_symbols = ccx.kept_items.iter().filter_map(|id| {
+        ccx.item_symbols.find(id).map(|s| s.to_owned())
+    }).to_owned_vec();
+    sort::quick_sort(kept_symbols, |a, b| *a <= *b);
+    reachable.push_all(kept_symbols);
+
+    // The symbols a library has to export are its reachable items (save for
+    // foreign items, which it doesn't define), those with a linkage of their
+    // own and its lang items, along with its crate map (which downstream crate
+    // maps refer to) and its metadata.
+    let mut exported_symbols = ~[];
+    for id in ccx.reachable.iter().chain(ccx.linkage_items.iter()) {
+        match ccx.tcx.items.find(id) {
+            Some(&ast_map::node_foreign_item(..)) => continue,
+            _ => {}
+        }
+        match ccx.item_symbols.find(id) {
+            Some(sym) => exported_symbols.push(sym.clone()),
+            None => {}
+        }
+    }
+    for (_, item) in ccx.tcx.lang_items.items() {
+        match *item {
+            Some(did) if is_local(did) => {
+                match ccx.item_symbols.find(&did.node) {
+                    Some(sym) => exported_symbols.push(sym.clone()),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    sort::quick_sort(exported_symbols, |a, b| *a <= *b);
+    exported_symbols.dedup();
+
+    // Windows needs to know which of them are data rather than code (see
+    // `def_file` in back::link)
+    let mut exported_statics = ~[];
+    for id in ccx.reachable.iter() {
+        let is_static = match ccx.tcx.items.find(id) {
+            Some(&ast_map::node_item(i, _)) => {
+                match i.node { ast::item_static(..) => true, _ => false }
+            }
+            _ => false
+        };
+        if !is_static { continue }
+        match ccx.item_symbols.find(id) {
+            Some(sym) => exported_statics.push(sym.clone()),
+            None => {}
+        }
+    }
+    sort::quick_sort(exported_statics, |a, b| *a <= *b);
+    if *sess.building_library {
+        exported_symbols.push(ccx.crate_map_name.to_owned());
+        exported_symbols.push(metadata_symbol_name(&link_meta));
+    }
+
     // Make sure that some other crucial symbols are not eliminated from the
     // module. This includes the main function (main/amain elsewhere), the crate
     // map (used for debug log settings and I/O), and finally the curious
@@ -3187,12 +3503,26 @@ pub fn trans_crate(sess: session::Session,
     reachable.push(~"amain");
     reachable.push(~"rust_stack_exhausted");
 
+    // A custom entry point is only referred to by the linker
+    match sess.opts.cg.entry {
+        Some(ref entry) => reachable.push(entry.clone()),
+        None => {}
+    }
+
     return CrateTranslation {
         context: llcx,
         module: llmod,
         link: link_meta,
+        metadata_context: ccx.metadata_llcx,
         metadata_module: ccx.metadata_llmod,
         metadata: metadata,
         reachable: reachable,
+        exported_symbols: exported_symbols,
+        exported_statics: exported_statics,
+        unmangled_symbols: unmangled_symbols,
+        kept_symbols: kept_symbols,
+        c_header: c_header,
+        metadata_json: metadata_json,
+        tmpdir: TempDir::new("rustc").expect("needs a temp dir"),
     };
 }
@@ -13,7 +13,7 @@
 
 use driver::session;
 use driver::session::Session;
-use lib::llvm::{ValueRef, BasicBlockRef, BuilderRef};
+use lib::llvm::{ValueRef, BasicBlockRef, BuilderRef, ContextRef};
 use lib::llvm::{True, False, Bool};
 use lib::llvm::llvm;
 use lib;
@@ -951,9 +951,13 @@ pub fn C_zero_byte_arr(size: uint) -> ValueRef {
 }
 
 pub fn C_struct(elts: &[ValueRef], packed: bool) -> ValueRef {
+    C_struct_in_context(base::task_llcx(), elts, packed)
+}
+
+pub fn C_struct_in_context(llcx: ContextRef, elts: &[ValueRef], packed: bool) -> ValueRef {
     unsafe {
         elts.as_imm_buf(|ptr, len| {
-            llvm::LLVMConstStructInContext(base::task_llcx(), ptr, len as c_uint, packed as Bool)
+            llvm::LLVMConstStructInContext(llcx, ptr, len as c_uint, packed as Bool)
         })
     }
 }
@@ -973,9 +977,13 @@ pub fn C_array(ty: Type, elts: &[ValueRef]) -> ValueRef {
 }
 
 pub fn C_bytes(bytes: &[u8]) -> ValueRef {
+    C_bytes_in_context(base::task_llcx(), bytes)
+}
+
+pub fn C_bytes_in_context(llcx: ContextRef, bytes: &[u8]) -> ValueRef {
     unsafe {
         let ptr = cast::transmute(vec::raw::to_ptr(bytes));
-        return llvm::LLVMConstStringInContext(base::task_llcx(), ptr, bytes.len() as c_uint, True);
+        return llvm::LLVMConstStringInContext(llcx, ptr, bytes.len() as c_uint, True);
     }
 }
 
@@ -9,7 +9,7 @@
 // except according to those terms.
 
 
-use back::{upcall};
+use back::{link, upcall};
 use driver::session;
 use lib::llvm::{ContextRef, ModuleRef, ValueRef};
 use lib::llvm::{llvm, TargetData, TypeNames};
@@ -23,6 +23,7 @@ use middle::trans::builder::Builder;
 use middle::trans::debuginfo;
 use middle::trans::common::{C_i32, C_null};
 use middle::ty;
+use syntax::abi;
 
 use middle::trans::type_::Type;
 
@@ -43,6 +44,9 @@ pub struct CrateContext {
      sess: session::Session,
      llmod: ModuleRef,
      llcx: ContextRef,
+     // The metadata module has a context of its own, so that its code can be
+     // generated alongside that of the crate's module
+     metadata_llcx: ContextRef,
      metadata_llmod: ModuleRef,
      td: TargetData,
      tn: TypeNames,
@@ -69,6 +73,17 @@ pub struct CrateContext {
      // will pevent in ii_item() structures from being encoded into the metadata
      // that is generated
      non_inlineable_statics: HashSet<ast::NodeId>,
+     // The functions and statics defined with a `#[linkage]` of their own,
+     // which stay visible to the linker even if nothing else refers to them
+     linkage_items: HashSet<ast::NodeId>,
+     // The functions and statics placed in a section with `#[link_section]`
+     // and the static constructors and destructors, which the linker has to
+     // keep even if nothing refers to them
+     kept_items: HashSet<ast::NodeId>,
+     // The static constructors and destructors with their priorities, which
+     // go into `llvm.global_ctors` and `llvm.global_dtors`
+     static_ctors: ~[(uint, ValueRef)],
+     static_dtors: ~[(uint, ValueRef)],
      // Cache instances of monomorphized functions
      monomorphized: HashMap<mono_id, ValueRef>,
      monomorphizing: HashMap<ast::DefId, uint>,
@@ -93,6 +108,10 @@ pub struct CrateContext {
      // Cache of external const values
      extern_const_values: HashMap<ast::DefId, ValueRef>,
 
+     // The upstream crates whose statics have to be imported from their DLLs
+     // (see `base::set_dllimport`)
+     dylib_crates: HashSet<ast::CrateNum>,
+
      impl_method_cache: HashMap<(ast::DefId, ast::Name), ast::DefId>,
 
      module_data: HashMap<~str, ValueRef>,
@@ -137,8 +156,9 @@ impl CrateContext {
             let llmod = name.with_c_str(|buf| {
                 llvm::LLVMModuleCreateWithNameInContext(buf, llcx)
             });
+            let metadata_llcx = llvm::LLVMContextCreate();
             let metadata_llmod = format!("{}_metadata", name).with_c_str(|buf| {
-                llvm::LLVMModuleCreateWithNameInContext(buf, llcx)
+                llvm::LLVMModuleCreateWithNameInContext(buf, metadata_llcx)
             });
             let data_layout: &str = sess.targ_cfg.target_strs.data_layout;
             let targ_triple: &str = sess.targ_cfg.target_strs.target_triple;
@@ -170,6 +190,12 @@ impl CrateContext {
             tn.associate_type("str_slice", &str_slice_ty);
 
             let (crate_map_name, crate_map) = decl_crate_map(sess, link_meta.clone(), llmod);
+            let dylib_crates = if targ_cfg.os == abi::OsWin32 {
+                link::dynamic_crates(sess)
+            } else {
+                HashSet::new()
+            };
+
             let dbg_cx = if sess.opts.debuginfo {
                 Some(debuginfo::CrateDebugContext::new(llmod, name.to_owned()))
             } else {
@@ -184,6 +210,7 @@ impl CrateContext {
                   sess: sess,
                   llmod: llmod,
                   llcx: llcx,
+                  metadata_llcx: metadata_llcx,
                   metadata_llmod: metadata_llmod,
                   td: td,
                   tn: tn,
@@ -202,6 +229,10 @@ impl CrateContext {
                   external: HashMap::new(),
                   external_srcs: HashMap::new(),
                   non_inlineable_statics: HashSet::new(),
+                  linkage_items: HashSet::new(),
+                  kept_items: HashSet::new(),
+                  static_ctors: ~[],
+                  static_dtors: ~[],
                   monomorphized: HashMap::new(),
                   monomorphizing: HashMap::new(),
                   vtables: HashMap::new(),
@@ -209,6 +240,7 @@ impl CrateContext {
                   const_globals: HashMap::new(),
                   const_values: HashMap::new(),
                   extern_const_values: HashMap::new(),
+                  dylib_crates: dylib_crates,
                   impl_method_cache: HashMap::new(),
                   module_data: HashMap::new(),
                   lltypes: HashMap::new(),
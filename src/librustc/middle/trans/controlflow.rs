@@ -350,6 +350,9 @@ fn trans_fail_value(bcx: @mut Block,
                  -> @mut Block {
     let _icx = push_ctxt("trans_fail_value");
     let ccx = bcx.ccx();
+    if ccx.sess.abort_on_failure() {
+        return trans_abort(bcx);
+    }
     let (V_filename, V_line) = match sp_opt {
       Some(sp) => {
         let sess = bcx.sess();
@@ -373,6 +376,9 @@ fn trans_fail_value(bcx: @mut Block,
 pub fn trans_fail_bounds_check(bcx: @mut Block, sp: Span,
                                index: ValueRef, len: ValueRef) -> @mut Block {
     let _icx = push_ctxt("trans_fail_bounds_check");
+    if bcx.ccx().sess.abort_on_failure() {
+        return trans_abort(bcx);
+    }
     let (filename, line) = filename_and_line_num_from_span(bcx, sp);
     let args = ~[filename, line, index, len];
     let did = langcall(bcx, Some(sp), "", FailBoundsCheckFnLangItem);
@@ -380,3 +386,11 @@ pub fn trans_fail_bounds_check(bcx: @mut Block, sp: Span,
     Unreachable(bcx);
     return bcx;
 }
+
+// With `-Z abort-on-failure`, failing stops the process right away rather
+// than starting to unwind the task
+fn trans_abort(bcx: @mut Block) -> @mut Block {
+    trap(bcx);
+    Unreachable(bcx);
+    return bcx;
+}
@@ -90,6 +90,9 @@ may change in the future)
 
 use driver::session;
 use lib::llvm::llvm;
+use lib::llvm::True;
+use lib;
+use metadata::filesearch;
 use lib::llvm::{ModuleRef, ContextRef, ValueRef};
 use lib::llvm::debuginfo::*;
 use middle::trans::common::*;
@@ -109,6 +112,7 @@ use std::libc::{c_uint, c_ulonglong, c_longlong};
 use std::ptr;
 use std::unstable::atomics;
 use std::vec;
+use syntax::abi;
 use syntax::codemap::{Span, Pos};
 use syntax::{ast, codemap, ast_util, ast_map, opt_vec};
 use syntax::parse::token;
@@ -228,20 +232,95 @@ enum VariableKind {
     CapturedVariable,
 }
 
-/// Create any deferred debug metadata nodes
-pub fn finalize(cx: @mut CrateContext) {
+/// Create any deferred debug metadata nodes. The debug info of the object file
+/// written to `output` is split off into a .dwo file if requested.
+pub fn finalize(cx: @mut CrateContext, output: &Path) {
     if cx.dbg_cx.is_none() {
         return;
     }
 
     debug!("finalize");
-    compile_unit_metadata(cx);
+    compile_unit_metadata(cx, output);
+    if needs_gdb_scripts_section(cx) {
+        insert_gdb_scripts_section(cx);
+    }
+    // The Windows debuggers read CodeView, which LLVM emits instead of DWARF
+    // when the module asks for it
+    if cx.sess.codeview() {
+        "CodeView".with_c_str(|name| {
+            unsafe {
+                llvm::LLVMRustAddModuleFlag(cx.llmod, name, 1);
+            }
+        })
+    }
+    match cx.sess.opts.debugging_values.dwarf_version {
+        Some(_) if cx.sess.codeview() => {}
+        Some(version) => {
+            "Dwarf Version".with_c_str(|name| {
+                unsafe {
+                    llvm::LLVMRustAddModuleFlag(cx.llmod, name, version as u32);
+                }
+            })
+        }
+        None => {}
+    }
     unsafe {
         llvm::LLVMDIBuilderFinalize(DIB(cx));
         llvm::LLVMDIBuilderDispose(DIB(cx));
     };
 }
 
+/// The name of the gdb script with the pretty-printers for Rust values, as
+/// installed into the sysroot.
+pub static GDB_SCRIPT: &'static str = "gdb_rust_pretty_printing.py";
+
+fn needs_gdb_scripts_section(cx: &CrateContext) -> bool {
+    !cx.sess.opts.cg.no_gdb_scripts &&
+        match cx.sess.targ_cfg.os {
+            abi::OsLinux | abi::OsAndroid | abi::OsFreebsd => true,
+            abi::OsMacos | abi::OsWin32 => false,
+        }
+}
+
+// gdb loads the scripts named in the .debug_gdb_scripts section of a program,
+// which is how the pretty-printers are found without any setup. Each entry is
+// the byte 1 (a python script) followed by the null terminated path of the
+// script. Every crate inserts the same entry, so the copies are merged by the
+// linker, and the entry is marked as used so that it survives LTO.
+fn insert_gdb_scripts_section(cx: &CrateContext) {
+    let script = cx.sess.filesearch.sysroot()
+                   .join_many([filesearch::libdir(), ~"rustlib", ~"etc"])
+                   .join(GDB_SCRIPT);
+    let mut contents = ~[1u8];
+    contents.push_all(script.as_vec());
+    contents.push(0u8);
+    let llcontents = C_bytes(contents);
+
+    unsafe {
+        let llglobal = "__rustc_debug_gdb_scripts_section__".with_c_str(|buf| {
+            llvm::LLVMAddGlobal(cx.llmod, val_ty(llcontents).to_ref(), buf)
+        });
+        llvm::LLVMSetInitializer(llglobal, llcontents);
+        llvm::LLVMSetGlobalConstant(llglobal, True);
+        lib::llvm::SetLinkage(llglobal, lib::llvm::WeakODRLinkage);
+        ".debug_gdb_scripts".with_c_str(|buf| {
+            llvm::LLVMSetSection(llglobal, buf)
+        });
+
+        let llused = C_array(Type::i8p(),
+                             [llvm::LLVMConstBitCast(llglobal,
+                                                     Type::i8p().to_ref())]);
+        let llusedglobal = "llvm.used".with_c_str(|buf| {
+            llvm::LLVMAddGlobal(cx.llmod, val_ty(llused).to_ref(), buf)
+        });
+        llvm::LLVMSetInitializer(llusedglobal, llused);
+        lib::llvm::SetLinkage(llusedglobal, lib::llvm::AppendingLinkage);
+        "llvm.metadata".with_c_str(|buf| {
+            llvm::LLVMSetSection(llusedglobal, buf)
+        });
+    }
+}
+
 /// Creates debug information for the given local variable.
 ///
 /// Adds the created metadata nodes directly to the crate's IR.
@@ -684,8 +763,13 @@ pub fn create_function_debug_context(cx: &mut CrateContext,
         source_locations_enabled: false,
     };
 
-    let arg_pats = fn_decl.inputs.map(|arg_ref| arg_ref.pat);
-    populate_scope_map(cx, arg_pats, top_level_block, fn_metadata, &mut fn_debug_context.scope_map);
+    // With line tables only, every line is in the scope of the function itself
+    // (see `scope_metadata`)
+    if !cx.sess.line_tables_only() {
+        let arg_pats = fn_decl.inputs.map(|arg_ref| arg_ref.pat);
+        populate_scope_map(cx, arg_pats, top_level_block, fn_metadata,
+                           &mut fn_debug_context.scope_map);
+    }
 
     return FunctionDebugContext(fn_debug_context);
 
@@ -864,9 +948,15 @@ fn create_DIArray(builder: DIBuilderRef, arr: &[DIDescriptor]) -> DIArray {
     };
 }
 
-fn compile_unit_metadata(cx: @mut CrateContext) {
+fn compile_unit_metadata(cx: @mut CrateContext, output: &Path) {
     let dcx = debug_context(cx);
     let crate_name: &str = dcx.crate_file;
+    // FIXME (#9639): This needs to handle non-utf8 paths
+    let split_name = if cx.sess.split_dwarf() {
+        output.with_extension("dwo").as_str().unwrap().to_owned()
+    } else {
+        ~""
+    };
 
     debug!("compile_unit_metadata: {:?}", crate_name);
 
@@ -878,7 +968,7 @@ fn compile_unit_metadata(cx: @mut CrateContext) {
         work_dir.with_c_str(|work_dir| {
             producer.with_c_str(|producer| {
                 "".with_c_str(|flags| {
-                    "".with_c_str(|split_name| {
+                    split_name.with_c_str(|split_name| {
                         unsafe {
                             llvm::LLVMDIBuilderCreateCompileUnit(
                                 dcx.builder,
@@ -1012,7 +1102,11 @@ fn scope_metadata(fcx: &FunctionContext,
                   node_id: ast::NodeId,
                   span: Span)
                -> DIScope {
-    let scope_map = &fcx.debug_context.get_ref(fcx.ccx, span).scope_map;
+    let debug_context = fcx.debug_context.get_ref(fcx.ccx, span);
+    if fcx.ccx.sess.line_tables_only() {
+        return debug_context.fn_metadata;
+    }
+    let scope_map = &debug_context.scope_map;
 
     match scope_map.find_copy(&node_id) {
         Some(scope_metadata) => scope_metadata,
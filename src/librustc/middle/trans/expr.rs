@@ -1038,6 +1038,7 @@ fn trans_lvalue_unadjusted(bcx: @mut Block, expr: &ast::Expr) -> DatumBlock {
                                                     llty.to_ref(),
                                                     buf)
                             });
+                            base::set_dllimport(bcx.ccx(), did, llval);
                             let extern_const_values = &mut bcx.ccx().extern_const_values;
                             extern_const_values.insert(did, llval);
                             llval
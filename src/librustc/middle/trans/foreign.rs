@@ -508,6 +508,7 @@ pub fn trans_rust_fn_with_foreign_abi(ccx: @mut CrateContext,
         //         return r;
         //     }
 
+        base::set_stack_protector(ccx, llwrapfn);
         let the_block =
             "the block".with_c_str(
                 |s| llvm::LLVMAppendBasicBlockInContext(ccx.llcx, llwrapfn, s));
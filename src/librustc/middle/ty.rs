@@ -1403,6 +1403,12 @@ pub fn fold_regions(cx: ctxt, ty: t, fldr: |r: Region| -> Region) -> t {
     ty_fold::RegionFolder::regions(cx, fldr).fold_ty(ty)
 }
 
+// Replaces every region in a type with 'static, as trans does not care
+// about regions and they must not leak into symbol hashes.
+pub fn erase_regions(cx: ctxt, ty: t) -> t {
+    fold_regions(cx, ty, |_| ReStatic)
+}
+
 // Substitute *only* type parameters.  Used in trans where regions are erased.
 pub fn subst_tps(tcx: ctxt, tps: &[t], self_ty_opt: Option<t>, typ: t) -> t {
     let mut subst = TpsSubst { tcx: tcx, self_ty_opt: self_ty_opt, tps: tps };
@@ -15,8 +15,21 @@ use syntax::visit;
 use syntax::visit::Visitor;
 
 use std::hashmap::HashSet;
+use std::io;
 use std::local_data;
+use std::unstable::atomics;
 use extra;
+use extra::json;
+use extra::treemap::TreeMap;
+
+// Global rather than task-local so that the codegen worker tasks see it too
+static mut json_times: atomics::AtomicBool = atomics::INIT_ATOMIC_BOOL;
+
+/// Makes `time` print one JSON record per pass to stderr instead of indented
+/// text, as asked for with `-Z time-passes=json`
+pub fn set_json_times(json: bool) {
+    unsafe { json_times.store(json, atomics::SeqCst) }
+}
 
 pub fn time<T, U>(do_it: bool, what: &str, u: U, f: |U| -> T) -> T {
     local_data_key!(depth: uint);
@@ -29,12 +42,49 @@ pub fn time<T, U>(do_it: bool, what: &str, u: U, f: |U| -> T) -> T {
     let rv = f(u);
     let end = extra::time::precise_time_s();
 
-    println!("{}time: {:3.3f} s\t{}", "  ".repeat(old), end - start, what);
+    if unsafe { json_times.load(atomics::SeqCst) } {
+        let mut record = ~TreeMap::new();
+        record.insert(~"pass", json::String(what.to_owned()));
+        record.insert(~"depth", json::Number(old as f64));
+        record.insert(~"time", json::Number(end - start));
+        match resident_memory() {
+            Some(bytes) => {
+                record.insert(~"rss", json::Number(bytes as f64));
+            }
+            None => {}
+        }
+        // Kept off stdout, which may be carrying `--pretty` or `--print`
+        // output at the same time
+        let line = json::Object(record).to_str() + "\n";
+        io::stderr().write(line.as_bytes());
+    } else {
+        println!("{}time: {:3.3f} s\t{}", "  ".repeat(old), end - start, what);
+    }
     local_data::set(depth, old);
 
     rv
 }
 
+// The resident set size of the compiler in bytes, where the OS tells us
+#[cfg(target_os = "linux")]
+#[cfg(target_os = "android")]
+fn resident_memory() -> Option<uint> {
+    use std::io::fs::File;
+    use std::os;
+    use std::str;
+
+    let statm = File::open(&Path::new("/proc/self/statm")).read_to_end();
+    let statm = match str::from_utf8_opt(statm) {
+        Some(s) => s,
+        None => return None,
+    };
+    statm.words().nth(1).and_then(|pages| from_str::<uint>(pages))
+                        .map(|pages| pages * os::page_size())
+}
+
+#[cfg(not(target_os = "linux"), not(target_os = "android"))]
+fn resident_memory() -> Option<uint> { None }
+
 pub fn indent<R>(op: || -> R) -> R {
     // Use in conjunction with the log post-processor like `src/etc/indenter`
     // to make debug output more readable.
@@ -20,6 +20,7 @@ use syntax;
 use std::os;
 use std::local_data;
 use std::hashmap::{HashSet};
+use extra::getopts;
 
 use visit_ast::RustdocVisitor;
 use clean;
@@ -47,12 +48,16 @@ fn get_ast_and_resolve(cpath: &Path,
     let parsesess = parse::new_parse_sess(None);
     let input = file_input(cpath.clone());
 
+    // Going through rustc's option parsing picks up the options of RUSTFLAGS
+    let matches = getopts::groups::getopts([], driver::driver::optgroups()).unwrap();
     let sessopts = @driver::session::options {
         binary: @"rustdoc",
         maybe_sysroot: Some(@os::self_exe_path().unwrap().dir_path()),
         addl_lib_search_paths: @mut libs,
         outputs: ~[driver::session::OutputDylib],
-        .. (*rustc::driver::session::basic_options()).clone()
+        .. (*driver::driver::build_session_options(@"rustdoc", &matches,
+                                                   @diagnostic::DefaultEmitter as
+                                                     @diagnostic::Emitter)).clone()
     };
 
 
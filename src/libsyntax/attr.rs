@@ -271,6 +271,32 @@ pub fn find_inline_attr(attrs: &[Attribute]) -> InlineAttr {
     })
 }
 
+#[deriving(Eq)]
+pub enum OptimizeAttr {
+    OptimizeNone,
+    OptimizeSize,
+    OptimizeMinSize,
+}
+
+/// The optimization goal given with #[optimize(size)] or
+/// #[optimize(min_size)], if any.
+pub fn find_optimize_attr(attrs: &[Attribute]) -> OptimizeAttr {
+    attrs.iter().fold(OptimizeNone, |oa, attr| {
+        match attr.node.value.node {
+          MetaList(n, ref items) if "optimize" == n => {
+            if contains_name(*items, "min_size") {
+                OptimizeMinSize
+            } else if contains_name(*items, "size") {
+                OptimizeSize
+            } else {
+                oa
+            }
+          }
+          _ => oa
+        }
+    })
+}
+
 /// Tests if any `cfg(...)` meta items in `metas` match `cfg`. e.g.
 ///
 /// test_cfg(`[foo="a", bar]`, `[cfg(foo), cfg(bar)]`) == true
@@ -9,10 +9,13 @@
 // except according to those terms.
 
 #include <stdio.h>
+#include <algorithm>
+#include <vector>
 
 #include "rustllvm.h"
 
 #include "llvm/Support/CBindingWrapping.h"
+#include "llvm/Support/ErrorHandling.h"
 #include "llvm/Target/TargetLibraryInfo.h"
 #include "llvm/Transforms/IPO/PassManagerBuilder.h"
 
@@ -60,6 +63,95 @@ LLVMRustAddPass(LLVMPassManagerRef PM, const char *PassName) {
     return false;
 }
 
+// The pass managers of `-Z verify-llvm-each-pass`. Every pass added to them
+// is followed by a verifier, which stops compilation with the name of the pass
+// as soon as the IR is found to be broken. Module verifiers are put between
+// the passes of the module pass manager, so its function passes are no longer
+// pipelined.
+namespace {
+    void reportBrokenModule(Module &M, const std::string &After) {
+        std::string Msg;
+        verifyModule(M, ReturnStatusAction, &Msg);
+        report_fatal_error("the LLVM IR is broken after the pass `" +
+                           Twine(After) + "`:\n" + Msg, false);
+    }
+
+    struct VerifyFunctionAfter : public FunctionPass {
+        static char ID;
+        std::string After;
+        std::string Name;
+        VerifyFunctionAfter(const char *after)
+            : FunctionPass(ID), After(after),
+              Name("Rust function verifier after `" + After + "`") {}
+        virtual const char *getPassName() const {
+            return Name.c_str();
+        }
+        virtual void getAnalysisUsage(AnalysisUsage &AU) const {
+            AU.setPreservesAll();
+        }
+        virtual bool runOnFunction(Function &F) {
+            if (verifyFunction(F, ReturnStatusAction))
+                reportBrokenModule(*F.getParent(), After);
+            return false;
+        }
+    };
+    char VerifyFunctionAfter::ID = 0;
+
+    struct VerifyModuleAfter : public ModulePass {
+        static char ID;
+        std::string After;
+        std::string Name;
+        VerifyModuleAfter(const char *after)
+            : ModulePass(ID), After(after),
+              Name("Rust module verifier after `" + After + "`") {}
+        virtual const char *getPassName() const {
+            return Name.c_str();
+        }
+        virtual void getAnalysisUsage(AnalysisUsage &AU) const {
+            AU.setPreservesAll();
+        }
+        virtual bool runOnModule(Module &M) {
+            if (verifyModule(M, ReturnStatusAction))
+                reportBrokenModule(M, After);
+            return false;
+        }
+    };
+    char VerifyModuleAfter::ID = 0;
+
+    class VerifyingPassManager : public PassManager {
+    public:
+        // The pass manager may delete the pass it's given, so its name is
+        // taken first
+        virtual void add(Pass *P) {
+            const char *Name = P->getPassName();
+            PassManager::add(P);
+            PassManager::add(new VerifyModuleAfter(Name));
+        }
+    };
+
+    class VerifyingFunctionPassManager : public FunctionPassManager {
+    public:
+        VerifyingFunctionPassManager(Module *M) : FunctionPassManager(M) {}
+        virtual void add(Pass *P) {
+            const char *Name = P->getPassName();
+            FunctionPassManager::add(P);
+            FunctionPassManager::add(new VerifyFunctionAfter(Name));
+        }
+    };
+}
+
+extern "C" LLVMPassManagerRef
+LLVMRustCreateVerifyingPassManager() {
+    PassManagerBase *PM = new VerifyingPassManager();
+    return wrap(PM);
+}
+
+extern "C" LLVMPassManagerRef
+LLVMRustCreateVerifyingFunctionPassManager(LLVMModuleRef M) {
+    PassManagerBase *PM = new VerifyingFunctionPassManager(unwrap(M));
+    return wrap(PM);
+}
+
 extern "C" LLVMTargetMachineRef
 LLVMRustCreateTargetMachine(const char *triple,
                             const char *cpu,
@@ -68,18 +160,21 @@ LLVMRustCreateTargetMachine(const char *triple,
                             Reloc::Model RM,
                             CodeGenOpt::Level OptLevel,
                             bool EnableSegmentedStacks,
-                            bool UseSoftFloat) {
+                            bool UseSoftFloat,
+                            bool NoFramePointerElim,
+                            bool FunctionSections,
+                            bool DataSections) {
     std::string Error;
     Triple Trip(Triple::normalize(triple));
     const llvm::Target *TheTarget = TargetRegistry::lookupTarget(Trip.getTriple(),
                                                                  Error);
     if (TheTarget == NULL) {
-        LLVMRustError = Error.c_str();
+        LLVMRustSetLastError(Error.c_str());
         return NULL;
     }
 
     TargetOptions Options;
-    Options.NoFramePointerElim = true;
+    Options.NoFramePointerElim = NoFramePointerElim;
     Options.EnableSegmentedStacks = EnableSegmentedStacks;
     Options.FloatABIType =
          (Trip.getEnvironment() == Triple::GNUEABIHF) ? FloatABI::Hard :
@@ -96,6 +191,8 @@ LLVMRustCreateTargetMachine(const char *triple,
                                                        RM,
                                                        CM,
                                                        OptLevel);
+    TM->setFunctionSections(FunctionSections);
+    TM->setDataSections(DataSections);
     return wrap(TM);
 }
 
@@ -116,20 +213,32 @@ LLVMRustAddAnalysisPasses(LLVMTargetMachineRef TM,
     unwrap(TM)->addAnalysisPasses(*PM);
 }
 
+// The library info of a target only depends on its triple, so it's created
+// once and then copied into each pass manager (builder), which takes
+// ownership of its copy.
+extern "C" TargetLibraryInfo*
+LLVMRustCreateTargetLibraryInfo(const char *triple) {
+    return new TargetLibraryInfo(Triple(triple));
+}
+
+extern "C" void
+LLVMRustDisposeTargetLibraryInfo(TargetLibraryInfo *TLI) {
+    delete TLI;
+}
+
 // Unfortunately, the LLVM C API doesn't provide a way to set the `LibraryInfo`
 // field of a PassManagerBuilder, we expose our own method of doing so.
 extern "C" void
-LLVMRustAddBuilderLibraryInfo(LLVMPassManagerBuilderRef PMB, LLVMModuleRef M) {
-    Triple TargetTriple(unwrap(M)->getTargetTriple());
-    unwrap(PMB)->LibraryInfo = new TargetLibraryInfo(TargetTriple);
+LLVMRustAddBuilderLibraryInfo(LLVMPassManagerBuilderRef PMB,
+                              TargetLibraryInfo *TLI) {
+    unwrap(PMB)->LibraryInfo = new TargetLibraryInfo(*TLI);
 }
 
 // Unfortunately, the LLVM C API doesn't provide a way to create the
 // TargetLibraryInfo pass, so we use this method to do so.
 extern "C" void
-LLVMRustAddLibraryInfo(LLVMPassManagerRef PMB, LLVMModuleRef M) {
-    Triple TargetTriple(unwrap(M)->getTargetTriple());
-    unwrap(PMB)->add(new TargetLibraryInfo(TargetTriple));
+LLVMRustAddLibraryInfo(LLVMPassManagerRef PMB, TargetLibraryInfo *TLI) {
+    unwrap(PMB)->add(new TargetLibraryInfo(*TLI));
 }
 
 // Unfortunately, the LLVM C API doesn't provide an easy way of iterating over
@@ -146,16 +255,45 @@ LLVMRustRunFunctionPassManager(LLVMPassManagerRef PM, LLVMModuleRef M) {
     P->doFinalization();
 }
 
-extern "C" void
+// Returns whether the options were set by this call, rather than by an
+// earlier one.
+extern "C" bool
 LLVMRustSetLLVMOptions(int Argc, char **Argv) {
     // Initializing the command-line options more than once is not allowed. So,
     // check if they've already been initialized.  (This could happen if we're
     // being called from rustpkg, for example). If the arguments change, then
     // that's just kinda unfortunate.
     static bool initialized = false;
-    if (initialized) return;
+    if (initialized) return false;
     initialized = true;
     cl::ParseCommandLineOptions(Argc, Argv);
+    return true;
+}
+
+// Applies a single option such as `-debug-pass=Arguments` after
+// `LLVMRustSetLLVMOptions`. `cl::ParseCommandLineOptions` exits the process
+// when an option's value is rejected, whereas this returns false, once LLVM
+// has printed why.
+extern "C" bool
+LLVMRustAddLLVMOption(const char *Arg) {
+    StringRef Name(Arg);
+    while (Name.startswith("-"))
+        Name = Name.substr(1);
+    StringRef Value;
+    size_t Eq = Name.find('=');
+    if (Eq != StringRef::npos) {
+        Value = Name.substr(Eq + 1);
+        Name = Name.substr(0, Eq);
+    }
+    StringMap<cl::Option*> Opts;
+    cl::getRegisteredOptions(Opts);
+    StringMap<cl::Option*>::iterator I = Opts.find(Name);
+    if (I == Opts.end())
+        return false;
+    cl::Option *O = I->getValue();
+    if (Eq != StringRef::npos && O->getValueExpectedFlag() == cl::ValueDisallowed)
+        return !O->error("does not allow a value! '" + Value + "' specified.");
+    return !O->addOccurrence(0, Name, Value);
 }
 
 extern "C" bool
@@ -163,18 +301,19 @@ LLVMRustWriteOutputFile(LLVMTargetMachineRef Target,
                         LLVMPassManagerRef PMR,
                         LLVMModuleRef M,
                         const char *path,
-                        TargetMachine::CodeGenFileType FileType) {
+                        TargetMachine::CodeGenFileType FileType,
+                        bool DisableVerify) {
   PassManager *PM = unwrap<PassManager>(PMR);
 
   std::string ErrorInfo;
   raw_fd_ostream OS(path, ErrorInfo, sys::fs::F_Binary);
   if (ErrorInfo != "") {
-    LLVMRustError = ErrorInfo.c_str();
+    LLVMRustSetLastError(ErrorInfo.c_str());
     return false;
   }
   formatted_raw_ostream FOS(OS);
 
-  unwrap(Target)->addPassesToEmitFile(*PM, FOS, FileType, false);
+  unwrap(Target)->addPassesToEmitFile(*PM, FOS, FileType, DisableVerify);
   PM->run(*unwrap(M));
   return true;
 }
@@ -207,6 +346,39 @@ LLVMRustPrintPasses() {
     PR->enumerateWith(&listener);
 }
 
+extern "C" bool
+LLVMRustHasPass(const char *PassName) {
+    LLVMInitializePasses();
+    PassRegistry *PR = PassRegistry::getPassRegistry();
+    return PR->getPassInfo(StringRef(PassName)) != NULL;
+}
+
+// The options of `LLVMRustSetLLVMOptions`, which are registered when LLVM is
+// loaded, so they can be looked at before it's been initialized.
+extern "C" bool
+LLVMRustHasLLVMOption(const char *Name) {
+    StringMap<cl::Option*> Opts;
+    cl::getRegisteredOptions(Opts);
+    return Opts.count(Name) != 0;
+}
+
+extern "C" void
+LLVMRustPrintLLVMOptions() {
+    StringMap<cl::Option*> Opts;
+    cl::getRegisteredOptions(Opts);
+    std::vector<std::string> Names;
+    for (StringMap<cl::Option*>::iterator I = Opts.begin(), E = Opts.end();
+         I != E; ++I) {
+        if (!I->getKey().empty())
+            Names.push_back(I->getKey().str());
+    }
+    std::sort(Names.begin(), Names.end());
+    for (std::vector<std::string>::iterator I = Names.begin(), E = Names.end();
+         I != E; ++I) {
+        printf("%30s - %s\n", ("-" + *I).c_str(), Opts[*I]->HelpStr);
+    }
+}
+
 extern "C" void
 LLVMRustAddAlwaysInlinePass(LLVMPassManagerBuilderRef PMB, bool AddLifetimes) {
     unwrap(PMB)->Inliner = createAlwaysInlinerPass(AddLifetimes);
@@ -20,18 +20,29 @@
 using namespace llvm;
 using namespace llvm::sys;
 
-const char *LLVMRustError;
+// A copy of the last error message of each thread. The metadata module is
+// written out on a thread of its own while the crate's module is generated.
+static ThreadLocal<char> LastError;
+
+void LLVMRustSetLastError(const char *Err) {
+  free(LastError.get());
+  LastError.set(strdup(Err));
+}
 
 extern "C" LLVMMemoryBufferRef
 LLVMRustCreateMemoryBufferWithContentsOfFile(const char *Path) {
   LLVMMemoryBufferRef MemBuf = NULL;
-  LLVMCreateMemoryBufferWithContentsOfFile(Path, &MemBuf,
-    const_cast<char **>(&LLVMRustError));
+  char *Err = NULL;
+  LLVMCreateMemoryBufferWithContentsOfFile(Path, &MemBuf, &Err);
+  if (Err != NULL) {
+    LLVMRustSetLastError(Err);
+    LLVMDisposeMessage(Err);
+  }
   return MemBuf;
 }
 
 extern "C" const char *LLVMRustGetLastError(void) {
-  return LLVMRustError;
+  return LastError.get();
 }
 
 extern "C" void
@@ -59,6 +70,18 @@ extern "C" void LLVMRustPrintPassTimings() {
   TimerGroup::printAll(OS);
 }
 
+// Appends the pass timings and the statistics counters to a file rather than
+// stderr. The counters are only kept by LLVM builds with assertions enabled.
+extern "C" bool LLVMRustWritePassTimingsAndStats(const char *Path) {
+  std::string ErrorInfo;
+  raw_fd_ostream OS(Path, ErrorInfo, sys::fs::F_Append);
+  if (ErrorInfo != "")
+    return false;
+  TimerGroup::printAll(OS);
+  PrintStatistics(OS);
+  return true;
+}
+
 extern "C" LLVMValueRef LLVMGetOrInsertFunction(LLVMModuleRef M,
                                                 const char* Name,
                                                 LLVMTypeRef FunctionTy) {
@@ -94,6 +117,16 @@ extern "C" void LLVMAddColdAttribute(LLVMValueRef Fn) {
   A->addAttribute(AttributeSet::FunctionIndex, Attribute::Cold);
 }
 
+extern "C" void LLVMAddMinSizeAttribute(LLVMValueRef Fn) {
+  Function *A = unwrap<Function>(Fn);
+  A->addAttribute(AttributeSet::FunctionIndex, Attribute::MinSize);
+}
+
+extern "C" void LLVMAddStackProtectStrongAttribute(LLVMValueRef Fn) {
+  Function *A = unwrap<Function>(Fn);
+  A->addAttribute(AttributeSet::FunctionIndex, Attribute::StackProtectStrong);
+}
+
 extern "C" LLVMValueRef LLVMBuildAtomicLoad(LLVMBuilderRef B,
                                             LLVMValueRef source,
                                             const char* Name,
@@ -540,6 +573,11 @@ extern "C" char *LLVMTypeToString(LLVMTypeRef Type) {
     return strdup(os.str().data());
 }
 
+extern "C" void
+LLVMRustAddModuleFlag(LLVMModuleRef M, const char *Name, uint32_t Value) {
+    unwrap(M)->addModuleFlag(Module::Warning, Name, Value);
+}
+
 extern "C" bool
 LLVMRustLinkInExternalBitcode(LLVMModuleRef dst, char *bc, size_t len) {
     Module *Dst = unwrap(dst);
@@ -547,13 +585,13 @@ LLVMRustLinkInExternalBitcode(LLVMModuleRef dst, char *bc, size_t len) {
     std::string Err;
     Module *Src = llvm::getLazyBitcodeModule(buf, Dst->getContext(), &Err);
     if (Src == NULL) {
-        LLVMRustError = Err.c_str();
+        LLVMRustSetLastError(Err.c_str());
         delete buf;
         return false;
     }
 
     if (Linker::LinkModules(Dst, Src, Linker::DestroySource, &Err)) {
-        LLVMRustError = Err.c_str();
+        LLVMRustSetLastError(Err.c_str());
         return false;
     }
     return true;
@@ -0,0 +1,22 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[pkgid="symbol_hash_a#0.1"];
+
+// A type of the same name and path as the one in symbol-hash-b.rs, but of a
+// different size
+
+pub struct S { x: int }
+
+pub fn replaces() -> bool {
+    let mut s = S { x: 1 };
+    let old = std::util::replace(&mut s, S { x: 2 });
+    old.x == 1 && s.x == 2
+}
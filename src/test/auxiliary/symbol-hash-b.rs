@@ -0,0 +1,22 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[pkgid="symbol_hash_b#0.1"];
+
+// A type of the same name and path as the one in symbol-hash-a.rs, but of a
+// different size
+
+pub struct S { x: int, y: int, z: int }
+
+pub fn replaces() -> bool {
+    let mut s = S { x: 1, y: 2, z: 3 };
+    let old = std::util::replace(&mut s, S { x: 4, y: 5, z: 6 });
+    old.x == 1 && old.y == 2 && old.z == 3 && s.x == 4 && s.y == 5 && s.z == 6
+}
@@ -0,0 +1,20 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// xfail-fast aux-build
+// aux-build:duplicate_symbol_lib.rs
+
+extern mod duplicate_symbol_lib;
+
+#[no_mangle]
+pub extern "C" fn rust_duplicate_symbol() {}
+//~^ ERROR symbol `rust_duplicate_symbol` is already defined in crate `duplicate_symbol_lib`
+
+fn main() {}
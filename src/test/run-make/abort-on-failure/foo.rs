@@ -0,0 +1,13 @@
+struct Noisy;
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        println!("unwound");
+    }
+}
+
+fn main() {
+    let _n = Noisy;
+    let v: ~[uint] = ~[];
+    println!("{}", v[std::os::args().len()]);
+}
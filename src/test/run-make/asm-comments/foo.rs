@@ -0,0 +1,9 @@
+#[no_mangle]
+pub fn answer() -> uint {
+    let answer = 6 * 7;
+    answer
+}
+
+fn main() {
+    assert_eq!(answer(), 42);
+}
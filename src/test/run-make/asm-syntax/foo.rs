@@ -0,0 +1,4 @@
+fn main() {
+    let v = ~[1u64, 2, 3];
+    assert_eq!(v.len(), 3);
+}
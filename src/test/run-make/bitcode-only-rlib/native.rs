@@ -0,0 +1,8 @@
+#[crate_type = "rlib"];
+
+#[link(name = "bar", kind = "static")]
+extern {
+    fn bar() -> i32;
+}
+
+pub fn call_bar() -> i32 { unsafe { bar() } }
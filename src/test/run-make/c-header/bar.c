@@ -0,0 +1,6 @@
+#include "foo.h"
+
+int main() {
+    foo_reset();
+    return foo_add(1, -1) + (FOO_NAME != 0);
+}
@@ -0,0 +1,21 @@
+#[crate_type = "staticlib"];
+
+#[no_mangle]
+pub static FOO_NAME: *u8 = 0 as *u8;
+
+#[no_mangle]
+pub extern "C" fn foo_add(a: i32, b: i32) -> i32 { a + b }
+
+#[no_mangle]
+pub extern "C" fn foo_reset() {}
+
+#[no_mangle]
+pub extern "C" fn foo_count(_a: **u8, _b: **mut u8) -> uint { 0 }
+
+#[no_mangle]
+pub extern "C" fn foo_swap(_p: *mut *i32) {}
+
+pub fn bar() {}
+
+// Its symbol is mangled, so it can't be declared under this name
+pub extern "C" fn mangled() {}
@@ -0,0 +1,8 @@
+#[crate_type = "cdylib"];
+
+extern mod bar;
+
+#[no_mangle]
+pub extern "C" fn foo() -> int {
+    bar::bar()
+}
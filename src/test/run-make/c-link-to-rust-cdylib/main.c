@@ -0,0 +1,7 @@
+#include <stdint.h>
+
+intptr_t foo();
+
+int main() {
+    return foo() == 1 ? 0 : 1;
+}
@@ -0,0 +1,8 @@
+#[link(name = "thisdoesnotexist")]
+extern {
+    fn foo();
+}
+
+fn main() {
+    unsafe { foo() }
+}
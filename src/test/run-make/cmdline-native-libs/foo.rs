@@ -0,0 +1,7 @@
+extern {
+    fn foo();
+}
+
+fn main() {
+    unsafe { foo(); }
+}
@@ -0,0 +1,8 @@
+fn main() {
+    let x = 1;
+    assert_eq!(x, 1);
+    {
+        let y = x + 1;
+        assert_eq!(y, 2);
+    }
+}
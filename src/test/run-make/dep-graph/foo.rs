@@ -0,0 +1,4 @@
+#[crate_type = "rlib"];
+#[pkgid = "foo#1.2"];
+
+pub fn foo() {}
@@ -0,0 +1,5 @@
+#[crate_type = "rlib"];
+
+extern mod foo;
+
+pub fn bar() { foo::foo() }
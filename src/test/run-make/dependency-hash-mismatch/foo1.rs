@@ -0,0 +1,4 @@
+#[pkgid = "foo#0.1"];
+#[crate_type = "rlib"];
+
+pub fn foo() {}
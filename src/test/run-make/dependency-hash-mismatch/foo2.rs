@@ -0,0 +1,4 @@
+#[pkgid = "foo#0.2"];
+#[crate_type = "rlib"];
+
+pub fn foo() {}
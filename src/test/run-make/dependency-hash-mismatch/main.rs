@@ -0,0 +1,3 @@
+extern mod bar;
+
+fn main() { bar::bar() }
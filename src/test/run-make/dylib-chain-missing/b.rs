@@ -0,0 +1,3 @@
+extern mod a;
+
+pub fn b() { a::a() }
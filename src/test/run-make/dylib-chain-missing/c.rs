@@ -0,0 +1,3 @@
+extern mod b;
+
+pub fn c() { b::b() }
@@ -0,0 +1,5 @@
+extern mod c;
+
+fn main() {
+    c::c();
+}
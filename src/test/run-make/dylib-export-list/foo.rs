@@ -0,0 +1,13 @@
+#[crate_type = "dylib"];
+
+pub fn exported() {
+    private();
+}
+
+#[inline(never)]
+fn private() {}
+
+#[no_mangle]
+pub extern "C" fn exported_c_function() {
+    private();
+}
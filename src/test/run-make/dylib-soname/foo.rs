@@ -0,0 +1,3 @@
+#[crate_type = "dylib"];
+
+pub fn foo() {}
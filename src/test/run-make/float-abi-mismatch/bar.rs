@@ -0,0 +1,5 @@
+extern mod foo;
+
+fn main() {
+    foo::foo();
+}
@@ -0,0 +1,6 @@
+#[deny(attribute_usage)];
+
+#[optimize(speed)]
+fn f() {}
+
+fn main() { f() }
@@ -0,0 +1,5 @@
+extern mod lib;
+
+fn main() {
+    println!("{}", lib::never_inlined(std::os::args().len()));
+}
@@ -0,0 +1,15 @@
+#[optimize(size)]
+pub fn small() -> uint { 1 }
+
+#[optimize(min_size)]
+pub fn smallest() -> uint { 2 }
+
+#[cold]
+pub fn rarely_called() -> uint { 3 }
+
+#[inline(never)]
+pub fn never_inlined() -> uint { 4 }
+
+fn main() {
+    println!("{}", small() + smallest() + rarely_called() + never_inlined());
+}
@@ -0,0 +1,4 @@
+#[crate_type = "rlib"];
+
+#[inline(never)]
+pub fn never_inlined(a: uint) -> uint { a * 2 + 1 }
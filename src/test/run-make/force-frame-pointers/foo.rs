@@ -0,0 +1,10 @@
+#[crate_type = "lib"];
+
+#[inline(never)]
+pub fn add(a: &[uint]) -> uint {
+    let mut sum = 0;
+    for x in a.iter() {
+        sum += *x;
+    }
+    sum
+}
@@ -0,0 +1,8 @@
+#[inline(never)]
+pub fn never_called() -> uint {
+    42
+}
+
+fn main() {
+    println!("hello");
+}
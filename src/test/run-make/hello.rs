@@ -0,0 +1,7 @@
+// A program for the tests which only look at how a crate is built; its
+// pkgid keeps the outputs named `foo` wherever it's built from
+#[pkgid = "foo"];
+
+fn main() {
+    println!("hello");
+}
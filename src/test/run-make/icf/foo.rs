@@ -0,0 +1,14 @@
+// Both instantiations have the same code, so the linker can fold them
+#[inline(never)]
+fn sum<T: Clone>(xs: &[T], f: |T| -> uint) -> uint {
+    let mut n = 0;
+    for x in xs.iter() {
+        n += f(x.clone());
+    }
+    n
+}
+
+fn main() {
+    assert_eq!(sum([1u, 2, 3], |x| x), 6);
+    assert_eq!(sum([1i, 2, 3], |x| x as uint), 6);
+}
@@ -0,0 +1,20 @@
+#[crate_type = "rlib"];
+
+#[cfg(not(changed_signature), not(changed_body))]
+pub fn foo(x: int) -> int { x + 1 }
+
+#[cfg(changed_body)]
+pub fn foo(x: int) -> int { x + 2 }
+
+#[cfg(changed_signature)]
+pub fn foo(x: uint) -> uint { x + 1 }
+
+pub mod m {
+    #[cfg(not(changed_private_body))]
+    fn private() -> int { 1 }
+
+    #[cfg(changed_private_body)]
+    fn private() -> int { 2 }
+
+    pub fn bar() -> int { private() }
+}
@@ -0,0 +1,30 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::libc::c_int;
+
+extern {
+    fn shim() -> c_int;
+    #[cfg(cfoo)]
+    fn foo() -> c_int;
+}
+
+#[cfg(cfoo)]
+fn check_foo() {
+    unsafe { assert_eq!(foo(), 4); }
+}
+
+#[cfg(not(cfoo))]
+fn check_foo() {}
+
+fn main() {
+    unsafe { assert_eq!(shim(), 3); }
+    check_foo();
+}
@@ -0,0 +1 @@
+int shim() { return 3; }
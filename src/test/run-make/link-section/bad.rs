@@ -0,0 +1,4 @@
+#[link_section = ".note.rustc"]
+pub static METADATA: u8 = 0;
+
+fn main() {}
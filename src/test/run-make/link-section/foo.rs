@@ -0,0 +1,7 @@
+#[link_section = ".rust_vectors"]
+static VECTORS: [u32, ..2] = [0xdead, 0xbeef];
+
+#[link_section = ".rust_text_cold"]
+fn never_called() {}
+
+fn main() {}
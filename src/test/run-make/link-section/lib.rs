@@ -0,0 +1,4 @@
+#[crate_type = "rlib"];
+
+#[link_section = ".rust_lib_info"]
+static INFO: [u8, ..4] = [1, 2, 3, 4];
@@ -0,0 +1,4 @@
+#!/bin/sh
+# Records the command it's given, then runs it
+echo "$@" > "$(dirname "$0")/wrapped"
+exec "$@"
@@ -0,0 +1,12 @@
+extern {
+    #[linkage = "weak"]
+    static a: i32;
+}
+
+#[linkage = "external_weak"]
+pub static b: i32 = 0;
+
+#[linkage = "common"]
+pub fn c() {}
+
+fn main() {}
@@ -0,0 +1,5 @@
+int overridable(void) {
+    return 2;
+}
+
+void bar_init(void) {}
@@ -0,0 +1,23 @@
+#[link(name = "bar", kind = "static")]
+extern {
+    fn bar_init();
+    #[linkage = "external_weak"]
+    static not_defined_anywhere: i32;
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+pub extern "C" fn overridable() -> i32 { 1 }
+
+#[no_mangle]
+#[linkage = "weak"]
+pub extern "C" fn not_overridden() -> i32 { 3 }
+
+fn main() {
+    unsafe {
+        bar_init();
+        assert!((&not_defined_anywhere as *i32).is_null());
+    }
+    assert_eq!(overridable(), 2);
+    assert_eq!(not_overridden(), 3);
+}
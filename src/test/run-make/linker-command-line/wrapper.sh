@@ -0,0 +1,8 @@
+#!/bin/sh
+# Records its arguments one per line, then links without the leading two given
+# with --linker, if there are any
+printf '%s\n' "$@" > "$(dirname "$0")/args"
+if [ "$1" = "--marker" ]; then
+    shift 2
+fi
+exec cc "$@"
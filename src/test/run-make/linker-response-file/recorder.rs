@@ -0,0 +1,20 @@
+// Records the arguments it's run with, one per line, in the file named by
+// $RECORD, followed by the contents of the response files among them
+use std::io::fs::File;
+use std::os;
+
+fn main() {
+    let args = os::args();
+    let record = os::getenv("RECORD").expect("RECORD names the record");
+    let mut record = File::create(&Path::new(record));
+    for arg in args.slice_from(1).iter() {
+        record.write(arg.as_bytes());
+        record.write(bytes!("\n"));
+    }
+    for arg in args.slice_from(1).iter() {
+        if arg.starts_with("@") {
+            let contents = File::open(&Path::new(arg.slice_from(1))).read_to_end();
+            record.write(contents);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+#[crate_type = "rlib"];
+#[pkgid = "foo#1.2"];
+
+#[link(name = "m")]
+extern {}
+
+pub fn foo() {}
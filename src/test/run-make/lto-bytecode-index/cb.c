@@ -0,0 +1,5 @@
+int answer(void);
+
+int call_answer(void) {
+    return answer();
+}
@@ -0,0 +1,5 @@
+#[crate_type = "rlib"];
+
+// Only ever called from the C code bundled into `native`
+#[no_mangle]
+pub extern "C" fn answer() -> i32 { 42 }
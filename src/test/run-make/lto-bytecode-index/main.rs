@@ -0,0 +1,5 @@
+extern mod native;
+
+fn main() {
+    assert_eq!(native::call(), 42);
+}
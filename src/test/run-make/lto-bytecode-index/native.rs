@@ -0,0 +1,10 @@
+#[crate_type = "rlib"];
+
+extern mod lib;
+
+#[link(name = "cb", kind = "static")]
+extern {
+    fn call_answer() -> i32;
+}
+
+pub fn call() -> i32 { unsafe { call_answer() } }
@@ -0,0 +1,7 @@
+#[crate_type = "rlib"];
+
+#[no_mangle]
+pub extern "C" fn kept() -> int { 1 }
+
+#[no_mangle]
+pub extern "C" fn dropped() -> int { 2 }
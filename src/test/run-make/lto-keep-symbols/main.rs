@@ -0,0 +1,3 @@
+extern mod lib;
+
+fn main() {}
@@ -0,0 +1,6 @@
+extern mod dep;
+extern mod lib;
+
+fn main() {
+    assert_eq!(dep::foo() + lib::bar(), 3);
+}
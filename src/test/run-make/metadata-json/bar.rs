@@ -0,0 +1,28 @@
+#[crate_type = "rlib"];
+
+extern mod foo;
+
+#[link(name = "m")]
+extern {}
+
+pub mod baz {
+    pub fn quux(x: int) -> int { ::foo::foo(); x }
+    fn private() {}
+}
+
+pub struct Point { x: int, y: int }
+
+impl Point {
+    pub fn origin() -> Point { Point { x: 0, y: 0 } }
+    fn hidden(&self) {}
+}
+
+pub trait Shape {
+    fn area(&self) -> int;
+}
+
+impl Shape for Point {
+    fn area(&self) -> int { 0 }
+}
+
+pub static ANSWER: uint = 42;
@@ -0,0 +1,3 @@
+extern mod foo;
+
+fn main() { foo::foo() }
@@ -0,0 +1,3 @@
+extern mod foo;
+
+pub fn bar() -> int { foo::foo() + 1 }
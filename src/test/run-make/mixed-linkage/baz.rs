@@ -0,0 +1 @@
+pub fn baz() -> int { 3 }
@@ -0,0 +1,7 @@
+extern mod bar;
+extern mod baz;
+
+fn main() {
+    assert_eq!(bar::bar(), 2);
+    assert_eq!(baz::baz(), 3);
+}
@@ -0,0 +1,7 @@
+extern mod foo1 = "foo#0.1";
+extern mod foo2 = "foo#0.2";
+
+fn main() {
+    assert_eq!(foo1::f(), 1);
+    assert_eq!(foo2::f(), 2);
+}
@@ -0,0 +1,3 @@
+#[pkgid = "foo#0.9"];
+
+pub fn foo() {}
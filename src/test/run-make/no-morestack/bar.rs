@@ -0,0 +1,7 @@
+fn bar(v: &[uint]) -> uint {
+    v.iter().fold(0, |a, &b| a + b)
+}
+
+fn main() {
+    println!("{}", bar([1, 2, 3]));
+}
@@ -0,0 +1,5 @@
+#[no_split_stack];
+
+fn main() {
+    println!("hello");
+}
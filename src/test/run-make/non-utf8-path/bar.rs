@@ -0,0 +1 @@
+pub fn bar() -> int { 3 }
@@ -0,0 +1,5 @@
+extern mod bar;
+
+fn main() {
+    assert_eq!(bar::bar(), 3);
+}
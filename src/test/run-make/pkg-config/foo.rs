@@ -0,0 +1,16 @@
+#[pkgid = "foo#0.9"];
+
+#[link(name = "m")]
+extern {
+    fn cos(x: f64) -> f64;
+}
+
+#[link(name = "m")]
+extern {
+    fn sin(x: f64) -> f64;
+}
+
+#[no_mangle]
+pub extern "C" fn foo(x: f64) -> f64 {
+    unsafe { cos(x) + sin(x) }
+}
@@ -0,0 +1,7 @@
+extern mod bar;
+extern mod baz;
+
+fn main() {
+    bar::bar();
+    baz::baz();
+}
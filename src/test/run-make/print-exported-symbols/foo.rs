@@ -0,0 +1,10 @@
+#[crate_type = "dylib"];
+
+pub fn exported() {}
+
+fn private() {}
+
+#[no_mangle]
+pub extern "C" fn exported_c_function() {
+    private();
+}
@@ -0,0 +1,9 @@
+#[inline(never)]
+fn chosen() -> uint { 1 }
+
+#[inline(never)]
+fn ignored() -> uint { 2 }
+
+fn main() {
+    assert_eq!(chosen() + ignored(), 3);
+}
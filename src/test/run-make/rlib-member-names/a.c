@@ -0,0 +1 @@
+int util_a() { return 1; }
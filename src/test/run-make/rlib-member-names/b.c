@@ -0,0 +1 @@
+int util_b() { return 2; }
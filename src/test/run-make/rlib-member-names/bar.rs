@@ -0,0 +1,5 @@
+extern mod foo;
+
+fn main() {
+    assert_eq!(foo::sum(), 3);
+}
@@ -0,0 +1,10 @@
+#[crate_type = "rlib"];
+
+extern {
+    fn util_a() -> i32;
+    fn util_b() -> i32;
+}
+
+pub fn sum() -> i32 {
+    unsafe { util_a() + util_b() }
+}
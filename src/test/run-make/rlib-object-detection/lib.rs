@@ -0,0 +1,9 @@
+#[crate_type = "rlib"];
+
+use std::libc::c_int;
+
+extern {
+    fn shim() -> c_int;
+}
+
+pub fn call_shim() -> c_int { unsafe { shim() } }
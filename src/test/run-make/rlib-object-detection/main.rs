@@ -0,0 +1,5 @@
+extern mod lib;
+
+fn main() {
+    assert_eq!(lib::call_shim(), 3);
+}
@@ -0,0 +1,2 @@
+#[cfg(from_env)]
+fn main() {}
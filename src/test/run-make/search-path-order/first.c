@@ -0,0 +1 @@
+int which() { return 1; }
@@ -0,0 +1,8 @@
+#[link(name = "which", kind = "static")]
+extern {
+    fn which() -> i32;
+}
+
+fn main() {
+    assert_eq!(unsafe { which() }, 1);
+}
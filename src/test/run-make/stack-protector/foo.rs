@@ -0,0 +1,14 @@
+fn fill(buf: &mut [u8]) {
+    for b in buf.mut_iter() {
+        *b = 1;
+    }
+}
+
+fn main() {
+    let mut buf = [0u8, ..64];
+    fill(buf);
+    assert_eq!(buf[63], 1);
+    // Has drop glue of its own
+    let boxed = ~[~1u8, ~2u8];
+    assert_eq!(*boxed[1], 2);
+}
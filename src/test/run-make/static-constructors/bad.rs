@@ -0,0 +1,10 @@
+#[static_constructor]
+fn not_extern() {}
+
+#[static_constructor]
+extern fn with_args(_: int) {}
+
+#[static_constructor = "100"]
+extern fn reserved() {}
+
+fn main() {}
@@ -0,0 +1,20 @@
+extern mod lib;
+
+use std::libc;
+
+fn say(s: &str) {
+    s.with_c_str(|s| unsafe { libc::puts(s); });
+}
+
+#[static_constructor]
+extern fn init() { say("init"); }
+
+#[static_constructor = "101"]
+extern fn first() { say("first"); }
+
+#[static_destructor]
+extern fn fini() { say("fini"); }
+
+fn main() {
+    say("main");
+}
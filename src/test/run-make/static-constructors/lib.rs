@@ -0,0 +1,8 @@
+#[crate_type = "rlib"];
+
+use std::libc;
+
+#[static_constructor]
+extern fn lib_init() {
+    "lib init".with_c_str(|s| unsafe { libc::puts(s); });
+}
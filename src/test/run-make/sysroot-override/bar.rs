@@ -0,0 +1,4 @@
+#[no_std];
+#[crate_type = "rlib"];
+
+pub fn bar() {}
@@ -0,0 +1,6 @@
+#[no_std];
+#[crate_type = "rlib"];
+
+extern mod bar;
+
+pub fn foo() { bar::bar() }
@@ -0,0 +1,10 @@
+#[crate_type = "rlib"];
+
+#[link(name = "cfoo", kind = "static")]
+extern {
+    fn foo() -> int;
+}
+
+pub fn rsfoo() -> int {
+    unsafe { foo() }
+}
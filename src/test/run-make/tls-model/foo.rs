@@ -0,0 +1,11 @@
+#[feature(thread_local)];
+
+#[thread_local]
+static mut COUNT: uint = 0;
+
+fn main() {
+    unsafe {
+        COUNT += 1;
+        assert_eq!(COUNT, 1);
+    }
+}
@@ -1,6 +1,9 @@
 RUSTC := $(RUSTC) --out-dir $(TMPDIR) -L $(TMPDIR)
 CC := $(CC) -L $(TMPDIR)
 
+# The program shared by the tests which only look at how a crate is built
+HELLO = ../hello.rs
+
 RUN = $(TMPDIR)/$(1)
 FAILS = $(TMPDIR)/$(1) && exit 1 || exit 0
 
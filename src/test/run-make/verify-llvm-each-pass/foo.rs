@@ -0,0 +1,7 @@
+fn sum(v: &[int]) -> int {
+    v.iter().fold(0, |a, &b| a + b)
+}
+
+fn main() {
+    assert_eq!(sum([1, 2, 3]), 6);
+}
@@ -0,0 +1,4 @@
+#[link(name = "CoreFoundation", kind = "framework,weak")]
+extern {}
+
+fn main() {}
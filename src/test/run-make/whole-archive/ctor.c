@@ -0,0 +1,6 @@
+extern void set_initialized(void);
+
+__attribute__((constructor))
+static void init(void) {
+    set_initialized();
+}
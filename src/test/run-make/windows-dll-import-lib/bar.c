@@ -0,0 +1,5 @@
+int foo_exported();
+
+int main() {
+    return foo_exported() == 3 ? 0 : 1;
+}
@@ -0,0 +1,4 @@
+#[crate_type = "cdylib"];
+
+#[no_mangle]
+pub extern "C" fn foo_exported() -> int { 3 }
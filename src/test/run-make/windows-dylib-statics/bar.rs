@@ -0,0 +1,8 @@
+extern mod foo;
+
+fn main() {
+    assert_eq!(foo::ANSWER, 42);
+    foo::bump();
+    foo::bump();
+    unsafe { assert_eq!(foo::COUNT, 2); }
+}
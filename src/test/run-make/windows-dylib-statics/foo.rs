@@ -0,0 +1,8 @@
+#[crate_type = "dylib"];
+
+pub static ANSWER: int = 42;
+pub static mut COUNT: int = 0;
+
+pub fn bump() {
+    unsafe { COUNT += 1; }
+}
@@ -0,0 +1,25 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::libc;
+
+#[cfg(unix)]
+#[link(name = "m", kind = "dylib")]
+extern {
+    fn floor(x: libc::c_double) -> libc::c_double;
+}
+
+#[cfg(unix)]
+pub fn main() {
+    unsafe { assert_eq!(floor(1.5), 1.0); }
+}
+
+#[cfg(not(unix))]
+pub fn main() {}
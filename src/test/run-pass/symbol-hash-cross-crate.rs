@@ -0,0 +1,28 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// xfail-fast
+// aux-build:symbol-hash-a.rs
+// aux-build:symbol-hash-b.rs
+
+// Both crates instantiate std::util::replace with a type which prints as
+// `S`, which must still get symbols of their own when they're linked
+// together.
+
+extern mod symbol_hash_a;
+extern mod symbol_hash_b;
+
+pub fn main() {
+    assert!(symbol_hash_a::replaces());
+    assert!(symbol_hash_b::replaces());
+    let mut a = symbol_hash_a::S { x: 1 };
+    std::util::replace(&mut a, symbol_hash_a::S { x: 2 });
+    assert_eq!(a.x, 2);
+}
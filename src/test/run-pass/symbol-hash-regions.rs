@@ -0,0 +1,21 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Items whose types mention regions must still get a symbol hash.
+
+pub fn f(x: &int) -> int { *x }
+
+pub fn g<'a>(x: &'a int, y: &'a int) -> &'a int { if *x > *y { x } else { y } }
+
+pub fn main() {
+    let (a, b) = (1, 2);
+    assert_eq!(f(&a), 1);
+    assert_eq!(*g(&a, &b), 2);
+}